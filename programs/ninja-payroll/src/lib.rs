@@ -18,11 +18,17 @@ pub mod ninja_payroll {
     use super::*;
 
     /// Initialize a new payroll batch
+    ///
+    /// The batch account can be closed `retention_period` seconds after it is
+    /// finalized or cancelled.
     pub fn initialize_batch(
         ctx: Context<InitializeBatch>,
         batch_id: u64,
         total_recipients: u16,
+        retention_period: i64,
     ) -> Result<()> {
+        require!(retention_period >= 0, PayrollError::InvalidRetention);
+
         let batch = &mut ctx.accounts.batch;
         batch.authority = ctx.accounts.authority.key();
         batch.batch_id = batch_id;
@@ -32,7 +38,17 @@ pub mod ninja_payroll {
         batch.status = BatchStatus::Initialized;
         batch.created_at = Clock::get()?.unix_timestamp;
         batch.bump = ctx.bumps.batch;
-
+        batch.payer = ctx.accounts.payer.key();
+        batch.retention_period = retention_period;
+        batch.paid_recipients = vec![0; PayrollBatch::bitmap_len(total_recipients)];
+
+        emit!(BatchInitialized {
+            batch: batch.key(),
+            batch_id,
+            authority: batch.authority,
+            total_recipients,
+            timestamp: batch.created_at,
+        });
         msg!("Batch {} initialized for {} recipients", batch_id, total_recipients);
         Ok(())
     }
@@ -68,6 +84,9 @@ pub mod ninja_payroll {
     }
 
     /// Process a single payment in the batch (executed in ephemeral rollup)
+    ///
+    /// Each recipient index can be paid once, so `processed_count` reaching
+    /// `total_recipients` means every recipient was paid.
     pub fn process_payment(
         ctx: Context<ProcessPayment>,
         amount: u64,
@@ -80,10 +99,7 @@ pub mod ninja_payroll {
             PayrollError::InvalidBatchStatus
         );
 
-        require!(
-            recipient_index < batch.total_recipients,
-            PayrollError::InvalidRecipientIndex
-        );
+        batch.mark_paid(recipient_index)?;
 
         // Execute SPL token transfer at high speed in ephemeral rollup
         let cpi_accounts = Transfer {
@@ -102,6 +118,14 @@ pub mod ninja_payroll {
         batch.total_amount += amount;
         batch.status = BatchStatus::Processing;
 
+        emit!(PaymentProcessed {
+            batch: batch.key(),
+            batch_id: batch.batch_id,
+            recipient_index,
+            recipient_token: ctx.accounts.recipient_token.key(),
+            amount,
+            processed_count: batch.processed_count,
+        });
         msg!(
             "Payment {} of {}: {} tokens to recipient index {}",
             batch.processed_count,
@@ -145,9 +169,15 @@ pub mod ninja_payroll {
             PayrollError::IncompletePayments
         );
 
-        batch.status = BatchStatus::Finalized;
-        batch.finalized_at = Some(Clock::get()?.unix_timestamp);
+        let finalized_at = batch.settle(BatchStatus::Finalized, Clock::get()?.unix_timestamp)?;
 
+        emit!(BatchFinalized {
+            batch: batch.key(),
+            batch_id: batch.batch_id,
+            processed_count: batch.processed_count,
+            total_amount: batch.total_amount,
+            timestamp: finalized_at,
+        });
         msg!(
             "Batch {} finalized: {} payments totaling {} tokens",
             batch.batch_id,
@@ -160,25 +190,52 @@ pub mod ninja_payroll {
 
     /// Cancel batch and undelegate (emergency use)
     /// Note: Undelegation must be done separately via process_undelegation instruction
+    ///
+    /// Only a batch that is still open can be cancelled, so the retention
+    /// period of a finalized or cancelled batch cannot be restarted.
     pub fn cancel_batch(ctx: Context<CancelBatch>) -> Result<()> {
         let batch = &mut ctx.accounts.batch;
-        batch.status = BatchStatus::Cancelled;
-
+        let cancelled_at = batch.settle(BatchStatus::Cancelled, Clock::get()?.unix_timestamp)?;
+
+        emit!(BatchCancelled {
+            batch: batch.key(),
+            batch_id: batch.batch_id,
+            processed_count: batch.processed_count,
+            total_amount: batch.total_amount,
+            timestamp: cancelled_at,
+        });
         msg!("Batch {} cancelled", batch.batch_id);
         msg!("Call process_undelegation to undelegate and retrieve final state");
         Ok(())
     }
+
+    /// Close a finalized or cancelled batch once its retention period has
+    /// passed, returning its rent to whoever paid for it
+    ///
+    /// Anyone may call this, so the service can sweep old batches. The batch
+    /// must have been undelegated first.
+    pub fn close_batch(ctx: Context<CloseBatch>) -> Result<()> {
+        let batch = &ctx.accounts.batch;
+
+        require!(
+            batch.is_closable(Clock::get()?.unix_timestamp),
+            PayrollError::BatchNotClosable
+        );
+
+        msg!("Batch {} closed", batch.batch_id);
+        Ok(())
+    }
 }
 
 // Account Contexts
 
 #[derive(Accounts)]
-#[instruction(batch_id: u64)]
+#[instruction(batch_id: u64, total_recipients: u16)]
 pub struct InitializeBatch<'info> {
     #[account(
         init,
         payer = payer,
-        space = 8 + PayrollBatch::LEN,
+        space = PayrollBatch::space(total_recipients),
         seeds = [b"payroll_batch", batch_id.to_le_bytes().as_ref()],
         bump
     )]
@@ -276,6 +333,20 @@ pub struct CancelBatch<'info> {
     pub payer: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct CloseBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"payroll_batch", &batch.batch_id.to_le_bytes()],
+        bump = batch.bump,
+        close = payer
+    )]
+    pub batch: Account<'info, PayrollBatch>,
+
+    #[account(mut, address = batch.payer @ PayrollError::Unauthorized)]
+    pub payer: SystemAccount<'info>,
+}
+
 // Data Structures
 
 #[account]
@@ -294,14 +365,82 @@ pub struct PayrollBatch {
     pub status: BatchStatus,
     /// Creation timestamp
     pub created_at: i64,
-    /// Finalization timestamp
+    /// Finalization or cancellation timestamp
     pub finalized_at: Option<i64>,
     /// PDA bump
     pub bump: u8,
+    /// Account that paid the rent, refunded when the batch is closed
+    pub payer: Pubkey,
+    /// Seconds the batch is kept after finalization or cancellation
+    pub retention_period: i64,
+    /// Bitmap of paid recipient indices, bit `i % 8` of byte `i / 8`
+    pub paid_recipients: Vec<u8>,
 }
 
 impl PayrollBatch {
-    pub const LEN: usize = 32 + 8 + 2 + 2 + 8 + 1 + 8 + 9 + 1;
+    /// Size without the paid recipients bitmap contents
+    pub const LEN: usize = 32 + 8 + 2 + 2 + 8 + 1 + 8 + 9 + 1 + 32 + 8 + 4;
+
+    /// Bytes of bitmap needed for `total_recipients`
+    pub const fn bitmap_len(total_recipients: u16) -> usize {
+        (total_recipients as usize).div_ceil(8)
+    }
+
+    /// Account size, discriminator included, for `total_recipients`
+    pub const fn space(total_recipients: u16) -> usize {
+        8 + Self::LEN + Self::bitmap_len(total_recipients)
+    }
+
+    /// Whether `recipient_index` has been paid
+    pub fn is_paid(&self, recipient_index: u16) -> bool {
+        let index = recipient_index as usize;
+        self.paid_recipients
+            .get(index / 8)
+            .is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+    }
+
+    /// Record the payment of `recipient_index`, failing if it is out of
+    /// range or was already paid
+    pub fn mark_paid(&mut self, recipient_index: u16) -> Result<()> {
+        require!(
+            recipient_index < self.total_recipients,
+            PayrollError::InvalidRecipientIndex
+        );
+        require!(
+            !self.is_paid(recipient_index),
+            PayrollError::BatchAlreadyPaid
+        );
+
+        let index = recipient_index as usize;
+        self.paid_recipients[index / 8] |= 1 << (index % 8);
+        Ok(())
+    }
+
+    /// Whether the batch has not yet been finalized or cancelled
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.status,
+            BatchStatus::Initialized | BatchStatus::Delegated | BatchStatus::Processing
+        )
+    }
+
+    /// Move an open batch to `status` (finalized or cancelled) at `now`,
+    /// returning the settlement time its retention period runs from
+    pub fn settle(&mut self, status: BatchStatus, now: i64) -> Result<i64> {
+        require!(self.is_open(), PayrollError::InvalidBatchStatus);
+
+        self.status = status;
+        Ok(*self.finalized_at.get_or_insert(now))
+    }
+
+    /// Whether the batch is finalized or cancelled and its retention period
+    /// has passed at `now`
+    pub fn is_closable(&self, now: i64) -> bool {
+        matches!(self.status, BatchStatus::Finalized | BatchStatus::Cancelled)
+            && self
+                .finalized_at
+                .is_some_and(|settled_at| now >= settled_at.saturating_add(self.retention_period))
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
@@ -313,6 +452,47 @@ pub enum BatchStatus {
     Cancelled,
 }
 
+// Events
+
+#[event]
+pub struct BatchInitialized {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub authority: Pubkey,
+    pub total_recipients: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PaymentProcessed {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub recipient_index: u16,
+    pub recipient_token: Pubkey,
+    pub amount: u64,
+    pub processed_count: u16,
+}
+
+#[event]
+pub struct BatchFinalized {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub processed_count: u16,
+    pub total_amount: u64,
+    pub timestamp: i64,
+}
+
+/// Payments processed before cancellation are not reversed; the totals show
+/// what was paid out
+#[event]
+pub struct BatchCancelled {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub processed_count: u16,
+    pub total_amount: u64,
+    pub timestamp: i64,
+}
+
 // Errors
 
 #[error_code]
@@ -327,4 +507,10 @@ pub enum PayrollError {
     BatchAlreadyFinalized,
     #[msg("Unauthorized: caller is not batch authority")]
     Unauthorized,
+    #[msg("Retention period must not be negative")]
+    InvalidRetention,
+    #[msg("Batch is not finalized or cancelled, or still within its retention period")]
+    BatchNotClosable,
+    #[msg("Recipient has already been paid in this batch")]
+    BatchAlreadyPaid,
 }
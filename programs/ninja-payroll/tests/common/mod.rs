@@ -0,0 +1,21 @@
+//! Shared batch fixtures
+
+use anchor_lang::prelude::*;
+use ninja_payroll::{BatchStatus, PayrollBatch};
+
+pub fn batch(total_recipients: u16) -> PayrollBatch {
+    PayrollBatch {
+        authority: Pubkey::new_unique(),
+        batch_id: 1,
+        total_recipients,
+        processed_count: 0,
+        total_amount: 0,
+        status: BatchStatus::Delegated,
+        created_at: 0,
+        finalized_at: None,
+        bump: 255,
+        payer: Pubkey::new_unique(),
+        retention_period: 0,
+        paid_recipients: vec![0; PayrollBatch::bitmap_len(total_recipients)],
+    }
+}
@@ -0,0 +1,48 @@
+//! Batches are finalized or cancelled once, and closed after retention
+
+mod common;
+
+use common::batch;
+use ninja_payroll::{BatchStatus, PayrollError};
+
+#[test]
+fn open_batches_settle_once() {
+    for status in [BatchStatus::Finalized, BatchStatus::Cancelled] {
+        let mut batch = batch(1);
+        assert!(batch.is_open());
+
+        assert_eq!(batch.settle(status.clone(), 100).unwrap(), 100);
+        assert!(batch.status == status);
+        assert!(!batch.is_open());
+        assert_eq!(batch.finalized_at, Some(100));
+    }
+}
+
+#[test]
+fn settled_batches_cannot_be_cancelled() {
+    for status in [BatchStatus::Finalized, BatchStatus::Cancelled] {
+        let mut batch = batch(1);
+        batch.retention_period = 50;
+        batch.settle(status.clone(), 100).unwrap();
+
+        assert_eq!(
+            batch.settle(BatchStatus::Cancelled, 140).err(),
+            Some(PayrollError::InvalidBatchStatus.into())
+        );
+        // The retention deadline is not pushed back
+        assert!(batch.status == status);
+        assert_eq!(batch.finalized_at, Some(100));
+        assert!(batch.is_closable(150));
+    }
+}
+
+#[test]
+fn closable_only_after_retention() {
+    let mut batch = batch(1);
+    batch.retention_period = 3_600;
+    assert!(!batch.is_closable(i64::MAX));
+
+    batch.settle(BatchStatus::Cancelled, 1_000).unwrap();
+    assert!(!batch.is_closable(4_599));
+    assert!(batch.is_closable(4_600));
+}
@@ -0,0 +1,74 @@
+//! Each recipient of a batch is paid at most once
+
+mod common;
+
+use anchor_lang::prelude::*;
+use common::batch;
+use ninja_payroll::{PayrollBatch, PayrollError};
+
+#[test]
+fn paying_a_recipient_twice_fails() {
+    let mut batch = batch(3);
+
+    batch.mark_paid(0).unwrap();
+    assert_eq!(
+        batch.mark_paid(0).err(),
+        Some(PayrollError::BatchAlreadyPaid.into())
+    );
+
+    // Other recipients are unaffected
+    batch.mark_paid(2).unwrap();
+    assert!(batch.is_paid(0) && !batch.is_paid(1) && batch.is_paid(2));
+    assert_eq!(
+        batch.mark_paid(2).err(),
+        Some(PayrollError::BatchAlreadyPaid.into())
+    );
+}
+
+#[test]
+fn every_index_is_paid_once_across_bitmap_bytes() {
+    let mut batch = batch(17);
+    assert_eq!(batch.paid_recipients.len(), 3);
+
+    for index in 0..17 {
+        batch.mark_paid(index).unwrap();
+    }
+    for index in 0..17 {
+        assert_eq!(
+            batch.mark_paid(index).err(),
+            Some(PayrollError::BatchAlreadyPaid.into()),
+            "index {}",
+            index
+        );
+    }
+}
+
+#[test]
+fn out_of_range_indices_are_rejected() {
+    let mut batch = batch(8);
+
+    assert_eq!(
+        batch.mark_paid(8).err(),
+        Some(PayrollError::InvalidRecipientIndex.into())
+    );
+    assert!(!batch.is_paid(8));
+    assert!(batch.paid_recipients.iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn space_fits_the_largest_batch() {
+    for total in [0, 1, 8, 9, 100, u16::MAX] {
+        // A settled batch, with `finalized_at` at its full size
+        let mut batch = batch(total);
+        batch.finalized_at = Some(0);
+
+        let mut data = Vec::new();
+        batch.try_serialize(&mut data).unwrap();
+        assert_eq!(
+            data.len(),
+            PayrollBatch::space(total),
+            "{} recipients",
+            total
+        );
+    }
+}
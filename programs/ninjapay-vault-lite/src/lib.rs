@@ -1,4 +1,7 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
 
 declare_id!("FKsek5byvQ7fMTD9atN55gAxytrWecci57chsjuYNFPP");
 
@@ -11,45 +14,316 @@ declare_id!("FKsek5byvQ7fMTD9atN55gAxytrWecci57chsjuYNFPP");
 pub mod ninjapay_vault_lite {
     use super::*;
 
+    /// Create the program config holding the Arcium service authority
+    ///
+    /// Only the program's upgrade authority may do this, and it becomes the
+    /// config admin.
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        service_authority: Pubkey,
+        payment_retention: i64,
+    ) -> Result<()> {
+        require!(payment_retention >= 0, VaultError::InvalidRetention);
+
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.service_authority = service_authority;
+        config.bump = ctx.bumps.config;
+        config.payment_retention = payment_retention;
+
+        msg!(
+            "Config initialized, service authority {}",
+            service_authority
+        );
+        Ok(())
+    }
+
+    /// Rotate the key the Arcium service signs state updates with
+    pub fn set_service_authority(
+        ctx: Context<UpdateConfig>,
+        new_service_authority: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.service_authority = new_service_authority;
+
+        msg!("Service authority rotated to {}", new_service_authority);
+        Ok(())
+    }
+
+    /// Change how long settled payments are kept before they can be closed
+    pub fn set_payment_retention(
+        ctx: Context<UpdateConfig>,
+        payment_retention: i64,
+    ) -> Result<()> {
+        require!(payment_retention >= 0, VaultError::InvalidRetention);
+
+        let config = &mut ctx.accounts.config;
+        config.payment_retention = payment_retention;
+
+        msg!("Payment retention set to {}s", payment_retention);
+        Ok(())
+    }
+
+    /// Hand the config admin role to another key
+    pub fn set_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = new_admin;
+
+        msg!("Config admin rotated to {}", new_admin);
+        Ok(())
+    }
+
     /// Initialize a vault for a merchant
     pub fn initialize_vault(ctx: Context<InitializeVault>) -> Result<()> {
         let vault = &mut ctx.accounts.vault;
+        vault.version = Vault::VERSION;
         vault.owner = ctx.accounts.owner.key();
-        vault.encrypted_balance = Vec::new();
         vault.last_updated = Clock::get()?.unix_timestamp;
         vault.bump = ctx.bumps.vault;
 
+        emit!(VaultInitialized {
+            vault: vault.key(),
+            owner: vault.owner,
+            timestamp: vault.last_updated,
+        });
         msg!("Vault initialized for {}", vault.owner);
         Ok(())
     }
 
-    /// Update encrypted balance (called by Arcium service after MPC computation)
+    /// Open the vault's encrypted balance for `mint`
+    ///
+    /// The account is sized for ciphertexts of `encrypted_len` bytes;
+    /// [`resize_balance`] grows it if the ciphertext format later needs more.
+    pub fn initialize_balance(ctx: Context<InitializeBalance>, encrypted_len: u32) -> Result<()> {
+        require!(
+            encrypted_len as usize <= MAX_ENCRYPTED_SIZE,
+            VaultError::DataTooLarge
+        );
+
+        let balance = &mut ctx.accounts.balance;
+        balance.vault = ctx.accounts.vault.key();
+        balance.mint = ctx.accounts.mint.key();
+        balance.encrypted_balance = Vec::new();
+        balance.last_updated = Clock::get()?.unix_timestamp;
+        balance.bump = ctx.bumps.balance;
+
+        msg!(
+            "Balance for mint {} initialized for {}",
+            balance.mint,
+            ctx.accounts.vault.owner
+        );
+        Ok(())
+    }
+
+    /// Update the encrypted balance for one mint (signed by the Arcium
+    /// service authority after MPC computation)
     pub fn update_balance(
         ctx: Context<UpdateBalance>,
         encrypted_balance: Vec<u8>,
     ) -> Result<()> {
-        let vault = &mut ctx.accounts.vault;
+        let balance = &mut ctx.accounts.balance;
+
+        check_balance_ciphertext(balance, &encrypted_balance)?;
+
+        balance.encrypted_balance = encrypted_balance;
+        balance.last_updated = Clock::get()?.unix_timestamp;
+
+        emit!(BalanceUpdated {
+            vault: balance.vault,
+            mint: balance.mint,
+            timestamp: balance.last_updated,
+        });
+        msg!(
+            "Balance for mint {} updated for {}",
+            balance.mint,
+            ctx.accounts.vault.owner
+        );
+        Ok(())
+    }
 
+    /// Grow a balance account to hold ciphertexts of `encrypted_len` bytes
+    ///
+    /// Balances keep the size they were created with, so this is needed
+    /// before storing a larger ciphertext than the account was sized for, up
+    /// to [`MAX_ENCRYPTED_SIZE`]. Accounts are never shrunk. Anyone may call this; `payer` funds
+    /// the extra rent.
+    pub fn resize_balance(ctx: Context<ResizeBalance>, encrypted_len: u32) -> Result<()> {
+        let encrypted_len = encrypted_len as usize;
         require!(
-            encrypted_balance.len() <= MAX_ENCRYPTED_SIZE,
+            encrypted_len <= MAX_ENCRYPTED_SIZE,
             VaultError::DataTooLarge
         );
 
-        vault.encrypted_balance = encrypted_balance;
-        vault.last_updated = Clock::get()?.unix_timestamp;
+        let info = ctx.accounts.balance.to_account_info();
+        let space = VaultBalance::space(encrypted_len);
+        if space > info.data_len() {
+            resize(&info, space, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        }
+
+        msg!(
+            "Balance for mint {} sized for {} byte ciphertexts",
+            ctx.accounts.balance.mint,
+            encrypted_len
+        );
+        Ok(())
+    }
+
+    /// Open the vault's custody token account for `mint`
+    ///
+    /// Works with both SPL Token and Token-2022 mints; the token account is a
+    /// PDA owned by the vault.
+    pub fn open_custody(ctx: Context<OpenCustody>) -> Result<()> {
+        let custody = &mut ctx.accounts.custody;
+        custody.vault = ctx.accounts.vault.key();
+        custody.mint = ctx.accounts.mint.key();
+        custody.token_account = ctx.accounts.custody_token_account.key();
+        custody.total_deposited = 0;
+        custody.total_withdrawn = 0;
+        custody.bump = ctx.bumps.custody;
+
+        msg!("Custody opened for mint {}", custody.mint);
+        Ok(())
+    }
+
+    /// Move `amount` tokens from the owner into custody
+    ///
+    /// `encrypted_balance` is the new ciphertext for the mint's balance,
+    /// credited with `expected_received`: what custody receives net of any
+    /// Token-2022 transfer fee. The deposit fails unless custody receives
+    /// exactly that, so the ciphertext the service authority co-signs always
+    /// matches the tokens held.
+    pub fn deposit(
+        ctx: Context<Deposit>,
+        amount: u64,
+        expected_received: u64,
+        encrypted_balance: Vec<u8>,
+    ) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        check_balance_ciphertext(&ctx.accounts.balance, &encrypted_balance)?;
+
+        let before = ctx.accounts.custody_token_account.amount;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.custody_token_account.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        ctx.accounts.custody_token_account.reload()?;
+
+        // Token-2022 transfer fees can leave custody with less than `amount`
+        let balance = ctx.accounts.custody_token_account.amount;
+        let received = balance
+            .checked_sub(before)
+            .ok_or(VaultError::CustodyInvariantViolated)?;
+        require!(
+            received == expected_received,
+            VaultError::UnexpectedDepositAmount
+        );
+
+        let custody = &mut ctx.accounts.custody;
+        custody.record_deposit(received)?;
+        custody.check_invariant(balance)?;
+
+        let balance = &mut ctx.accounts.balance;
+        balance.encrypted_balance = encrypted_balance;
+        balance.last_updated = Clock::get()?.unix_timestamp;
+
+        emit!(BalanceUpdated {
+            vault: balance.vault,
+            mint: balance.mint,
+            timestamp: balance.last_updated,
+        });
+        msg!(
+            "Deposited {} of {} into {}",
+            received,
+            custody.mint,
+            ctx.accounts.vault.owner
+        );
+        Ok(())
+    }
+
+    /// Release `amount` tokens from custody to `destination`
+    ///
+    /// Like [`deposit`], co-signed by the service authority, which only signs
+    /// once the encrypted balance covers the withdrawal.
+    pub fn withdraw(
+        ctx: Context<Withdraw>,
+        amount: u64,
+        encrypted_balance: Vec<u8>,
+    ) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        check_balance_ciphertext(&ctx.accounts.balance, &encrypted_balance)?;
+        require!(
+            ctx.accounts.custody.held()? >= amount,
+            VaultError::InsufficientCustody
+        );
+
+        let owner = ctx.accounts.vault.owner;
+        let seeds: &[&[u8]] = &[b"vault", owner.as_ref(), &[ctx.accounts.vault.bump]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.custody_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.vault.to_account_info(),
+                },
+                &[seeds],
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        ctx.accounts.custody_token_account.reload()?;
+
+        let custody = &mut ctx.accounts.custody;
+        custody.record_withdrawal(amount)?;
+        custody.check_invariant(ctx.accounts.custody_token_account.amount)?;
 
-        msg!("Balance updated for {}", vault.owner);
+        let balance = &mut ctx.accounts.balance;
+        balance.encrypted_balance = encrypted_balance;
+        balance.last_updated = Clock::get()?.unix_timestamp;
+
+        emit!(BalanceUpdated {
+            vault: balance.vault,
+            mint: balance.mint,
+            timestamp: balance.last_updated,
+        });
+        msg!(
+            "Withdrew {} of {} from {}",
+            amount,
+            custody.mint,
+            ctx.accounts.vault.owner
+        );
         Ok(())
     }
 
-    /// Record a payment intent (for audit trail)
+    /// Record a payment intent (for audit trail), signed by the vault owner or
+    /// the service authority
+    ///
+    /// The payment is in the mint of the `balance` account passed. With
+    /// `expires_at` set, the payment can no longer be finalized from that
+    /// unix timestamp on. `encrypted_memo` (a memo or invoice reference) and
+    /// `encrypted_payer` (who paid) are encrypted off-chain by the Arcium
+    /// service under a key it derives for the vault owner, and may be empty.
     pub fn record_payment(
         ctx: Context<RecordPayment>,
         payment_id: String,
         encrypted_amount: Vec<u8>,
         recipient: Pubkey,
+        expires_at: Option<i64>,
+        encrypted_memo: Vec<u8>,
+        encrypted_payer: Vec<u8>,
     ) -> Result<()> {
         let payment = &mut ctx.accounts.payment;
+        let now = Clock::get()?.unix_timestamp;
 
         require!(
             payment_id.len() <= MAX_ID_LENGTH,
@@ -61,31 +335,70 @@ pub mod ninjapay_vault_lite {
             VaultError::DataTooLarge
         );
 
+        require!(
+            encrypted_memo.len() <= MAX_MEMO_SIZE,
+            VaultError::DataTooLarge
+        );
+
+        require!(
+            encrypted_payer.len() <= MAX_PAYER_SIZE,
+            VaultError::DataTooLarge
+        );
+
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > now, VaultError::InvalidExpiry);
+        }
+
+        payment.version = PaymentRecord::VERSION;
         payment.vault = ctx.accounts.vault.key();
         payment.payment_id = payment_id;
         payment.encrypted_amount = encrypted_amount;
         payment.recipient = recipient;
-        payment.timestamp = Clock::get()?.unix_timestamp;
+        payment.mint = ctx.accounts.balance.mint;
+        payment.timestamp = now;
         payment.status = PaymentStatus::Pending;
         payment.bump = ctx.bumps.payment;
+        payment.payer = ctx.accounts.payer.key();
+        payment.updated_at = now;
+        payment.expires_at = expires_at;
+        payment.encrypted_refund_amount = Vec::new();
+        payment.encrypted_memo = encrypted_memo;
+        payment.encrypted_payer = encrypted_payer;
 
+        emit!(PaymentRecorded {
+            vault: payment.vault,
+            payment: payment.key(),
+            payment_id: payment.payment_id.clone(),
+            mint: payment.mint,
+            recipient: payment.recipient,
+            expires_at: payment.expires_at,
+            timestamp: now,
+        });
         msg!("Payment recorded: {}", payment.payment_id);
         Ok(())
     }
 
-    /// Finalize a payment (called after MPC computation completes)
+    /// Finalize a pending payment (signed by the service authority after MPC
+    /// computation completes)
     pub fn finalize_payment(
         ctx: Context<FinalizePayment>,
         success: bool,
     ) -> Result<()> {
         let payment = &mut ctx.accounts.payment;
+        let now = Clock::get()?.unix_timestamp;
 
-        payment.status = if success {
-            PaymentStatus::Completed
-        } else {
-            PaymentStatus::Failed
-        };
+        require!(!payment.is_expired(now), VaultError::PaymentExpired);
 
+        payment.transition(
+            if success {
+                PaymentStatus::Completed
+            } else {
+                PaymentStatus::Failed
+            },
+            now,
+        )?;
+
+        emit_status_change(payment)?;
         msg!(
             "Payment {} finalized: {}",
             payment.payment_id,
@@ -93,14 +406,309 @@ pub mod ninjapay_vault_lite {
         );
         Ok(())
     }
+
+    /// Cancel a pending payment, signed by the vault owner or the service
+    /// authority
+    pub fn cancel_payment(ctx: Context<CancelPayment>) -> Result<()> {
+        let payment = &mut ctx.accounts.payment;
+        payment.transition(PaymentStatus::Cancelled, Clock::get()?.unix_timestamp)?;
+
+        emit_status_change(payment)?;
+
+        msg!("Payment {} cancelled", payment.payment_id);
+        Ok(())
+    }
+
+    /// Mark a pending payment whose expiry has passed as expired
+    ///
+    /// Anyone may call this; the outcome depends only on the clock.
+    pub fn expire_payment(ctx: Context<ExpirePayment>) -> Result<()> {
+        let payment = &mut ctx.accounts.payment;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(payment.is_expired(now), VaultError::PaymentNotExpired);
+        payment.transition(PaymentStatus::Expired, now)?;
+
+        emit_status_change(payment)?;
+
+        msg!("Payment {} expired", payment.payment_id);
+        Ok(())
+    }
+
+    /// Refund a completed payment (signed by the service authority)
+    ///
+    /// `encrypted_refund_amount` is the total refunded so far; the service
+    /// only signs once MPC has checked it does not exceed the payment amount.
+    /// `full` marks the payment `Refunded`, otherwise `PartiallyRefunded`,
+    /// which may be refunded further.
+    pub fn refund_payment(
+        ctx: Context<FinalizePayment>,
+        encrypted_refund_amount: Vec<u8>,
+        full: bool,
+    ) -> Result<()> {
+        let payment = &mut ctx.accounts.payment;
+
+        require!(
+            encrypted_refund_amount.len() <= MAX_ENCRYPTED_SIZE,
+            VaultError::DataTooLarge
+        );
+
+        payment.transition(
+            if full {
+                PaymentStatus::Refunded
+            } else {
+                PaymentStatus::PartiallyRefunded
+            },
+            Clock::get()?.unix_timestamp,
+        )?;
+        payment.encrypted_refund_amount = encrypted_refund_amount;
+
+        emit_status_change(payment)?;
+
+        msg!(
+            "Payment {} {}",
+            payment.payment_id,
+            if full { "refunded" } else { "partially refunded" }
+        );
+        Ok(())
+    }
+
+    /// Close a settled payment record once the retention period has passed
+    /// since its last status change, returning its rent to whoever paid for it
+    ///
+    /// Anyone may call this, so the service can sweep old records.
+    pub fn close_payment(ctx: Context<ClosePayment>) -> Result<()> {
+        let payment = &ctx.accounts.payment;
+
+        require!(
+            payment.is_closable(
+                Clock::get()?.unix_timestamp,
+                ctx.accounts.config.payment_retention
+            ),
+            VaultError::PaymentNotClosable
+        );
+
+        msg!("Payment {} closed", payment.payment_id);
+        Ok(())
+    }
+
+    /// Upgrade a vault stored under an older layout to the current one
+    ///
+    /// Vaults from before per-mint balances hold a single encrypted balance,
+    /// which moves to a new [`VaultBalance`] for `mint`. Only the owner knows
+    /// which mint that balance is in, so the owner must sign; `payer` funds
+    /// the balance account.
+    pub fn migrate_vault(ctx: Context<MigrateVault>) -> Result<()> {
+        let info = ctx.accounts.vault.to_account_info();
+        let (vault, legacy_balance) = Vault::migrate(&info.try_borrow_data()?)?;
+
+        let expected = Pubkey::create_program_address(
+            &[b"vault", vault.owner.as_ref(), &[vault.bump]],
+            &crate::ID,
+        )
+        .map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+        require_keys_eq!(info.key(), expected, ErrorCode::ConstraintSeeds);
+        require_keys_eq!(
+            ctx.accounts.owner.key(),
+            vault.owner,
+            VaultError::Unauthorized
+        );
+
+        resize(&info, Vault::MAX_SIZE, &ctx.accounts.payer, &ctx.accounts.system_program)?;
+        vault.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        let balance = &mut ctx.accounts.balance;
+        balance.vault = info.key();
+        balance.mint = ctx.accounts.mint.key();
+        balance.encrypted_balance = legacy_balance;
+        balance.last_updated = vault.last_updated;
+        balance.bump = ctx.bumps.balance;
+
+        msg!(
+            "Vault for {} migrated to v{}, balance moved to mint {}",
+            vault.owner,
+            Vault::VERSION,
+            balance.mint
+        );
+        Ok(())
+    }
+
+    /// Upgrade a payment record stored under an older layout to the current
+    /// one
+    ///
+    /// The vault must be migrated first. Records from before rent tracking
+    /// refund their rent to the vault owner when closed.
+    pub fn migrate_payment(ctx: Context<MigratePayment>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let info = ctx.accounts.payment.to_account_info();
+        let payment = PaymentRecord::migrate(&info.try_borrow_data()?, vault.owner)?;
+
+        require_keys_eq!(payment.vault, vault.key(), VaultError::Unauthorized);
+        let expected = Pubkey::create_program_address(
+            &[
+                b"payment",
+                payment.vault.as_ref(),
+                payment.payment_id.as_bytes(),
+                &[payment.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+        require_keys_eq!(info.key(), expected, ErrorCode::ConstraintSeeds);
+
+        resize(
+            &info,
+            PaymentRecord::MAX_SIZE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+        payment.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        msg!(
+            "Payment {} migrated to v{}",
+            payment.payment_id,
+            PaymentRecord::VERSION
+        );
+        Ok(())
+    }
+}
+
+/// Resize `account` to `len` bytes, topping it up to rent exemption from
+/// `payer`
+fn resize<'info>(
+    account: &AccountInfo<'info>,
+    len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let shortfall = Rent::get()?
+        .minimum_balance(len)
+        .saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(len, true)?;
+    Ok(())
+}
+
+/// Fail unless `encrypted_balance` is within limits and fits the allocation of
+/// `balance`
+fn check_balance_ciphertext(
+    balance: &Account<VaultBalance>,
+    encrypted_balance: &[u8],
+) -> Result<()> {
+    require!(
+        encrypted_balance.len() <= MAX_ENCRYPTED_SIZE,
+        VaultError::DataTooLarge
+    );
+    require!(
+        VaultBalance::space(encrypted_balance.len()) <= balance.to_account_info().data_len(),
+        VaultError::AccountTooSmall
+    );
+    Ok(())
+}
+
+/// Layout version of a stored account
+///
+/// Accounts from before the version byte are version 1 and are recognized by
+/// their allocation size, which was fixed.
+fn stored_version(data: &[u8], discriminator: [u8; 8], v1_size: usize) -> Result<u8> {
+    require!(
+        data.len() > 8 && data[..8] == discriminator,
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    if data.len() == v1_size {
+        Ok(1)
+    } else {
+        Ok(data[8])
+    }
+}
+
+fn emit_status_change(payment: &Account<PaymentRecord>) -> Result<()> {
+    emit!(PaymentFinalized {
+        vault: payment.vault,
+        payment: payment.key(),
+        payment_id: payment.payment_id.clone(),
+        status: payment.status,
+        timestamp: payment.updated_at,
+    });
+    Ok(())
 }
 
 // Account Structures
 
+#[account]
+pub struct Config {
+    /// May rotate the admin and service authority
+    pub admin: Pubkey,
+    /// Key the Arcium service signs balance updates and finalizations with
+    pub service_authority: Pubkey,
+    /// PDA bump
+    pub bump: u8,
+    /// Seconds a settled payment record is kept before it can be closed
+    pub payment_retention: i64,
+}
+
+impl Config {
+    pub const MAX_SIZE: usize = 8 + 32 + 32 + 1 + 8;
+}
+
 #[account]
 pub struct Vault {
+    /// Layout version, see [`Vault::VERSION`]
+    pub version: u8,
     /// Vault owner (merchant)
     pub owner: Pubkey,
+    /// Creation timestamp
+    pub last_updated: i64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl Vault {
+    /// Current layout version
+    pub const VERSION: u8 = 3;
+    pub const MAX_SIZE: usize = 8 + 1 + 32 + 8 + 1;
+
+    /// Decode a vault stored under an older layout, upgraded to this one,
+    /// along with the single-mint encrypted balance it held
+    pub fn migrate(data: &[u8]) -> Result<(Self, Vec<u8>)> {
+        let decode_error = |_| error!(ErrorCode::AccountDidNotDeserialize);
+        match stored_version(data, Self::DISCRIMINATOR, VaultV1::SIZE)? {
+            1 => Ok(VaultV1::deserialize(&mut &data[8..])
+                .map_err(decode_error)?
+                .upgrade()
+                .upgrade()),
+            2 => Ok(VaultV2::deserialize(&mut &data[8..])
+                .map_err(decode_error)?
+                .upgrade()),
+            Self::VERSION => err!(VaultError::AccountAlreadyMigrated),
+            _ => err!(VaultError::UnsupportedAccountVersion),
+        }
+    }
+}
+
+/// A vault's encrypted balance in one mint
+///
+/// Unlike [`Vault`] and [`PaymentRecord`] the layout carries no version: only
+/// the allocation varies with the ciphertext size the balance was opened for.
+/// Balances opened before that were allocated at [`VaultBalance::MAX_SIZE`],
+/// the same layout at the largest size, so they need no migration.
+#[account]
+pub struct VaultBalance {
+    /// Owning vault
+    pub vault: Pubkey,
+    /// Token mint the balance is denominated in
+    pub mint: Pubkey,
     /// Encrypted balance (encrypted with user-specific key)
     pub encrypted_balance: Vec<u8>,
     /// Last update timestamp
@@ -109,12 +717,84 @@ pub struct Vault {
     pub bump: u8,
 }
 
-impl Vault {
-    pub const MAX_SIZE: usize = 8 + 32 + 4 + 256 + 8 + 1;
+impl VaultBalance {
+    pub const MAX_SIZE: usize = Self::space(MAX_ENCRYPTED_SIZE);
+
+    /// Account size for a ciphertext of `encrypted_len` bytes
+    pub const fn space(encrypted_len: usize) -> usize {
+        8 + 32 + 32 + (4 + encrypted_len) + 8 + 1
+    }
+}
+
+/// Tokens of one mint held by a vault
+///
+/// `total_deposited - total_withdrawn` counts the tokens that arrived through
+/// [`deposit`] and have not left through [`withdraw`]. The balance of
+/// `token_account` is never below it, and exceeds it only by tokens
+/// transferred in directly.
+///
+/// [`deposit`]: ninjapay_vault_lite::deposit
+/// [`withdraw`]: ninjapay_vault_lite::withdraw
+#[account]
+pub struct Custody {
+    /// Owning vault
+    pub vault: Pubkey,
+    /// Token mint
+    pub mint: Pubkey,
+    /// Vault-owned token account holding the tokens
+    pub token_account: Pubkey,
+    /// Tokens received into custody, net of transfer fees
+    pub total_deposited: u64,
+    /// Tokens released from custody
+    pub total_withdrawn: u64,
+    /// PDA bump
+    pub bump: u8,
+}
+
+impl Custody {
+    pub const MAX_SIZE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1;
+
+    /// Tokens custody should currently hold
+    pub fn held(&self) -> Result<u64> {
+        self.total_deposited
+            .checked_sub(self.total_withdrawn)
+            .ok_or_else(|| error!(VaultError::CustodyInvariantViolated))
+    }
+
+    pub fn record_deposit(&mut self, amount: u64) -> Result<()> {
+        self.total_deposited = self
+            .total_deposited
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_withdrawal(&mut self, amount: u64) -> Result<()> {
+        require!(self.held()? >= amount, VaultError::InsufficientCustody);
+        self.total_withdrawn = self
+            .total_withdrawn
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Fail if the token account holds less than the recorded totals
+    ///
+    /// Anyone can transfer tokens straight into the custody account, so a
+    /// surplus is tolerated; it is never credited to the vault.
+    pub fn check_invariant(&self, token_balance: u64) -> Result<()> {
+        require!(
+            self.held()? <= token_balance,
+            VaultError::CustodyInvariantViolated
+        );
+        Ok(())
+    }
 }
 
 #[account]
 pub struct PaymentRecord {
+    /// Layout version, see [`PaymentRecord::VERSION`]
+    pub version: u8,
     /// Associated vault
     pub vault: Pubkey,
     /// Payment intent ID (from database)
@@ -129,64 +809,529 @@ pub struct PaymentRecord {
     pub status: PaymentStatus,
     /// PDA bump
     pub bump: u8,
+    /// Token mint the payment is in
+    pub mint: Pubkey,
+    /// Unix timestamp from which the payment can no longer be finalized
+    pub expires_at: Option<i64>,
+    /// Encrypted total refunded so far
+    pub encrypted_refund_amount: Vec<u8>,
+    /// Account that paid the rent, refunded when the record is closed
+    pub payer: Pubkey,
+    /// Timestamp of the last status change
+    pub updated_at: i64,
+    /// Memo or invoice reference, encrypted for the vault owner
+    pub encrypted_memo: Vec<u8>,
+    /// Payer identity, encrypted for the vault owner
+    pub encrypted_payer: Vec<u8>,
 }
 
 impl PaymentRecord {
-    pub const MAX_SIZE: usize = 8 + 32 + 4 + 64 + 4 + 128 + 32 + 8 + 1 + 1;
+    /// Current layout version
+    pub const VERSION: u8 = 3;
+    pub const MAX_SIZE: usize = 8
+        + 1
+        + 32
+        + (4 + MAX_ID_LENGTH)
+        + (4 + MAX_ENCRYPTED_SIZE)
+        + 32
+        + 8
+        + 1
+        + 1
+        + 32
+        + (1 + 8)
+        + (4 + MAX_ENCRYPTED_SIZE)
+        + 32
+        + 8
+        + (4 + MAX_MEMO_SIZE)
+        + (4 + MAX_PAYER_SIZE);
+
+    /// Decode a payment record stored under an older layout, upgraded to this
+    /// one; `rent_payer` is recorded where the old layout did not track it
+    pub fn migrate(data: &[u8], rent_payer: Pubkey) -> Result<Self> {
+        match stored_version(data, Self::DISCRIMINATOR, PaymentRecordV1::SIZE)? {
+            1 => Ok(PaymentRecordV1::deserialize(&mut &data[8..])
+                .map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))?
+                .upgrade(rent_payer)
+                .upgrade()),
+            2 => Ok(PaymentRecordV2::deserialize(&mut &data[8..])
+                .map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))?
+                .upgrade()),
+            Self::VERSION => err!(VaultError::AccountAlreadyMigrated),
+            _ => err!(VaultError::UnsupportedAccountVersion),
+        }
+    }
+
+    /// Whether a pending payment has run past its expiry at `now`
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.status == PaymentStatus::Pending
+            && self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// Whether the record may be closed at `now` under `retention`
+    ///
+    /// Completed and partially refunded payments can still be refunded until
+    /// they are closed, so the retention period doubles as the refund window.
+    pub fn is_closable(&self, now: i64, retention: i64) -> bool {
+        self.status != PaymentStatus::Pending && now >= self.updated_at.saturating_add(retention)
+    }
+
+    /// Move to `next` at `now`, failing if the lifecycle does not allow it
+    pub fn transition(&mut self, next: PaymentStatus, now: i64) -> Result<()> {
+        require!(
+            self.status.can_transition_to(next),
+            VaultError::InvalidStatusTransition
+        );
+        self.status = next;
+        self.updated_at = now;
+        Ok(())
+    }
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PaymentStatus {
     Pending,
     Completed,
     Failed,
+    Cancelled,
+    Refunded,
+    PartiallyRefunded,
+    Expired,
 }
 
-// Context Definitions
+impl PaymentStatus {
+    /// Allowed lifecycle moves:
+    ///
+    /// - `Pending` → `Completed`, `Failed`, `Cancelled` or `Expired`
+    /// - `Completed` → `Refunded` or `PartiallyRefunded`
+    /// - `PartiallyRefunded` → `Refunded` or `PartiallyRefunded`
+    ///
+    /// Every other state is terminal.
+    pub fn can_transition_to(self, next: PaymentStatus) -> bool {
+        use PaymentStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Completed | Failed | Cancelled | Expired)
+                | (Completed | PartiallyRefunded, Refunded | PartiallyRefunded)
+        )
+    }
+}
 
-#[derive(Accounts)]
-pub struct InitializeVault<'info> {
-    #[account(
-        init,
-        payer = payer,
-        space = Vault::MAX_SIZE,
-        seeds = [b"vault", owner.key().as_ref()],
-        bump
-    )]
-    pub vault: Account<'info, Vault>,
+// Legacy Layouts
 
-    pub owner: Signer<'info>,
+/// Vault layout before the version byte
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct VaultV1 {
+    pub owner: Pubkey,
+    pub encrypted_balance: Vec<u8>,
+    pub last_updated: i64,
+    pub bump: u8,
+}
 
-    #[account(mut)]
-    pub payer: Signer<'info>,
+impl VaultV1 {
+    /// Allocation size, discriminator included
+    pub const SIZE: usize = 8 + 32 + 4 + 256 + 8 + 1;
 
-    pub system_program: Program<'info, System>,
+    pub fn upgrade(self) -> VaultV2 {
+        VaultV2 {
+            version: 2,
+            owner: self.owner,
+            encrypted_balance: self.encrypted_balance,
+            last_updated: self.last_updated,
+            bump: self.bump,
+        }
+    }
 }
 
-#[derive(Accounts)]
-pub struct UpdateBalance<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault", owner.key().as_ref()],
-        bump = vault.bump,
-        has_one = owner
-    )]
-    pub vault: Account<'info, Vault>,
-
-    pub owner: Signer<'info>,
+/// Vault layout with a single-mint balance, from before [`VaultBalance`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct VaultV2 {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub encrypted_balance: Vec<u8>,
+    pub last_updated: i64,
+    pub bump: u8,
 }
 
-#[derive(Accounts)]
-#[instruction(payment_id: String)]
-pub struct RecordPayment<'info> {
-    #[account(
-        seeds = [b"vault", vault.owner.as_ref()],
-        bump = vault.bump
-    )]
-    pub vault: Account<'info, Vault>,
+impl VaultV2 {
+    /// Allocation size, discriminator included
+    pub const SIZE: usize = 8 + 1 + 32 + (4 + MAX_ENCRYPTED_SIZE) + 8 + 1;
 
-    #[account(
-        init,
+    /// The vault, and the balance it held for [`VaultBalance`] to take over
+    pub fn upgrade(self) -> (Vault, Vec<u8>) {
+        (
+            Vault {
+                version: Vault::VERSION,
+                owner: self.owner,
+                last_updated: self.last_updated,
+                bump: self.bump,
+            },
+            self.encrypted_balance,
+        )
+    }
+}
+
+/// Payment record layout before the version byte, from before per-mint
+/// balances, expiry, refunds and rent tracking
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PaymentRecordV1 {
+    pub vault: Pubkey,
+    pub payment_id: String,
+    pub encrypted_amount: Vec<u8>,
+    pub recipient: Pubkey,
+    pub timestamp: i64,
+    pub status: PaymentStatus,
+    pub bump: u8,
+}
+
+impl PaymentRecordV1 {
+    /// Allocation size, discriminator included
+    pub const SIZE: usize = 8 + 32 + 4 + 64 + 4 + 128 + 32 + 8 + 1 + 1;
+
+    /// The mint is left as the default key, as v1 payments predate per-mint
+    /// balances
+    pub fn upgrade(self, rent_payer: Pubkey) -> PaymentRecordV2 {
+        PaymentRecordV2 {
+            version: 2,
+            vault: self.vault,
+            payment_id: self.payment_id,
+            encrypted_amount: self.encrypted_amount,
+            recipient: self.recipient,
+            timestamp: self.timestamp,
+            status: self.status,
+            bump: self.bump,
+            mint: Pubkey::default(),
+            expires_at: None,
+            encrypted_refund_amount: Vec::new(),
+            payer: rent_payer,
+            updated_at: self.timestamp,
+        }
+    }
+}
+
+/// Payment record layout before encrypted memos and payer identities
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PaymentRecordV2 {
+    pub version: u8,
+    pub vault: Pubkey,
+    pub payment_id: String,
+    pub encrypted_amount: Vec<u8>,
+    pub recipient: Pubkey,
+    pub timestamp: i64,
+    pub status: PaymentStatus,
+    pub bump: u8,
+    pub mint: Pubkey,
+    pub expires_at: Option<i64>,
+    pub encrypted_refund_amount: Vec<u8>,
+    pub payer: Pubkey,
+    pub updated_at: i64,
+}
+
+impl PaymentRecordV2 {
+    pub fn upgrade(self) -> PaymentRecord {
+        PaymentRecord {
+            version: PaymentRecord::VERSION,
+            vault: self.vault,
+            payment_id: self.payment_id,
+            encrypted_amount: self.encrypted_amount,
+            recipient: self.recipient,
+            timestamp: self.timestamp,
+            status: self.status,
+            bump: self.bump,
+            mint: self.mint,
+            expires_at: self.expires_at,
+            encrypted_refund_amount: self.encrypted_refund_amount,
+            payer: self.payer,
+            updated_at: self.updated_at,
+            encrypted_memo: Vec::new(),
+            encrypted_payer: Vec::new(),
+        }
+    }
+}
+
+// Context Definitions
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = Config::MAX_SIZE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+    )]
+    pub program: Program<'info, crate::program::NinjapayVaultLite>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ VaultError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ VaultError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVault<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Vault::MAX_SIZE,
+        seeds = [b"vault", owner.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(encrypted_len: u32)]
+pub struct InitializeBalance<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump,
+        has_one = owner @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = VaultBalance::space(encrypted_len as usize),
+        seeds = [b"vault_balance", vault.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub balance: Account<'info, VaultBalance>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBalance<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_balance", vault.key().as_ref(), balance.mint.as_ref()],
+        bump = balance.bump
+    )]
+    pub balance: Account<'info, VaultBalance>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        constraint = authority.key() == config.service_authority @ VaultError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResizeBalance<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_balance", vault.key().as_ref(), balance.mint.as_ref()],
+        bump = balance.bump
+    )]
+    pub balance: Account<'info, VaultBalance>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenCustody<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump,
+        has_one = owner @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Custody::MAX_SIZE,
+        seeds = [b"custody", vault.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub custody: Account<'info, Custody>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"custody_tokens", vault.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vault
+    )]
+    pub custody_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump,
+        has_one = owner @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_balance", vault.key().as_ref(), mint.key().as_ref()],
+        bump = balance.bump
+    )]
+    pub balance: Account<'info, VaultBalance>,
+
+    #[account(
+        mut,
+        seeds = [b"custody", vault.key().as_ref(), mint.key().as_ref()],
+        bump = custody.bump
+    )]
+    pub custody: Account<'info, Custody>,
+
+    #[account(mut, address = custody.token_account)]
+    pub custody_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = owner
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        constraint = authority.key() == config.service_authority @ VaultError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump,
+        has_one = owner @ VaultError::Unauthorized
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_balance", vault.key().as_ref(), mint.key().as_ref()],
+        bump = balance.bump
+    )]
+    pub balance: Account<'info, VaultBalance>,
+
+    #[account(
+        mut,
+        seeds = [b"custody", vault.key().as_ref(), mint.key().as_ref()],
+        bump = custody.bump
+    )]
+    pub custody: Account<'info, Custody>,
+
+    #[account(mut, address = custody.token_account)]
+    pub custody_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint = mint)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        constraint = authority.key() == config.service_authority @ VaultError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(payment_id: String)]
+pub struct RecordPayment<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        seeds = [b"vault_balance", vault.key().as_ref(), balance.mint.as_ref()],
+        bump = balance.bump
+    )]
+    pub balance: Account<'info, VaultBalance>,
+
+    #[account(
+        init,
         payer = payer,
         space = PaymentRecord::MAX_SIZE,
         seeds = [b"payment", vault.key().as_ref(), payment_id.as_bytes()],
@@ -194,7 +1339,15 @@ pub struct RecordPayment<'info> {
     )]
     pub payment: Account<'info, PaymentRecord>,
 
-    pub owner: Signer<'info>,
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        constraint = authority.key() == vault.owner
+            || authority.key() == config.service_authority
+            @ VaultError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
 
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -217,13 +1370,178 @@ pub struct FinalizePayment<'info> {
     )]
     pub payment: Account<'info, PaymentRecord>,
 
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        constraint = authority.key() == config.service_authority @ VaultError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelPayment<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"payment", vault.key().as_ref(), payment.payment_id.as_bytes()],
+        bump = payment.bump
+    )]
+    pub payment: Account<'info, PaymentRecord>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        constraint = authority.key() == vault.owner
+            || authority.key() == config.service_authority
+            @ VaultError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpirePayment<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"payment", vault.key().as_ref(), payment.payment_id.as_bytes()],
+        bump = payment.bump
+    )]
+    pub payment: Account<'info, PaymentRecord>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePayment<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    #[account(
+        mut,
+        seeds = [b"payment", vault.key().as_ref(), payment.payment_id.as_bytes()],
+        bump = payment.bump,
+        close = payer
+    )]
+    pub payment: Account<'info, PaymentRecord>,
+
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    #[account(mut, address = payment.payer @ VaultError::Unauthorized)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    /// CHECK: decoded and checked against its seeds and `owner` by
+    /// `migrate_vault`
+    #[account(mut, owner = crate::ID)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Mint the legacy balance is denominated in
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Takes over the legacy balance; sized like balances opened before
+    /// ciphertext-sized allocation
+    #[account(
+        init,
+        payer = payer,
+        space = VaultBalance::MAX_SIZE,
+        seeds = [b"vault_balance", vault.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub balance: Account<'info, VaultBalance>,
+
     pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePayment<'info> {
+    #[account(
+        seeds = [b"vault", vault.owner.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: decoded and checked against its seeds by `migrate_payment`
+    #[account(mut, owner = crate::ID)]
+    pub payment: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// Events
+
+#[event]
+pub struct VaultInitialized {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted whenever a per-mint encrypted balance is rewritten, including by
+/// deposits and withdrawals
+#[event]
+pub struct BalanceUpdated {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PaymentRecorded {
+    pub vault: Pubkey,
+    pub payment: Pubkey,
+    pub payment_id: String,
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    pub expires_at: Option<i64>,
+    pub timestamp: i64,
+}
+
+/// Emitted on every payment status change after recording: finalization,
+/// cancellation, expiry and refunds
+#[event]
+pub struct PaymentFinalized {
+    pub vault: Pubkey,
+    pub payment: Pubkey,
+    pub payment_id: String,
+    pub status: PaymentStatus,
+    pub timestamp: i64,
 }
 
 // Constants
 
-const MAX_ENCRYPTED_SIZE: usize = 256;
-const MAX_ID_LENGTH: usize = 64;
+/// Largest ciphertext stored, with room for a multi-block Rescue ciphertext
+/// plus its x25519 public key and nonce
+pub const MAX_ENCRYPTED_SIZE: usize = 256;
+/// Longest payment id
+pub const MAX_ID_LENGTH: usize = 64;
+/// Largest encrypted payment memo or invoice reference
+pub const MAX_MEMO_SIZE: usize = 192;
+/// Largest encrypted payer identity
+pub const MAX_PAYER_SIZE: usize = 96;
 
 // Errors
 
@@ -235,4 +1553,32 @@ pub enum VaultError {
     IdTooLong,
     #[msg("Unauthorized access")]
     Unauthorized,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Custody holds fewer tokens than requested")]
+    InsufficientCustody,
+    #[msg("Custody balance is below recorded deposits net of withdrawals")]
+    CustodyInvariantViolated,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Payment status does not allow this transition")]
+    InvalidStatusTransition,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Payment has expired")]
+    PaymentExpired,
+    #[msg("Payment has not expired")]
+    PaymentNotExpired,
+    #[msg("Retention period must not be negative")]
+    InvalidRetention,
+    #[msg("Payment is pending or still within its retention period")]
+    PaymentNotClosable,
+    #[msg("Account already uses the current layout")]
+    AccountAlreadyMigrated,
+    #[msg("Account layout version is not supported")]
+    UnsupportedAccountVersion,
+    #[msg("Account is too small for this ciphertext, resize it first")]
+    AccountTooSmall,
+    #[msg("Custody received a different amount than the balance was credited with")]
+    UnexpectedDepositAmount,
 }
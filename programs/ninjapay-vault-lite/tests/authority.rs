@@ -0,0 +1,202 @@
+//! Account validation for the service-authority model
+//!
+//! These run each instruction's `Accounts` validation directly, so they
+//! check exactly the constraints the runtime would enforce.
+
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{program_account, signer};
+use ninjapay_vault_lite::{
+    Config, FinalizePayment, FinalizePaymentBumps, PaymentRecord, PaymentStatus, UpdateBalance,
+    UpdateBalanceBumps, UpdateConfig, UpdateConfigBumps, Vault, VaultBalance, VaultError, ID,
+};
+use std::collections::BTreeSet;
+
+struct Fixture {
+    admin: Pubkey,
+    owner: Pubkey,
+    service: Pubkey,
+    config: AccountInfo<'static>,
+    vault: AccountInfo<'static>,
+    balance: AccountInfo<'static>,
+    payment: AccountInfo<'static>,
+}
+
+fn fixture() -> Fixture {
+    let admin = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let service = Pubkey::new_unique();
+
+    let (config_key, config_bump) = Pubkey::find_program_address(&[b"config"], &ID);
+    let (vault_key, vault_bump) = Pubkey::find_program_address(&[b"vault", owner.as_ref()], &ID);
+    let mint = Pubkey::new_unique();
+    let (balance_key, balance_bump) =
+        Pubkey::find_program_address(&[b"vault_balance", vault_key.as_ref(), mint.as_ref()], &ID);
+    let payment_id = "pi_123".to_string();
+    let (payment_key, payment_bump) = Pubkey::find_program_address(
+        &[b"payment", vault_key.as_ref(), payment_id.as_bytes()],
+        &ID,
+    );
+
+    Fixture {
+        admin,
+        owner,
+        service,
+        config: program_account(
+            config_key,
+            &Config {
+                admin,
+                service_authority: service,
+                bump: config_bump,
+                payment_retention: 0,
+            },
+        ),
+        vault: program_account(
+            vault_key,
+            &Vault {
+                version: Vault::VERSION,
+                owner,
+                last_updated: 0,
+                bump: vault_bump,
+            },
+        ),
+        balance: program_account(
+            balance_key,
+            &VaultBalance {
+                vault: vault_key,
+                mint,
+                encrypted_balance: Vec::new(),
+                last_updated: 0,
+                bump: balance_bump,
+            },
+        ),
+        payment: program_account(
+            payment_key,
+            &PaymentRecord {
+                version: PaymentRecord::VERSION,
+                vault: vault_key,
+                payment_id,
+                encrypted_amount: vec![1; 36],
+                recipient: Pubkey::new_unique(),
+                timestamp: 0,
+                status: PaymentStatus::Pending,
+                bump: payment_bump,
+                mint,
+                expires_at: None,
+                encrypted_refund_amount: Vec::new(),
+                payer: Pubkey::new_unique(),
+                updated_at: 0,
+                encrypted_memo: Vec::new(),
+                encrypted_payer: Vec::new(),
+            },
+        ),
+    }
+}
+
+fn unauthorized() -> anchor_lang::error::Error {
+    VaultError::Unauthorized.into()
+}
+
+fn finalize_accounts(f: &Fixture, authority: Pubkey) -> Result<FinalizePayment<'static>> {
+    let accounts = Box::leak(Box::new([
+        f.vault.clone(),
+        f.payment.clone(),
+        f.config.clone(),
+        signer(authority),
+    ]));
+    FinalizePayment::try_accounts(
+        &ID,
+        &mut &accounts[..],
+        &[],
+        &mut FinalizePaymentBumps::default(),
+        &mut BTreeSet::new(),
+    )
+}
+
+fn update_balance_accounts(f: &Fixture, authority: Pubkey) -> Result<UpdateBalance<'static>> {
+    balance_update_accounts(f, &f.balance, authority)
+}
+
+fn balance_update_accounts(
+    f: &Fixture,
+    balance: &AccountInfo<'static>,
+    authority: Pubkey,
+) -> Result<UpdateBalance<'static>> {
+    let accounts = Box::leak(Box::new([
+        f.vault.clone(),
+        balance.clone(),
+        f.config.clone(),
+        signer(authority),
+    ]));
+    UpdateBalance::try_accounts(
+        &ID,
+        &mut &accounts[..],
+        &[],
+        &mut UpdateBalanceBumps::default(),
+        &mut BTreeSet::new(),
+    )
+}
+
+fn update_config_accounts(f: &Fixture, admin: Pubkey) -> Result<UpdateConfig<'static>> {
+    let accounts = Box::leak(Box::new([f.config.clone(), signer(admin)]));
+    UpdateConfig::try_accounts(
+        &ID,
+        &mut &accounts[..],
+        &[],
+        &mut UpdateConfigBumps::default(),
+        &mut BTreeSet::new(),
+    )
+}
+
+#[test]
+fn finalize_payment_requires_service_authority() {
+    let f = fixture();
+
+    assert!(finalize_accounts(&f, f.service).is_ok());
+    assert_eq!(
+        finalize_accounts(&f, Pubkey::new_unique()).err(),
+        Some(unauthorized())
+    );
+    // The vault owner cannot finalize its own payments either
+    assert_eq!(finalize_accounts(&f, f.owner).err(), Some(unauthorized()));
+}
+
+#[test]
+fn update_balance_requires_service_authority() {
+    let f = fixture();
+
+    assert!(update_balance_accounts(&f, f.service).is_ok());
+    assert_eq!(
+        update_balance_accounts(&f, Pubkey::new_unique()).err(),
+        Some(unauthorized())
+    );
+    assert_eq!(
+        update_balance_accounts(&f, f.owner).err(),
+        Some(unauthorized())
+    );
+}
+
+#[test]
+fn update_balance_is_scoped_to_the_vault() {
+    let f = fixture();
+    let other = fixture();
+
+    // Another vault's balance fails the seeds check against this vault
+    assert!(balance_update_accounts(&f, &other.balance, f.service).is_err());
+}
+
+#[test]
+fn rotation_requires_admin() {
+    let f = fixture();
+
+    assert!(update_config_accounts(&f, f.admin).is_ok());
+    assert_eq!(
+        update_config_accounts(&f, f.service).err(),
+        Some(unauthorized())
+    );
+    assert_eq!(
+        update_config_accounts(&f, Pubkey::new_unique()).err(),
+        Some(unauthorized())
+    );
+}
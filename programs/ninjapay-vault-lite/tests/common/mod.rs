@@ -0,0 +1,110 @@
+//! Helpers for running `Accounts` validation against in-memory accounts
+
+#![allow(dead_code)]
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::{
+    self, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER, SUCCESS,
+};
+use anchor_lang::solana_program::program_stubs::{self, SyscallStubs};
+use anchor_lang::AccountSerialize;
+use ninjapay_vault_lite::ID;
+
+pub fn leak<T>(value: T) -> &'static mut T {
+    Box::leak(Box::new(value))
+}
+
+pub fn account_info(
+    key: Pubkey,
+    owner: Pubkey,
+    data: Vec<u8>,
+    is_signer: bool,
+    is_writable: bool,
+) -> AccountInfo<'static> {
+    AccountInfo::new(
+        leak(key),
+        is_signer,
+        is_writable,
+        leak(1_000_000_000),
+        Box::leak(data.into_boxed_slice()),
+        leak(owner),
+        false,
+        0,
+    )
+}
+
+pub fn program_account<T: AccountSerialize>(key: Pubkey, account: &T) -> AccountInfo<'static> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    account_info(key, ID, data, false, true)
+}
+
+pub fn signer(key: Pubkey) -> AccountInfo<'static> {
+    account_info(key, System::id(), Vec::new(), true, false)
+}
+
+pub fn executable(key: Pubkey) -> AccountInfo<'static> {
+    AccountInfo::new(
+        leak(key),
+        false,
+        false,
+        leak(1_000_000_000),
+        Box::leak(Vec::new().into_boxed_slice()),
+        leak(Pubkey::default()),
+        true,
+        0,
+    )
+}
+
+/// Copy `accounts` into the input layout the runtime hands a program, so that
+/// `AccountInfo::realloc` can grow them
+pub fn runtime_accounts(accounts: &[AccountInfo]) -> Vec<AccountInfo<'static>> {
+    let mut input = (accounts.len() as u64).to_le_bytes().to_vec();
+    for account in accounts {
+        input.extend([
+            NON_DUP_MARKER,
+            account.is_signer as u8,
+            account.is_writable as u8,
+            account.executable as u8,
+        ]);
+        input.extend([0; 4]);
+        input.extend(account.key.as_ref());
+        input.extend(account.owner.as_ref());
+        input.extend(account.lamports().to_le_bytes());
+        let data = account.data.borrow();
+        input.extend((data.len() as u64).to_le_bytes());
+        input.extend(data.iter());
+        input.resize(input.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        input.resize(input.len().next_multiple_of(8), 0);
+        input.extend(account.rent_epoch.to_le_bytes());
+    }
+    input.extend(0u64.to_le_bytes());
+    input.extend(ID.as_ref());
+
+    // The runtime input is 8-byte aligned
+    let aligned = leak(vec![0u64; input.len().div_ceil(8)]);
+    let aligned = aligned.as_mut_ptr() as *mut u8;
+    unsafe {
+        std::ptr::copy_nonoverlapping(input.as_ptr(), aligned, input.len());
+        entrypoint::deserialize(aligned).1
+    }
+}
+
+struct Sysvars;
+
+impl SyscallStubs for Sysvars {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Clock) = Clock::default() };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+}
+
+/// Serve default `Clock` and `Rent` sysvars to instruction handlers
+pub fn stub_sysvars() {
+    program_stubs::set_syscall_stubs(Box::new(Sysvars));
+}
@@ -0,0 +1,285 @@
+//! Token custody bookkeeping and account validation
+
+mod common;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_spl::token_2022::spl_token_2022::state::{
+    Account as SplAccount, AccountState, Mint as SplMint,
+};
+use common::{account_info, executable, program_account, signer};
+use ninjapay_vault_lite::{
+    Config, Custody, Deposit, DepositBumps, Vault, VaultBalance, VaultError, Withdraw,
+    WithdrawBumps, ID,
+};
+use std::collections::BTreeSet;
+
+struct Fixture {
+    owner: Pubkey,
+    service: Pubkey,
+    token_program: Pubkey,
+    mint: Pubkey,
+    config: AccountInfo<'static>,
+    vault: AccountInfo<'static>,
+    balance: AccountInfo<'static>,
+    custody: AccountInfo<'static>,
+    custody_tokens: AccountInfo<'static>,
+    mint_account: AccountInfo<'static>,
+}
+
+fn token_account(
+    token_program: Pubkey,
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> AccountInfo<'static> {
+    let mut data = vec![0; SplAccount::LEN];
+    SplAccount::pack(
+        SplAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Default::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    account_info(Pubkey::new_unique(), token_program, data, false, true)
+}
+
+fn fixture(token_program: Pubkey) -> Fixture {
+    let owner = Pubkey::new_unique();
+    let service = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+
+    let (config_key, config_bump) = Pubkey::find_program_address(&[b"config"], &ID);
+    let (vault_key, vault_bump) = Pubkey::find_program_address(&[b"vault", owner.as_ref()], &ID);
+    let (balance_key, balance_bump) =
+        Pubkey::find_program_address(&[b"vault_balance", vault_key.as_ref(), mint.as_ref()], &ID);
+    let (custody_key, custody_bump) =
+        Pubkey::find_program_address(&[b"custody", vault_key.as_ref(), mint.as_ref()], &ID);
+
+    let custody_tokens = token_account(token_program, mint, vault_key, 500);
+
+    let mut mint_data = vec![0; SplMint::LEN];
+    SplMint::pack(
+        SplMint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        },
+        &mut mint_data,
+    )
+    .unwrap();
+
+    Fixture {
+        owner,
+        service,
+        token_program,
+        mint,
+        config: program_account(
+            config_key,
+            &Config {
+                admin: Pubkey::new_unique(),
+                service_authority: service,
+                bump: config_bump,
+                payment_retention: 0,
+            },
+        ),
+        vault: program_account(
+            vault_key,
+            &Vault {
+                version: Vault::VERSION,
+                owner,
+                last_updated: 0,
+                bump: vault_bump,
+            },
+        ),
+        balance: program_account(
+            balance_key,
+            &VaultBalance {
+                vault: vault_key,
+                mint,
+                encrypted_balance: Vec::new(),
+                last_updated: 0,
+                bump: balance_bump,
+            },
+        ),
+        custody: program_account(
+            custody_key,
+            &Custody {
+                vault: vault_key,
+                mint,
+                token_account: custody_tokens.key(),
+                total_deposited: 800,
+                total_withdrawn: 300,
+                bump: custody_bump,
+            },
+        ),
+        custody_tokens,
+        mint_account: account_info(mint, token_program, mint_data, false, false),
+    }
+}
+
+fn deposit_accounts(f: &Fixture, owner: Pubkey, authority: Pubkey) -> Result<Deposit<'static>> {
+    let accounts = Box::leak(Box::new([
+        f.vault.clone(),
+        f.balance.clone(),
+        f.custody.clone(),
+        f.custody_tokens.clone(),
+        token_account(f.token_program, f.mint, owner, 1_000),
+        f.mint_account.clone(),
+        signer(owner),
+        f.config.clone(),
+        signer(authority),
+        executable(f.token_program),
+    ]));
+    Deposit::try_accounts(
+        &ID,
+        &mut &accounts[..],
+        &[],
+        &mut DepositBumps::default(),
+        &mut BTreeSet::new(),
+    )
+}
+
+fn withdraw_accounts(
+    f: &Fixture,
+    custody_tokens: &AccountInfo<'static>,
+    authority: Pubkey,
+) -> Result<Withdraw<'static>> {
+    let accounts = Box::leak(Box::new([
+        f.vault.clone(),
+        f.balance.clone(),
+        f.custody.clone(),
+        custody_tokens.clone(),
+        token_account(f.token_program, f.mint, Pubkey::new_unique(), 0),
+        f.mint_account.clone(),
+        signer(f.owner),
+        f.config.clone(),
+        signer(authority),
+        executable(f.token_program),
+    ]));
+    Withdraw::try_accounts(
+        &ID,
+        &mut &accounts[..],
+        &[],
+        &mut WithdrawBumps::default(),
+        &mut BTreeSet::new(),
+    )
+}
+
+fn custody(total_deposited: u64, total_withdrawn: u64) -> Custody {
+    Custody {
+        vault: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        token_account: Pubkey::new_unique(),
+        total_deposited,
+        total_withdrawn,
+        bump: 255,
+    }
+}
+
+#[test]
+fn totals_track_custody_balance() {
+    let mut custody = custody(0, 0);
+
+    custody.record_deposit(1_000).unwrap();
+    custody.check_invariant(1_000).unwrap();
+
+    custody.record_withdrawal(400).unwrap();
+    assert_eq!(custody.held().unwrap(), 600);
+    custody.check_invariant(600).unwrap();
+
+    assert_eq!(
+        custody.check_invariant(599).err(),
+        Some(VaultError::CustodyInvariantViolated.into())
+    );
+}
+
+#[test]
+fn direct_transfer_does_not_block_withdrawals() {
+    let f = fixture(anchor_spl::token::ID);
+
+    // 200 tokens sent straight to the custody account on top of the 500 held
+    let vault = f.vault.key();
+    let donated = token_account(f.token_program, f.mint, vault, 700);
+    let custody_key = f.custody.key();
+    let mut custody = Custody::try_deserialize(&mut &f.custody.data.borrow()[..]).unwrap();
+    custody.token_account = donated.key();
+    let custody_account = program_account(custody_key, &custody);
+    let f = Fixture {
+        custody: custody_account,
+        custody_tokens: donated,
+        ..f
+    };
+    assert!(withdraw_accounts(&f, &f.custody_tokens, f.service).is_ok());
+
+    custody.check_invariant(700).unwrap();
+    custody.record_withdrawal(500).unwrap();
+    custody.check_invariant(200).unwrap();
+
+    // The surplus is not the vault's to withdraw
+    assert_eq!(
+        custody.record_withdrawal(1).err(),
+        Some(VaultError::InsufficientCustody.into())
+    );
+}
+
+#[test]
+fn withdrawal_cannot_exceed_holdings() {
+    let mut custody = custody(500, 200);
+
+    assert_eq!(
+        custody.record_withdrawal(301).err(),
+        Some(VaultError::InsufficientCustody.into())
+    );
+    // A rejected withdrawal leaves the totals untouched
+    assert_eq!(custody.total_withdrawn, 200);
+
+    custody.record_withdrawal(300).unwrap();
+    assert_eq!(custody.held().unwrap(), 0);
+}
+
+#[test]
+fn totals_do_not_overflow() {
+    let mut custody = custody(u64::MAX, 0);
+
+    assert_eq!(
+        custody.record_deposit(1).err(),
+        Some(VaultError::MathOverflow.into())
+    );
+}
+
+#[test]
+fn deposit_requires_owner_and_service_authority() {
+    for token_program in [anchor_spl::token::ID, anchor_spl::token_2022::ID] {
+        let f = fixture(token_program);
+
+        assert!(deposit_accounts(&f, f.owner, f.service).is_ok());
+        assert_eq!(
+            deposit_accounts(&f, f.owner, Pubkey::new_unique()).err(),
+            Some(VaultError::Unauthorized.into())
+        );
+        assert_eq!(
+            deposit_accounts(&f, Pubkey::new_unique(), f.service).err(),
+            Some(VaultError::Unauthorized.into())
+        );
+    }
+}
+
+#[test]
+fn withdraw_only_from_recorded_custody_account() {
+    let f = fixture(anchor_spl::token::ID);
+
+    assert!(withdraw_accounts(&f, &f.custody_tokens, f.service).is_ok());
+    assert_eq!(
+        withdraw_accounts(&f, &f.custody_tokens, f.owner).err(),
+        Some(VaultError::Unauthorized.into())
+    );
+
+    let vault = f.vault.key();
+    let other = token_account(f.token_program, f.mint, vault, 500);
+    assert!(withdraw_accounts(&f, &other, f.service).is_err());
+}
@@ -0,0 +1,223 @@
+//! Payment status transitions, expiry and closing
+
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{account_info, program_account, signer};
+use ninjapay_vault_lite::{
+    CancelPayment, CancelPaymentBumps, ClosePayment, ClosePaymentBumps, Config, PaymentRecord,
+    PaymentStatus, Vault, VaultError, ID,
+};
+use std::collections::BTreeSet;
+use PaymentStatus::*;
+
+const ALL: [PaymentStatus; 7] = [
+    Pending,
+    Completed,
+    Failed,
+    Cancelled,
+    Refunded,
+    PartiallyRefunded,
+    Expired,
+];
+
+fn payment(status: PaymentStatus, expires_at: Option<i64>) -> PaymentRecord {
+    PaymentRecord {
+        version: PaymentRecord::VERSION,
+        vault: Pubkey::new_unique(),
+        payment_id: "pi_123".to_string(),
+        encrypted_amount: vec![1; 36],
+        recipient: Pubkey::new_unique(),
+        timestamp: 0,
+        status,
+        bump: 255,
+        mint: Pubkey::new_unique(),
+        expires_at,
+        encrypted_refund_amount: Vec::new(),
+        payer: Pubkey::new_unique(),
+        updated_at: 0,
+        encrypted_memo: Vec::new(),
+        encrypted_payer: Vec::new(),
+    }
+}
+
+#[test]
+fn only_pending_payments_settle() {
+    for next in [Completed, Failed, Cancelled, Expired] {
+        for from in ALL {
+            assert_eq!(
+                from.can_transition_to(next),
+                from == Pending,
+                "{:?} -> {:?}",
+                from,
+                next
+            );
+        }
+    }
+}
+
+#[test]
+fn only_completed_payments_refund() {
+    for next in [Refunded, PartiallyRefunded] {
+        for from in ALL {
+            assert_eq!(
+                from.can_transition_to(next),
+                matches!(from, Completed | PartiallyRefunded),
+                "{:?} -> {:?}",
+                from,
+                next
+            );
+        }
+    }
+}
+
+#[test]
+fn terminal_states_stay_put() {
+    for from in [Failed, Cancelled, Refunded, Expired] {
+        for next in ALL {
+            assert!(!from.can_transition_to(next), "{:?} -> {:?}", from, next);
+        }
+    }
+}
+
+#[test]
+fn rejected_transition_keeps_status() {
+    let mut record = payment(Failed, None);
+
+    assert_eq!(
+        record.transition(Completed, 0).err(),
+        Some(VaultError::InvalidStatusTransition.into())
+    );
+    assert_eq!(record.status, Failed);
+
+    let mut record = payment(Completed, None);
+    record.transition(PartiallyRefunded, 10).unwrap();
+    record.transition(Refunded, 20).unwrap();
+    assert_eq!(record.status, Refunded);
+    assert_eq!(record.updated_at, 20);
+}
+
+#[test]
+fn closing_waits_for_settlement_and_retention() {
+    let retention = 3_600;
+    assert!(!payment(Pending, None).is_closable(i64::MAX, retention));
+
+    let mut record = payment(Pending, None);
+    record.transition(Completed, 1_000).unwrap();
+    assert!(!record.is_closable(4_599, retention));
+    assert!(record.is_closable(4_600, retention));
+
+    // A refund restarts the retention period
+    record.transition(PartiallyRefunded, 4_000).unwrap();
+    assert!(!record.is_closable(4_600, retention));
+    assert!(record.is_closable(7_600, retention));
+}
+
+#[test]
+fn expiry_applies_to_pending_payments() {
+    assert!(!payment(Pending, None).is_expired(i64::MAX));
+
+    let pending = payment(Pending, Some(1_000));
+    assert!(!pending.is_expired(999));
+    assert!(pending.is_expired(1_000));
+
+    // Settled payments are never expired, whatever the clock says
+    assert!(!payment(Completed, Some(1_000)).is_expired(2_000));
+}
+
+struct Fixture {
+    owner: Pubkey,
+    service: Pubkey,
+    payer: Pubkey,
+    accounts: [AccountInfo<'static>; 3],
+}
+
+fn fixture() -> Fixture {
+    let owner = Pubkey::new_unique();
+    let service = Pubkey::new_unique();
+
+    let (config_key, config_bump) = Pubkey::find_program_address(&[b"config"], &ID);
+    let (vault_key, vault_bump) = Pubkey::find_program_address(&[b"vault", owner.as_ref()], &ID);
+    let mut record = payment(Pending, None);
+    record.vault = vault_key;
+    let (payment_key, payment_bump) = Pubkey::find_program_address(
+        &[b"payment", vault_key.as_ref(), record.payment_id.as_bytes()],
+        &ID,
+    );
+    record.bump = payment_bump;
+
+    Fixture {
+        owner,
+        service,
+        payer: record.payer,
+        accounts: [
+            program_account(
+                vault_key,
+                &Vault {
+                    version: Vault::VERSION,
+                    owner,
+                    last_updated: 0,
+                    bump: vault_bump,
+                },
+            ),
+            program_account(payment_key, &record),
+            program_account(
+                config_key,
+                &Config {
+                    admin: Pubkey::new_unique(),
+                    service_authority: service,
+                    bump: config_bump,
+                    payment_retention: 0,
+                },
+            ),
+        ],
+    }
+}
+
+fn cancel_accounts(f: &Fixture, authority: Pubkey) -> Result<CancelPayment<'static>> {
+    let [vault, payment, config] = f.accounts.clone();
+    let accounts = Box::leak(Box::new([vault, payment, config, signer(authority)]));
+    CancelPayment::try_accounts(
+        &ID,
+        &mut &accounts[..],
+        &[],
+        &mut CancelPaymentBumps::default(),
+        &mut BTreeSet::new(),
+    )
+}
+
+#[test]
+fn cancel_requires_owner_or_service() {
+    let f = fixture();
+
+    assert!(cancel_accounts(&f, f.owner).is_ok());
+    assert!(cancel_accounts(&f, f.service).is_ok());
+    assert_eq!(
+        cancel_accounts(&f, Pubkey::new_unique()).err(),
+        Some(VaultError::Unauthorized.into())
+    );
+}
+
+fn close_accounts(f: &Fixture, payer: Pubkey) -> Result<ClosePayment<'static>> {
+    let [vault, payment, config] = f.accounts.clone();
+    let payer = account_info(payer, System::id(), Vec::new(), false, true);
+    let accounts = Box::leak(Box::new([vault, payment, config, payer]));
+    ClosePayment::try_accounts(
+        &ID,
+        &mut &accounts[..],
+        &[],
+        &mut ClosePaymentBumps::default(),
+        &mut BTreeSet::new(),
+    )
+}
+
+#[test]
+fn close_refunds_the_original_payer() {
+    let f = fixture();
+
+    assert!(close_accounts(&f, f.payer).is_ok());
+    assert_eq!(
+        close_accounts(&f, f.owner).err(),
+        Some(VaultError::Unauthorized.into())
+    );
+}
@@ -0,0 +1,327 @@
+//! Upgrading accounts stored under older layouts
+
+mod common;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::Discriminator;
+use anchor_spl::token_2022::spl_token_2022::state::Mint as SplMint;
+use common::{account_info, executable, leak, runtime_accounts, signer, stub_sysvars};
+use ninjapay_vault_lite::{
+    MigratePayment, MigratePaymentBumps, MigrateVault, MigrateVaultBumps, PaymentRecord,
+    PaymentRecordV1, PaymentRecordV2, PaymentStatus, Vault, VaultBalance, VaultError, VaultV1,
+    VaultV2, ID,
+};
+use std::collections::BTreeSet;
+
+/// Account data as the program allocated it: discriminator, body, zero padding
+fn stored(discriminator: [u8; 8], body: &impl AnchorSerialize, size: usize) -> Vec<u8> {
+    let mut data = discriminator.to_vec();
+    body.serialize(&mut data).unwrap();
+    assert!(data.len() <= size);
+    data.resize(size, 0);
+    data
+}
+
+/// Write `account` into a buffer of `size` bytes, as after the realloc, and
+/// read it back
+fn reload<T: AccountSerialize + AccountDeserialize>(account: &T, size: usize) -> T {
+    let mut data = vec![0; size];
+    account.try_serialize(&mut &mut data[..]).unwrap();
+    T::try_deserialize(&mut &data[..]).unwrap()
+}
+
+fn v1_payment(vault: Pubkey) -> PaymentRecordV1 {
+    PaymentRecordV1 {
+        vault,
+        payment_id: "p".repeat(64),
+        encrypted_amount: vec![7; 128],
+        recipient: Pubkey::new_unique(),
+        timestamp: 1_700_000_000,
+        status: PaymentStatus::Completed,
+        bump: 254,
+    }
+}
+
+fn mint_account(key: Pubkey) -> AccountInfo<'static> {
+    let mut data = vec![0; SplMint::LEN];
+    SplMint::pack(
+        SplMint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    account_info(key, anchor_spl::token::ID, data, false, false)
+}
+
+#[test]
+fn v1_vault_reads_back_after_upgrade() {
+    let v1 = VaultV1 {
+        owner: Pubkey::new_unique(),
+        encrypted_balance: vec![3; 256],
+        last_updated: 1_700_000_000,
+        bump: 253,
+    };
+    let data = stored(Vault::DISCRIMINATOR, &v1, VaultV1::SIZE);
+
+    let (vault, legacy_balance) = Vault::migrate(&data).unwrap();
+    let vault = reload(&vault, Vault::MAX_SIZE);
+    assert_eq!(vault.version, Vault::VERSION);
+    assert_eq!(vault.owner, v1.owner);
+    assert_eq!(vault.last_updated, v1.last_updated);
+    assert_eq!(vault.bump, v1.bump);
+    assert_eq!(legacy_balance, v1.encrypted_balance);
+}
+
+#[test]
+fn v2_vault_hands_over_its_balance() {
+    let v2 = VaultV2 {
+        encrypted_balance: vec![4; 96],
+        ..VaultV1 {
+            owner: Pubkey::new_unique(),
+            encrypted_balance: Vec::new(),
+            last_updated: 1_700_000_000,
+            bump: 252,
+        }
+        .upgrade()
+    };
+    let data = stored(Vault::DISCRIMINATOR, &v2, VaultV2::SIZE);
+
+    let (vault, legacy_balance) = Vault::migrate(&data).unwrap();
+    let vault = reload(&vault, Vault::MAX_SIZE);
+    assert_eq!(vault.version, Vault::VERSION);
+    assert_eq!(vault.owner, v2.owner);
+    assert_eq!(vault.bump, v2.bump);
+    assert_eq!(legacy_balance, v2.encrypted_balance);
+}
+
+#[test]
+fn v1_payment_reads_back_after_upgrade() {
+    let v1 = v1_payment(Pubkey::new_unique());
+    let owner = Pubkey::new_unique();
+    let data = stored(PaymentRecord::DISCRIMINATOR, &v1, PaymentRecordV1::SIZE);
+
+    let payment = reload(
+        &PaymentRecord::migrate(&data, owner).unwrap(),
+        PaymentRecord::MAX_SIZE,
+    );
+    assert_eq!(payment.version, PaymentRecord::VERSION);
+    assert_eq!(payment.vault, v1.vault);
+    assert_eq!(payment.payment_id, v1.payment_id);
+    assert_eq!(payment.encrypted_amount, v1.encrypted_amount);
+    assert_eq!(payment.recipient, v1.recipient);
+    assert_eq!(payment.status, PaymentStatus::Completed);
+    assert_eq!(payment.bump, v1.bump);
+    assert_eq!(payment.mint, Pubkey::default());
+    assert_eq!(payment.expires_at, None);
+    assert!(payment.encrypted_refund_amount.is_empty());
+    assert_eq!(payment.payer, owner);
+    // Retention counts from the last status change, unknown for v1 records
+    assert_eq!(payment.updated_at, v1.timestamp);
+}
+
+#[test]
+fn v2_payment_gains_empty_memo_and_payer() {
+    let v2 = PaymentRecordV2 {
+        expires_at: Some(1_700_003_600),
+        encrypted_refund_amount: vec![9; 36],
+        updated_at: 1_700_000_100,
+        ..v1_payment(Pubkey::new_unique()).upgrade(Pubkey::new_unique())
+    };
+    // v2 records were allocated at their own full size
+    let data = stored(PaymentRecord::DISCRIMINATOR, &v2, 1_000);
+
+    let payment = reload(
+        &PaymentRecord::migrate(&data, Pubkey::new_unique()).unwrap(),
+        PaymentRecord::MAX_SIZE,
+    );
+    assert_eq!(payment.version, PaymentRecord::VERSION);
+    assert_eq!(payment.payment_id, v2.payment_id);
+    assert_eq!(payment.mint, v2.mint);
+    assert_eq!(payment.expires_at, v2.expires_at);
+    assert_eq!(payment.encrypted_refund_amount, v2.encrypted_refund_amount);
+    // The rent payer recorded in v2 is kept
+    assert_eq!(payment.payer, v2.payer);
+    assert_eq!(payment.updated_at, v2.updated_at);
+    assert!(payment.encrypted_memo.is_empty());
+    assert!(payment.encrypted_payer.is_empty());
+}
+
+#[test]
+fn only_older_layouts_migrate() {
+    let current = Vault {
+        version: Vault::VERSION,
+        owner: Pubkey::new_unique(),
+        last_updated: 0,
+        bump: 255,
+    };
+    let mut data = vec![0; Vault::MAX_SIZE];
+    current.try_serialize(&mut &mut data[..]).unwrap();
+    assert_eq!(
+        Vault::migrate(&data).err(),
+        Some(VaultError::AccountAlreadyMigrated.into())
+    );
+
+    data[8] = Vault::VERSION + 1;
+    assert_eq!(
+        Vault::migrate(&data).err(),
+        Some(VaultError::UnsupportedAccountVersion.into())
+    );
+
+    let payment = stored(
+        PaymentRecord::DISCRIMINATOR,
+        &v1_payment(Pubkey::new_unique()),
+        PaymentRecordV1::SIZE,
+    );
+    assert_eq!(
+        Vault::migrate(&payment).err(),
+        Some(ErrorCode::AccountDiscriminatorMismatch.into())
+    );
+}
+
+/// Accounts for `migrate_vault` on a v2 vault, in the runtime layout
+///
+/// Instruction handlers cannot run CPIs here, so the balance account starts
+/// out as `init` leaves it: allocated, owned by the program and zeroed.
+fn migrate_vault_accounts(vault_owner: Pubkey, signer_key: Pubkey) -> Vec<AccountInfo<'static>> {
+    let (vault_key, bump) = Pubkey::find_program_address(&[b"vault", vault_owner.as_ref()], &ID);
+    let mint = Pubkey::new_unique();
+    let (balance_key, _) =
+        Pubkey::find_program_address(&[b"vault_balance", vault_key.as_ref(), mint.as_ref()], &ID);
+    let v2 = VaultV2 {
+        version: 2,
+        owner: vault_owner,
+        encrypted_balance: vec![8; 64],
+        last_updated: 1_700_000_000,
+        bump,
+    };
+
+    runtime_accounts(&[
+        account_info(
+            vault_key,
+            ID,
+            stored(Vault::DISCRIMINATOR, &v2, VaultV2::SIZE),
+            false,
+            true,
+        ),
+        mint_account(mint),
+        account_info(
+            balance_key,
+            ID,
+            vec![0; VaultBalance::MAX_SIZE],
+            false,
+            true,
+        ),
+        signer(signer_key),
+        account_info(Pubkey::new_unique(), System::id(), Vec::new(), true, true),
+        executable(System::id()),
+    ])
+}
+
+fn migrate_vault(accounts: &[AccountInfo<'static>]) -> Result<()> {
+    let infos = Box::leak(accounts.to_vec().into_boxed_slice());
+    let mut bumps = MigrateVaultBumps::default();
+    let mut ctx =
+        MigrateVault::try_accounts(&ID, &mut &infos[..], &[], &mut bumps, &mut BTreeSet::new())?;
+    ninjapay_vault_lite::ninjapay_vault_lite::migrate_vault(Context::new(
+        &ID,
+        &mut ctx,
+        &[],
+        bumps,
+    ))?;
+    ctx.exit(&ID)
+}
+
+#[test]
+fn migrate_vault_moves_the_balance_to_the_mint() {
+    stub_sysvars();
+    let owner = Pubkey::new_unique();
+    let accounts = migrate_vault_accounts(owner, owner);
+    migrate_vault(&accounts).unwrap();
+
+    let (vault, mint, balance) = (&accounts[0], &accounts[1], &accounts[2]);
+    assert_eq!(vault.data_len(), Vault::MAX_SIZE);
+    let migrated = Vault::try_deserialize(&mut &vault.data.borrow()[..]).unwrap();
+    assert_eq!(migrated.version, Vault::VERSION);
+    assert_eq!(migrated.owner, owner);
+
+    let balance = VaultBalance::try_deserialize(&mut &balance.data.borrow()[..]).unwrap();
+    assert_eq!(balance.vault, vault.key());
+    assert_eq!(balance.mint, mint.key());
+    assert_eq!(balance.encrypted_balance, vec![8; 64]);
+    assert_eq!(balance.last_updated, migrated.last_updated);
+
+    // The vault can no longer be migrated
+    assert_eq!(
+        migrate_vault(&accounts).err(),
+        Some(VaultError::AccountAlreadyMigrated.into())
+    );
+}
+
+#[test]
+fn migrate_vault_needs_the_owner() {
+    stub_sysvars();
+    let accounts = migrate_vault_accounts(Pubkey::new_unique(), Pubkey::new_unique());
+    assert_eq!(
+        migrate_vault(&accounts).err(),
+        Some(VaultError::Unauthorized.into())
+    );
+}
+
+#[test]
+fn migrate_vault_only_takes_program_accounts() {
+    stub_sysvars();
+    let owner = Pubkey::new_unique();
+    let mut accounts = migrate_vault_accounts(owner, owner);
+    accounts[0].owner = leak(Pubkey::new_unique());
+    assert_eq!(
+        migrate_vault(&accounts).err(),
+        Some(ErrorCode::ConstraintOwner.into())
+    );
+}
+
+#[test]
+fn payments_migrate_after_their_vault() {
+    let owner = Pubkey::new_unique();
+    let (vault_key, bump) = Pubkey::find_program_address(&[b"vault", owner.as_ref()], &ID);
+    let v1 = VaultV1 {
+        owner,
+        encrypted_balance: Vec::new(),
+        last_updated: 0,
+        bump,
+    };
+    let payment = stored(
+        PaymentRecord::DISCRIMINATOR,
+        &v1_payment(vault_key),
+        PaymentRecordV1::SIZE,
+    );
+    let validate = |vault_data: Vec<u8>| {
+        let accounts = Box::leak(Box::new([
+            account_info(vault_key, ID, vault_data, false, false),
+            account_info(Pubkey::new_unique(), ID, payment.clone(), false, true),
+            account_info(Pubkey::new_unique(), System::id(), Vec::new(), true, true),
+            executable(System::id()),
+        ]));
+        MigratePayment::try_accounts(
+            &ID,
+            &mut &accounts[..],
+            &[],
+            &mut MigratePaymentBumps::default(),
+            &mut BTreeSet::new(),
+        )
+        .map(|_| ())
+    };
+
+    assert!(validate(stored(Vault::DISCRIMINATOR, &v1, VaultV1::SIZE)).is_err());
+
+    let mut migrated = vec![0; Vault::MAX_SIZE];
+    v1.upgrade()
+        .upgrade()
+        .0
+        .try_serialize(&mut &mut migrated[..])
+        .unwrap();
+    assert!(validate(migrated).is_ok());
+}
@@ -0,0 +1,275 @@
+//! Account sizes against the payload limits
+
+mod common;
+
+use anchor_lang::prelude::*;
+use common::{account_info, executable, program_account, runtime_accounts, stub_sysvars};
+use ninjapay_vault_lite::{
+    Config, Custody, PaymentRecord, PaymentStatus, ResizeBalance, ResizeBalanceBumps,
+    UpdateBalance, UpdateBalanceBumps, Vault, VaultBalance, VaultError, ID, MAX_ENCRYPTED_SIZE,
+    MAX_ID_LENGTH, MAX_MEMO_SIZE, MAX_PAYER_SIZE,
+};
+use std::collections::BTreeSet;
+
+fn serialized_len<T: AccountSerialize>(account: &T) -> usize {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data.len()
+}
+
+fn fits<T: AccountSerialize>(account: &T, space: usize) -> bool {
+    let mut data = vec![0; space];
+    account.try_serialize(&mut &mut data[..]).is_ok()
+}
+
+fn payment(encrypted_len: usize) -> PaymentRecord {
+    PaymentRecord {
+        version: PaymentRecord::VERSION,
+        vault: Pubkey::new_unique(),
+        payment_id: "p".repeat(MAX_ID_LENGTH),
+        encrypted_amount: vec![1; encrypted_len],
+        recipient: Pubkey::new_unique(),
+        timestamp: 0,
+        status: PaymentStatus::PartiallyRefunded,
+        bump: 255,
+        mint: Pubkey::new_unique(),
+        expires_at: Some(0),
+        encrypted_refund_amount: vec![2; encrypted_len],
+        payer: Pubkey::new_unique(),
+        updated_at: 0,
+        encrypted_memo: vec![5; MAX_MEMO_SIZE],
+        encrypted_payer: vec![6; MAX_PAYER_SIZE],
+    }
+}
+
+fn balance(encrypted_len: usize) -> VaultBalance {
+    VaultBalance {
+        vault: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        encrypted_balance: vec![3; encrypted_len],
+        last_updated: 0,
+        bump: 255,
+    }
+}
+
+#[test]
+fn max_size_is_exactly_a_full_account() {
+    assert_eq!(
+        serialized_len(&payment(MAX_ENCRYPTED_SIZE)),
+        PaymentRecord::MAX_SIZE
+    );
+    assert_eq!(
+        serialized_len(&balance(MAX_ENCRYPTED_SIZE)),
+        VaultBalance::MAX_SIZE
+    );
+    assert_eq!(
+        serialized_len(&Vault {
+            version: Vault::VERSION,
+            owner: Pubkey::new_unique(),
+            last_updated: 0,
+            bump: 255,
+        }),
+        Vault::MAX_SIZE
+    );
+    assert_eq!(
+        serialized_len(&Config {
+            admin: Pubkey::new_unique(),
+            service_authority: Pubkey::new_unique(),
+            bump: 255,
+            payment_retention: 0,
+        }),
+        Config::MAX_SIZE
+    );
+    assert_eq!(
+        serialized_len(&Custody {
+            vault: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            token_account: Pubkey::new_unique(),
+            total_deposited: 0,
+            total_withdrawn: 0,
+            bump: 255,
+        }),
+        Custody::MAX_SIZE
+    );
+}
+
+#[test]
+fn payment_amounts_up_to_the_limit_fit() {
+    // Amounts of 129 to 256 bytes used to pass validation and then overflow
+    for len in [0, 128, 129, MAX_ENCRYPTED_SIZE] {
+        assert!(
+            fits(&payment(len), PaymentRecord::MAX_SIZE),
+            "{} bytes",
+            len
+        );
+    }
+    assert!(!fits(
+        &payment(MAX_ENCRYPTED_SIZE + 1),
+        PaymentRecord::MAX_SIZE
+    ));
+}
+
+#[test]
+fn balance_space_tracks_the_ciphertext() {
+    assert_eq!(
+        VaultBalance::space(MAX_ENCRYPTED_SIZE),
+        VaultBalance::MAX_SIZE
+    );
+    for len in [0, 96, MAX_ENCRYPTED_SIZE] {
+        assert_eq!(serialized_len(&balance(len)), VaultBalance::space(len));
+    }
+}
+
+#[test]
+fn small_balance_grows_for_a_larger_ciphertext() {
+    stub_sysvars();
+    let owner = Pubkey::new_unique();
+    let service = Pubkey::new_unique();
+    let (vault_key, vault_bump) = Pubkey::find_program_address(&[b"vault", owner.as_ref()], &ID);
+    let (config_key, config_bump) = Pubkey::find_program_address(&[b"config"], &ID);
+    let mut record = balance(0);
+    let (balance_key, balance_bump) = Pubkey::find_program_address(
+        &[b"vault_balance", vault_key.as_ref(), record.mint.as_ref()],
+        &ID,
+    );
+    record.vault = vault_key;
+    record.bump = balance_bump;
+
+    // Opened for 32-byte ciphertexts
+    let mut data = vec![0; VaultBalance::space(32)];
+    record.try_serialize(&mut &mut data[..]).unwrap();
+    let accounts = runtime_accounts(&[
+        program_account(
+            vault_key,
+            &Vault {
+                version: Vault::VERSION,
+                owner,
+                last_updated: 0,
+                bump: vault_bump,
+            },
+        ),
+        account_info(balance_key, ID, data, false, true),
+        program_account(
+            config_key,
+            &Config {
+                admin: Pubkey::new_unique(),
+                service_authority: service,
+                bump: config_bump,
+                payment_retention: 0,
+            },
+        ),
+        account_info(service, System::id(), Vec::new(), true, true),
+        executable(System::id()),
+    ]);
+    let (vault, balance, config, signer, system) = (
+        &accounts[0],
+        &accounts[1],
+        &accounts[2],
+        &accounts[3],
+        &accounts[4],
+    );
+
+    let update = |encrypted_len: usize| {
+        let infos = Box::leak(Box::new([
+            vault.clone(),
+            balance.clone(),
+            config.clone(),
+            signer.clone(),
+        ]));
+        let mut bumps = UpdateBalanceBumps::default();
+        let mut ctx = UpdateBalance::try_accounts(
+            &ID,
+            &mut &infos[..],
+            &[],
+            &mut bumps,
+            &mut BTreeSet::new(),
+        )
+        .unwrap();
+        ninjapay_vault_lite::ninjapay_vault_lite::update_balance(
+            Context::new(&ID, &mut ctx, &[], bumps),
+            vec![7; encrypted_len],
+        )?;
+        ctx.exit(&ID)
+    };
+
+    update(32).unwrap();
+    assert_eq!(update(96).err(), Some(VaultError::AccountTooSmall.into()));
+
+    let infos = Box::leak(Box::new([
+        vault.clone(),
+        balance.clone(),
+        signer.clone(),
+        system.clone(),
+    ]));
+    let mut bumps = ResizeBalanceBumps::default();
+    let mut ctx =
+        ResizeBalance::try_accounts(&ID, &mut &infos[..], &[], &mut bumps, &mut BTreeSet::new())
+            .unwrap();
+    ninjapay_vault_lite::ninjapay_vault_lite::resize_balance(
+        Context::new(&ID, &mut ctx, &[], bumps),
+        96,
+    )
+    .unwrap();
+    ctx.exit(&ID).unwrap();
+    assert_eq!(balance.data_len(), VaultBalance::space(96));
+
+    update(96).unwrap();
+    let stored = VaultBalance::try_deserialize(&mut &balance.data.borrow()[..]).unwrap();
+    assert_eq!(stored.encrypted_balance, vec![7; 96]);
+
+    // Beyond the cap no resize helps
+    assert_eq!(
+        update(MAX_ENCRYPTED_SIZE + 1).err(),
+        Some(VaultError::DataTooLarge.into())
+    );
+}
+
+#[test]
+fn resize_balance_checks_the_balance_belongs_to_the_vault() {
+    let owner = Pubkey::new_unique();
+    let (vault_key, vault_bump) = Pubkey::find_program_address(&[b"vault", owner.as_ref()], &ID);
+    let vault = program_account(
+        vault_key,
+        &Vault {
+            version: Vault::VERSION,
+            owner,
+            last_updated: 0,
+            bump: vault_bump,
+        },
+    );
+
+    let validate = |balance_vault: Pubkey| {
+        let mut record = balance(32);
+        let (key, bump) = Pubkey::find_program_address(
+            &[
+                b"vault_balance",
+                balance_vault.as_ref(),
+                record.mint.as_ref(),
+            ],
+            &ID,
+        );
+        record.vault = balance_vault;
+        record.bump = bump;
+
+        let accounts = Box::leak(Box::new([
+            vault.clone(),
+            program_account(key, &record),
+            account_info(Pubkey::new_unique(), System::id(), Vec::new(), true, true),
+            executable(System::id()),
+        ]));
+        ResizeBalance::try_accounts(
+            &ID,
+            &mut &accounts[..],
+            &[],
+            &mut ResizeBalanceBumps::default(),
+            &mut BTreeSet::new(),
+        )
+        .map(|_| ())
+    };
+
+    assert!(validate(vault_key).is_ok());
+    assert_eq!(
+        validate(Pubkey::new_unique()).err(),
+        Some(ErrorCode::ConstraintSeeds.into())
+    );
+}
@@ -0,0 +1,105 @@
+//! Generates the instruction manifest from `encrypted-ixs/src/lib.rs`
+//!
+//! Every `#[instruction]` function in the circuits crate is recorded with its
+//! doc comment, typed parameters and return type in
+//! `$OUT_DIR/instruction_manifest.json`, which `InstructionLoader` embeds.
+//! The circuits are the single source of truth: adding, renaming or retyping
+//! one changes what the API validates and the simulator accepts.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const CIRCUITS_SOURCE: &str = "encrypted-ixs/src/lib.rs";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", CIRCUITS_SOURCE);
+
+    let source = fs::read_to_string(CIRCUITS_SOURCE)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", CIRCUITS_SOURCE, e));
+
+    let instructions = parse_instructions(&source);
+    if instructions.is_empty() {
+        panic!("no #[instruction] functions found in {}", CIRCUITS_SOURCE);
+    }
+
+    let manifest = serde_json::json!({ "instructions": instructions });
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("instruction_manifest.json");
+    fs::write(&out_path, serde_json::to_string_pretty(&manifest).unwrap())
+        .unwrap_or_else(|e| panic!("failed to write {}: {}", out_path.display(), e));
+}
+
+/// Collect every `#[instruction]` function with its preceding doc comment
+fn parse_instructions(source: &str) -> Vec<serde_json::Value> {
+    let mut instructions = Vec::new();
+    let mut doc: Vec<String> = Vec::new();
+    let mut is_instruction = false;
+    let mut lines = source.lines();
+
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+
+        if let Some(text) = line.strip_prefix("///") {
+            doc.push(text.trim().to_string());
+        } else if line == "#[instruction]" {
+            is_instruction = true;
+        } else if is_instruction && line.starts_with("pub fn ") {
+            // Signatures may wrap; read up to the opening brace
+            let mut signature = line.to_string();
+            while !signature.contains('{') {
+                match lines.next() {
+                    Some(next) => {
+                        signature.push(' ');
+                        signature.push_str(next.trim());
+                    }
+                    None => panic!("unterminated signature: {}", signature),
+                }
+            }
+            instructions.push(parse_signature(&signature, &doc));
+            doc.clear();
+            is_instruction = false;
+        } else if !line.starts_with("#[") {
+            doc.clear();
+            is_instruction = false;
+        }
+    }
+
+    instructions
+}
+
+/// Parse `pub fn name(a: T, b: U) -> R {`
+fn parse_signature(signature: &str, doc: &[String]) -> serde_json::Value {
+    let rest = &signature["pub fn ".len()..];
+    let open = rest
+        .find('(')
+        .unwrap_or_else(|| panic!("malformed signature: {}", signature));
+    let close = rest
+        .rfind(')')
+        .unwrap_or_else(|| panic!("malformed signature: {}", signature));
+
+    let name = rest[..open].trim();
+    let params: Vec<serde_json::Value> = rest[open + 1..close]
+        .split(',')
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (param_name, ty) = param
+                .split_once(':')
+                .unwrap_or_else(|| panic!("untyped parameter '{}' in {}", param, name));
+            serde_json::json!({ "name": param_name.trim(), "type": ty.trim() })
+        })
+        .collect();
+
+    let returns = rest[close + 1..]
+        .trim_start()
+        .strip_prefix("->")
+        .map(|ret| ret.split('{').next().unwrap_or_default().trim())
+        .unwrap_or_else(|| panic!("instruction {} must return a value", name));
+
+    serde_json::json!({
+        "name": name,
+        "description": doc.join(" "),
+        "params": params,
+        "returns": returns,
+    })
+}
@@ -1,8 +1,14 @@
 use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
 use arcium_anchor::{derive_cluster_pda, queue_computation};
 
 declare_id!("26gA8vfbazMA8SWXg71VsJ89XCs949XCni4fPPYFA5nz");
 
+// Offsets the `init_computation_definition_accounts` macro derives the
+// comp-def PDAs from
+const COMP_DEF_OFFSET_ENCRYPTED_TRANSFER: u32 = comp_def_offset("encrypted_transfer");
+const COMP_DEF_OFFSET_BATCH_PAYROLL: u32 = comp_def_offset("batch_payroll");
+
 #[program]
 pub mod ninjapay_vault {
     use super::*;
@@ -19,6 +25,49 @@ pub mod ninjapay_vault {
         Ok(())
     }
 
+    /// Register the `encrypted_transfer` circuit with Arcium
+    ///
+    /// With `circuit_source` the cluster fetches the circuit from its URL and
+    /// checks it against the hash; without one the circuit bytes must be
+    /// uploaded to the computation definition before it can be used. The
+    /// definition is permanent, so only the program's upgrade authority may
+    /// create it and choose the source.
+    pub fn init_encrypted_transfer_comp_def(
+        ctx: Context<InitEncryptedTransferCompDef>,
+        circuit_source: Option<OffChainSource>,
+    ) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            0,
+            circuit_source.map(CircuitSource::from),
+            None,
+        )?;
+
+        msg!("Computation definition initialized: encrypted_transfer");
+        Ok(())
+    }
+
+    /// Register the `batch_payroll` circuit with Arcium
+    ///
+    /// Restricted to the upgrade authority like
+    /// [`init_encrypted_transfer_comp_def`].
+    pub fn init_batch_payroll_comp_def(
+        ctx: Context<InitBatchPayrollCompDef>,
+        circuit_source: Option<OffChainSource>,
+    ) -> Result<()> {
+        init_comp_def(
+            ctx.accounts,
+            true,
+            0,
+            circuit_source.map(CircuitSource::from),
+            None,
+        )?;
+
+        msg!("Computation definition initialized: batch_payroll");
+        Ok(())
+    }
+
     /// Queue a confidential transfer computation
     pub fn confidential_transfer(
         ctx: Context<ConfidentialTransfer>,
@@ -65,6 +114,82 @@ pub struct InitializeVault<'info> {
     pub system_program: Program<'info, System>,
 }
 
+/// Where the cluster fetches a circuit from, and its SHA-256
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct OffChainSource {
+    pub source: String,
+    pub hash: [u8; 32],
+}
+
+impl From<OffChainSource> for CircuitSource {
+    fn from(source: OffChainSource) -> Self {
+        CircuitSource::OffChain(OffChainCircuitSource {
+            source: source.source,
+            hash: source.hash,
+        })
+    }
+}
+
+#[init_computation_definition_accounts("encrypted_transfer", payer)]
+#[derive(Accounts)]
+pub struct InitEncryptedTransferCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+
+    /// CHECK: created by the Arcium program at the offset for encrypted_transfer
+    #[account(mut)]
+    pub comp_def_account: UncheckedAccount<'info>,
+
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub program: Program<'info, crate::program::NinjapayVault>,
+
+    /// Program data of this program; `payer` must be its upgrade authority
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(payer.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+}
+
+#[init_computation_definition_accounts("batch_payroll", payer)]
+#[derive(Accounts)]
+pub struct InitBatchPayrollCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+
+    /// CHECK: created by the Arcium program at the offset for batch_payroll
+    #[account(mut)]
+    pub comp_def_account: UncheckedAccount<'info>,
+
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub program: Program<'info, crate::program::NinjapayVault>,
+
+    /// Program data of this program; `payer` must be its upgrade authority
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(payer.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+}
+
 #[derive(Accounts)]
 pub struct ConfidentialTransfer<'info> {
     #[account(
@@ -175,4 +300,6 @@ pub enum ErrorCode {
     InvalidComputationResult,
     #[msg("Vault not initialized")]
     VaultNotInitialized,
+    #[msg("Signer is not the program's upgrade authority")]
+    Unauthorized,
 }
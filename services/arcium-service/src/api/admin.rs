@@ -0,0 +1,55 @@
+//! Operator endpoints, restricted to internal services
+
+use crate::middleware::AuthContext;
+use crate::AppState;
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
+
+/// Prepare the cluster and register every circuit's computation definition
+///
+/// Idempotent: run it after each deployment. Uploading circuits can take
+/// minutes, so this starts a background job and answers `202 Accepted`;
+/// poll `GET /admin/initialize` for the outcome.
+#[post("/admin/initialize")]
+async fn initialize(app_state: web::Data<AppState>, auth: AuthContext) -> impl Responder {
+    if let Err(e) = auth.require_internal() {
+        return e.error_response();
+    }
+
+    HttpResponse::Accepted().json(app_state.mpc_client.start_initialization().await)
+}
+
+/// Status of the last initialization job
+#[get("/admin/initialize")]
+async fn initialization_status(
+    app_state: web::Data<AppState>,
+    auth: AuthContext,
+) -> impl Responder {
+    if let Err(e) = auth.require_internal() {
+        return e.error_response();
+    }
+
+    HttpResponse::Ok().json(app_state.mpc_client.initialization_status().await)
+}
+
+/// Close settled payment records and payroll batches whose retention period
+/// has passed
+#[post("/admin/sweep")]
+async fn sweep(app_state: web::Data<AppState>, auth: AuthContext) -> impl Responder {
+    if let Err(e) = auth.require_internal() {
+        return e.error_response();
+    }
+
+    match app_state.mpc_client.sweep_accounts().await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            log::error!("❌ Account sweep failed: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(initialize)
+        .service(initialization_status)
+        .service(sweep);
+}
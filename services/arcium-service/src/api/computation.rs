@@ -1,14 +1,24 @@
+use super::validation::{check_reference_part, validate_invoke};
+use crate::error::ServiceError;
+use crate::middleware::auth::AuthError;
+use crate::middleware::rate_limit::{consume_daily_quota, limit_user, refund_daily_quota};
+use crate::middleware::wallet_proof::verify_wallet_proof;
+use crate::middleware::AuthContext;
 use crate::mpc::{
-    ComputationRequest as MpcRequest, ComputationType, InstructionInfo, InstructionLoader,
+    instruction_spec, AccountRole, ComputationCursor, ComputationFilter,
+    ComputationRequest as MpcRequest, ComputationType, InstructionInfo, RegisteredInstruction,
 };
 use crate::AppState;
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder, ResponseError};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Deserialize)]
 pub struct InvokeComputationRequest {
     pub computation_type: String,
-    pub encrypted_inputs: Vec<String>, // Base64 or hex encoded
+    pub encrypted_inputs: Vec<String>,
+    /// Encoding of `encrypted_inputs`: "base64" or "hex"
+    pub encoding: Option<String>,
     pub user_pubkey: String,
     pub callback_url: Option<String>,
     pub entity_type: Option<String>,
@@ -39,6 +49,19 @@ pub struct ComputationStatusResponse {
     pub completed_at: Option<u64>,
 }
 
+#[derive(Deserialize)]
+pub struct ListComputationsQuery {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    pub status: Option<String>,
+    pub computation_type: Option<String>,
+    pub entity_type: Option<String>,
+    pub reference_id: Option<String>,
+}
+
+const DEFAULT_LIST_LIMIT: usize = 50;
+const MAX_LIST_LIMIT: usize = 200;
+
 #[derive(Deserialize)]
 pub struct ComputationCallbackRequest {
     pub computation_id: String,
@@ -49,49 +72,66 @@ pub struct ComputationCallbackRequest {
 /// Invoke a new MPC computation
 ///
 /// This endpoint accepts encrypted inputs and queues them for execution
-/// in the Arcium MPC cluster or local simulator.
+/// in the Arcium MPC cluster or local simulator. The request must carry a
+/// wallet signature by `user_pubkey` (see `middleware::wallet_proof`).
 #[post("/computation/invoke")]
 async fn invoke_computation(
     app_state: web::Data<AppState>,
-    req: web::Json<InvokeComputationRequest>,
+    auth: AuthContext,
+    http_req: HttpRequest,
+    body: web::Bytes,
 ) -> impl Responder {
+    // The raw body is needed for the wallet signature digest
+    let req: InvokeComputationRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => {
+            return ServiceError::Validation(format!("Invalid request body: {}", e))
+                .error_response();
+        }
+    };
+
     log::info!(
         "📥 Received computation request: {} for user: {}",
         req.computation_type,
         req.user_pubkey
     );
 
-    // Parse computation type
-    let computation_type = match req.computation_type.as_str() {
-        "confidential_transfer" | "encrypted_transfer" => ComputationType::ConfidentialTransfer,
-        "batch_payroll" => ComputationType::BatchPayroll,
-        "balance_query" | "query_balance" => ComputationType::BalanceQuery,
-        custom => ComputationType::Custom(custom.to_string()),
-    };
-
-    // Decode encrypted inputs from base64/hex
-    let encrypted_inputs: Result<Vec<Vec<u8>>, _> = req
-        .encrypted_inputs
-        .iter()
-        .map(|input| {
-            // Try base64 first, then hex
-            base64::decode(input)
-                .or_else(|_| hex::decode(input))
-                .map_err(|e| format!("Invalid input encoding: {}", e))
-        })
-        .collect();
-
-    let encrypted_inputs = match encrypted_inputs {
-        Ok(inputs) => inputs,
+    let validated = match validate_invoke(&req, app_state.mpc_client.registry()) {
+        Ok(validated) => validated,
         Err(e) => {
-            log::error!("❌ Failed to decode inputs: {}", e);
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "invalid_input",
-                "message": e
-            }));
+            log::warn!("⚠️  Rejected computation request: {}", e);
+            return e.error_response();
         }
     };
 
+    if let Err(e) = auth.authorize_user(&req.user_pubkey) {
+        return e.error_response();
+    }
+
+    if !validated.instruction.allows(&auth) {
+        return ServiceError::from(AuthError::Forbidden(format!(
+            "Not permitted to invoke '{}'",
+            validated.instruction.name
+        )))
+        .error_response();
+    }
+
+    if let Err(e) = verify_wallet_proof(
+        &auth,
+        &http_req,
+        &body,
+        &req.user_pubkey,
+        app_state.mpc_client.redis(),
+    )
+    .await
+    {
+        return e.error_response();
+    }
+
+    if let Err(e) = limit_user(&app_state, &auth, &req.user_pubkey).await {
+        return e.error_response();
+    }
+
     // Create MPC request
     let mut metadata = req
         .metadata
@@ -108,14 +148,21 @@ async fn invoke_computation(
     }
 
     let mpc_request = MpcRequest {
-        computation_type,
-        encrypted_inputs,
+        computation_type: validated.computation_type,
+        encrypted_inputs: validated.encrypted_inputs,
         user_pubkey: req.user_pubkey.clone(),
         metadata,
         callback_url: req.callback_url.clone(),
         entity_type: req.entity_type.clone(),
         reference_id: req.reference_id.clone(),
         user_signature: req.user_signature.clone(),
+        merchant_id: auth.merchant_id.clone(),
+    };
+
+    // Checked last so rejected requests don't use up the quota
+    let quota = match consume_daily_quota(&app_state, &auth).await {
+        Ok(quota) => quota,
+        Err(e) => return e.error_response(),
     };
 
     // Invoke computation
@@ -123,7 +170,11 @@ async fn invoke_computation(
         Ok(computation_id) => {
             log::info!("✅ Computation queued: {}", computation_id);
 
-            HttpResponse::Ok().json(InvokeComputationResponse {
+            let mut response = HttpResponse::Ok();
+            if let Some(quota) = &quota {
+                quota.apply_headers(&mut response);
+            }
+            response.json(InvokeComputationResponse {
                 computation_id: computation_id.clone(),
                 status: "queued".to_string(),
                 message: format!("Computation {} queued successfully", computation_id),
@@ -131,11 +182,11 @@ async fn invoke_computation(
         }
         Err(e) => {
             log::error!("❌ Failed to invoke computation: {}", e);
-
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "computation_failed",
-                "message": format!("Failed to invoke computation: {}", e)
-            }))
+            // Nothing was queued, so the computation does not count
+            if let Some(quota) = &quota {
+                refund_daily_quota(&app_state, &auth, quota).await;
+            }
+            e.error_response()
         }
     }
 }
@@ -146,10 +197,36 @@ async fn invoke_computation(
 #[get("/computation/status")]
 async fn get_computation_status(
     app_state: web::Data<AppState>,
+    auth: AuthContext,
     query: web::Query<ComputationStatusQuery>,
 ) -> impl Responder {
     log::debug!("🔍 Status check for: {}", query.computation_id);
 
+    match app_state
+        .mpc_client
+        .redis()
+        .get_computation_metadata(&query.computation_id)
+        .await
+    {
+        Ok(Some(metadata)) => {
+            if let Err(e) = auth.authorize_computation(&metadata) {
+                return e.error_response();
+            }
+        }
+        // Without metadata there is no owner to authorize against
+        Ok(None) => {
+            return ServiceError::NotFound(format!(
+                "Computation {} not found",
+                query.computation_id
+            ))
+            .error_response();
+        }
+        Err(e) => {
+            log::error!("❌ Failed to load computation metadata: {}", e);
+            return e.error_response();
+        }
+    }
+
     match app_state
         .mpc_client
         .get_computation_result(&query.computation_id)
@@ -199,11 +276,7 @@ async fn get_computation_status(
         }
         Err(e) => {
             log::error!("❌ Failed to get computation status: {}", e);
-
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "status_check_failed",
-                "message": format!("Failed to check status: {}", e)
-            }))
+            e.error_response()
         }
     }
 }
@@ -215,19 +288,22 @@ async fn get_computation_status(
 #[post("/computation/callback")]
 async fn computation_callback(
     app_state: web::Data<AppState>,
+    auth: AuthContext,
     req: web::Json<ComputationCallbackRequest>,
 ) -> impl Responder {
     log::info!("📥 Callback received for: {}", req.computation_id);
 
+    if let Err(e) = auth.require_internal() {
+        return e.error_response();
+    }
+
     // Decode result from base64
     let encrypted_result = match base64::decode(&req.result) {
         Ok(result) => result,
         Err(e) => {
             log::error!("❌ Invalid result encoding: {}", e);
-            return HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "invalid_encoding",
-                "message": "Result must be base64 encoded"
-            }));
+            return ServiceError::Validation("Result must be base64 encoded".to_string())
+                .error_response();
         }
     };
 
@@ -249,82 +325,250 @@ async fn computation_callback(
                 "computation_id": result.computation_id
             }))
         }
+        Err(e @ ServiceError::Conflict(_)) => {
+            log::warn!("⚠️  Rejected callback: {}", e);
+            e.error_response()
+        }
         Err(e) => {
             log::error!("❌ Failed to process callback: {}", e);
-
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "callback_failed",
-                "message": format!("Failed to process callback: {}", e)
-            }))
+            e.error_response()
         }
     }
 }
 
-/// List recent computations for a user
+/// List computations for a user, newest first
+///
+/// Supports cursor pagination (`limit`, `cursor`) and filtering by `status`,
+/// `computation_type`, `entity_type` and `reference_id`. The request must
+/// carry a wallet signature by `user_pubkey`.
 #[get("/computation/list/{user_pubkey}")]
 async fn list_user_computations(
     app_state: web::Data<AppState>,
+    auth: AuthContext,
+    http_req: HttpRequest,
     path: web::Path<String>,
+    query: web::Query<ListComputationsQuery>,
 ) -> impl Responder {
     let user_pubkey = path.into_inner();
     log::debug!("📋 Listing computations for: {}", user_pubkey);
 
+    if let Err(e) = auth.authorize_user(&user_pubkey) {
+        return e.error_response();
+    }
+
+    if let Err(e) = verify_wallet_proof(
+        &auth,
+        &http_req,
+        &[],
+        &user_pubkey,
+        app_state.mpc_client.redis(),
+    )
+    .await
+    {
+        return e.error_response();
+    }
+
+    if let Err(e) = limit_user(&app_state, &auth, &user_pubkey).await {
+        return e.error_response();
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+
+    let cursor = match query.cursor.as_deref().map(str::parse::<ComputationCursor>) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(e)) => return ServiceError::Validation(e).error_response(),
+        None => None,
+    };
+
+    let status = match query.status.as_deref().map(str::parse) {
+        Some(Ok(status)) => Some(status),
+        Some(Err(e)) => return ServiceError::Validation(e).error_response(),
+        None => None,
+    };
+
+    let filter = ComputationFilter {
+        merchant_id: auth.merchant_id.clone(),
+        status,
+        computation_type: query
+            .computation_type
+            .as_deref()
+            .map(ComputationType::from_name),
+        entity_type: query.entity_type.clone(),
+        reference_id: query.reference_id.clone(),
+    };
+
     match app_state
         .mpc_client
-        .list_user_computations(&user_pubkey, 50)
+        .list_user_computations(&user_pubkey, &filter, cursor.as_ref(), limit)
         .await
     {
-        Ok(computations) => {
-            log::debug!("✅ Found {} computations", computations.len());
+        Ok(page) => {
+            log::debug!("✅ Found {} computations", page.computations.len());
 
             HttpResponse::Ok().json(serde_json::json!({
-                "computations": computations
+                "computations": page.computations,
+                "next_cursor": page.next_cursor
             }))
         }
         Err(e) => {
             log::error!("❌ Failed to list computations: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// List computations linked to an external entity
+///
+/// Returns every attempt recorded for the given `entity_type` and
+/// `reference_id` (e.g. a payment intent or payroll run), newest first.
+#[get("/computation/by-reference/{entity_type}/{reference_id}")]
+async fn list_reference_computations(
+    app_state: web::Data<AppState>,
+    auth: AuthContext,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (entity_type, reference_id) = path.into_inner();
+    log::debug!(
+        "📋 Listing computations for {}/{}",
+        entity_type,
+        reference_id
+    );
+
+    for (field, value) in [
+        ("entity_type", &entity_type),
+        ("reference_id", &reference_id),
+    ] {
+        if let Err(e) = check_reference_part(value) {
+            return ServiceError::Validation(format!("{} {}", field, e)).error_response();
+        }
+    }
+
+    match app_state
+        .mpc_client
+        .list_reference_computations(&entity_type, &reference_id)
+        .await
+    {
+        Ok(computations) => {
+            // Only return attempts the caller is scoped to
+            let computations: Vec<_> = computations
+                .into_iter()
+                .filter(|metadata| auth.authorize_computation(metadata).is_ok())
+                .collect();
+            log::debug!("✅ Found {} computations", computations.len());
 
-            HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "list_failed",
-                "message": format!("Failed to list computations: {}", e)
+            HttpResponse::Ok().json(serde_json::json!({
+                "entity_type": entity_type,
+                "reference_id": reference_id,
+                "computations": computations
             }))
         }
+        Err(e) => {
+            log::error!("❌ Failed to list reference computations: {}", e);
+            e.error_response()
+        }
+    }
+}
+
+/// A registered computation as exposed by the instructions API
+#[derive(Serialize)]
+pub struct RegisteredInstructionResponse {
+    pub name: String,
+    pub circuit: String,
+    pub arity: usize,
+    pub description: String,
+    pub parameters: Vec<String>,
+    pub returns: String,
+    /// SHA-256 of the compiled circuit loaded by the simulator, if any
+    pub circuit_sha256: Option<String>,
+    pub cluster_instruction: String,
+    /// Hex-encoded Cluster mode discriminator
+    pub discriminator: String,
+    pub accounts: Vec<AccountRole>,
+    /// Only shown to internal callers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_callers: Option<Vec<String>>,
+}
+
+impl RegisteredInstructionResponse {
+    fn new(
+        entry: &RegisteredInstruction,
+        auth: &AuthContext,
+        circuit_hashes: &HashMap<String, String>,
+    ) -> Result<Self, ServiceError> {
+        let circuit = instruction_spec(&entry.circuit)
+            .map(InstructionInfo::from)
+            .ok_or_else(|| {
+                ServiceError::Configuration(format!(
+                    "Instruction '{}' references unknown circuit '{}'",
+                    entry.name, entry.circuit
+                ))
+            })?;
+        Ok(Self {
+            name: entry.name.clone(),
+            circuit: entry.circuit.clone(),
+            arity: entry.arity,
+            description: circuit.description,
+            parameters: circuit.parameters,
+            returns: circuit.returns,
+            circuit_sha256: circuit_hashes.get(&entry.circuit).cloned(),
+            cluster_instruction: entry.cluster_instruction.clone(),
+            discriminator: hex::encode(entry.cluster_discriminator()?),
+            accounts: entry.accounts.clone(),
+            allowed_callers: auth.is_internal().then(|| entry.allowed_callers.clone()),
+        })
     }
 }
 
-/// List available Arcium instructions
+/// List the computations the caller may invoke
 #[get("/computation/instructions")]
-async fn list_instructions() -> impl Responder {
-    let loader = InstructionLoader::new("build".to_string());
-
-    let instructions: Vec<InstructionInfo> = vec![
-        "encrypted_transfer",
-        "batch_payroll",
-        "query_balance",
-        "validate_amount",
-        "add_values",
-    ]
-    .iter()
-    .filter_map(|name| loader.get_instruction_info(name))
-    .collect();
-
-    HttpResponse::Ok().json(serde_json::json!({
-        "instructions": instructions
-    }))
+async fn list_instructions(app_state: web::Data<AppState>, auth: AuthContext) -> impl Responder {
+    let circuit_hashes = app_state.mpc_client.circuit_hashes();
+    let instructions: Result<Vec<_>, _> = app_state
+        .mpc_client
+        .registry()
+        .entries()
+        .into_iter()
+        .filter(|entry| entry.allows(&auth))
+        .map(|entry| RegisteredInstructionResponse::new(entry, &auth, &circuit_hashes))
+        .collect();
+
+    match instructions {
+        Ok(instructions) => HttpResponse::Ok().json(serde_json::json!({
+            "instructions": instructions
+        })),
+        Err(e) => e.error_response(),
+    }
 }
 
 /// Get specific instruction details
 #[get("/computation/instructions/{name}")]
-async fn get_instruction_details(path: web::Path<String>) -> impl Responder {
+async fn get_instruction_details(
+    app_state: web::Data<AppState>,
+    auth: AuthContext,
+    path: web::Path<String>,
+) -> impl Responder {
     let name = path.into_inner();
-    let loader = InstructionLoader::new("build".to_string());
-
-    match loader.get_instruction_info(&name) {
-        Some(info) => HttpResponse::Ok().json(info),
-        None => HttpResponse::NotFound().json(serde_json::json!({
-            "error": "instruction_not_found",
-            "message": format!("Instruction '{}' not found", name)
-        })),
+    let entry = app_state
+        .mpc_client
+        .registry()
+        .get(&ComputationType::from_name(&name))
+        .filter(|entry| entry.allows(&auth));
+
+    match entry {
+        Some(entry) => match RegisteredInstructionResponse::new(
+            entry,
+            &auth,
+            &app_state.mpc_client.circuit_hashes(),
+        ) {
+            Ok(details) => HttpResponse::Ok().json(details),
+            Err(e) => e.error_response(),
+        },
+        None => {
+            ServiceError::NotFound(format!("Instruction '{}' not found", name)).error_response()
+        }
     }
 }
 
@@ -333,6 +577,7 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         .service(get_computation_status)
         .service(computation_callback)
         .service(list_user_computations)
+        .service(list_reference_computations)
         .service(list_instructions)
         .service(get_instruction_details);
 }
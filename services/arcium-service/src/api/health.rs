@@ -1,3 +1,4 @@
+use crate::mpc::{IntegrityReport, VerificationPolicy};
 use crate::AppState;
 use actix_web::{get, web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
@@ -10,12 +11,16 @@ pub struct HealthResponse {
     version: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize)]
 pub struct DetailedHealthResponse {
     status: String,
     timestamp: u64,
     version: String,
     services: ServiceHealth,
+    /// Circuit verification against on-chain computation definitions
+    /// (Cluster mode only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    circuit_integrity: Option<IntegrityReport>,
     uptime_ms: u128,
 }
 
@@ -47,7 +52,7 @@ async fn detailed_health(data: web::Data<AppState>) -> impl Responder {
     // Real health checks for all services
     let redis_status = check_redis_health(&data).await;
     let solana_status = check_solana_health().await;
-    let arcium_status = check_arcium_health(&data).await;
+    let (arcium_status, circuit_integrity) = check_arcium_health(&data).await;
 
     let overall_status =
         if redis_status == "healthy" && solana_status == "healthy" && arcium_status == "healthy" {
@@ -68,6 +73,7 @@ async fn detailed_health(data: web::Data<AppState>) -> impl Responder {
             solana_rpc: solana_status,
             arcium_cluster: arcium_status,
         },
+        circuit_integrity,
         uptime_ms: START_TIME.elapsed().as_millis(),
     })
 }
@@ -120,22 +126,39 @@ async fn check_solana_health() -> String {
     }
 }
 
-async fn check_arcium_health(data: &web::Data<AppState>) -> String {
+async fn check_arcium_health(data: &web::Data<AppState>) -> (String, Option<IntegrityReport>) {
     // Check MPC simulator/cluster availability
     match data.mpc_client.mode() {
         crate::mpc::MpcMode::Local => {
             // In local mode, check if simulator has instructions loaded
             if data.mpc_client.list_instructions().is_empty() {
                 log::warn!("MPC simulator has no instructions loaded");
-                "degraded".to_string()
+                ("degraded".to_string(), None)
             } else {
-                "healthy".to_string()
+                ("healthy".to_string(), None)
             }
         }
         crate::mpc::MpcMode::Cluster => {
-            // In cluster mode, would check cluster connectivity
-            // For now, return healthy if client is initialized
-            "healthy".to_string()
+            // In cluster mode, check the circuits match the computation definitions
+            match data.mpc_client.circuit_integrity().await {
+                Ok(Some(report)) if !report.verified => {
+                    log::warn!(
+                        "Circuit integrity check failed: {}",
+                        report.failures().join(", ")
+                    );
+                    let status = if report.policy == VerificationPolicy::Enforce {
+                        "unhealthy"
+                    } else {
+                        "degraded"
+                    };
+                    (status.to_string(), Some(report))
+                }
+                Ok(report) => ("healthy".to_string(), report),
+                Err(e) => {
+                    log::error!("Circuit integrity check failed: {}", e);
+                    ("degraded".to_string(), None)
+                }
+            }
         }
     }
 }
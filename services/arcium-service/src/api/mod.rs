@@ -1,3 +1,6 @@
 pub mod account;
+pub mod admin;
 pub mod computation;
 pub mod health;
+pub mod payment;
+pub mod validation;
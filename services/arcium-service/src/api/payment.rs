@@ -0,0 +1,102 @@
+//! Merchant-only payment metadata on vault-lite payment records
+//!
+//! A payment's memo or invoice reference and its payer identity are encrypted
+//! under the vault owner's key before `record_payment`, and the service only
+//! decrypts them for the owner. The key is derived from the service master
+//! key, so the service itself can read them; see `mpc::vault_lite`.
+
+use crate::middleware::wallet_proof::verify_wallet_proof;
+use crate::middleware::AuthContext;
+use crate::AppState;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder, ResponseError};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct EncryptMetadataRequest {
+    /// Vault owner the metadata is encrypted to
+    pub owner_pubkey: String,
+    pub memo: Option<String>,
+    pub payer_identity: Option<String>,
+}
+
+/// Base64 ciphertexts to pass to `record_payment`; empty when the field was
+/// not given
+#[derive(Serialize)]
+pub struct EncryptMetadataResponse {
+    pub encrypted_memo: String,
+    pub encrypted_payer: String,
+}
+
+/// Encrypt a payment memo and payer identity under the vault owner's key
+#[post("/payment/metadata/encrypt")]
+async fn encrypt_metadata(
+    app_state: web::Data<AppState>,
+    auth: AuthContext,
+    req: web::Json<EncryptMetadataRequest>,
+) -> impl Responder {
+    if let Err(e) = auth.authorize_user(&req.owner_pubkey) {
+        return e.error_response();
+    }
+
+    match app_state.mpc_client.encrypt_payment_metadata(
+        &req.owner_pubkey,
+        req.memo.as_deref(),
+        req.payer_identity.as_deref(),
+    ) {
+        Ok(metadata) => {
+            let engine = base64::engine::general_purpose::STANDARD;
+            HttpResponse::Ok().json(EncryptMetadataResponse {
+                encrypted_memo: engine.encode(metadata.encrypted_memo),
+                encrypted_payer: engine.encode(metadata.encrypted_payer),
+            })
+        }
+        Err(e) => e.error_response(),
+    }
+}
+
+/// Read a payment record with its memo and payer identity decrypted
+///
+/// Only the vault owner may read them, so the request must carry a wallet
+/// signature by `owner_pubkey` (see `middleware::wallet_proof`).
+#[get("/payment/{owner_pubkey}/{payment_id}/metadata")]
+async fn get_metadata(
+    app_state: web::Data<AppState>,
+    auth: AuthContext,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (owner_pubkey, payment_id) = path.into_inner();
+
+    if let Err(e) = auth.authorize_user(&owner_pubkey) {
+        return e.error_response();
+    }
+
+    if let Err(e) = verify_wallet_proof(
+        &auth,
+        &http_req,
+        &[],
+        &owner_pubkey,
+        app_state.mpc_client.redis(),
+    )
+    .await
+    {
+        return e.error_response();
+    }
+
+    match app_state
+        .mpc_client
+        .payment_metadata(&owner_pubkey, &payment_id)
+        .await
+    {
+        Ok(metadata) => HttpResponse::Ok().json(metadata),
+        Err(e) => {
+            log::warn!("⚠️  Failed to read payment {}: {}", payment_id, e);
+            e.error_response()
+        }
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(encrypt_metadata).service(get_metadata);
+}
@@ -0,0 +1,365 @@
+//! Validation of `/computation/invoke` requests
+//!
+//! A request must name a registered computation, use a valid base58 `user_pubkey`,
+//! declare how its inputs are encoded, and supply exactly one ciphertext of
+//! the right size per parameter of the registered circuit. An optional `entity_type` and
+//! `reference_id` must be usable in the reference index key. Every problem found is reported
+//! against the offending field rather than stopping at the first one.
+
+use super::computation::InvokeComputationRequest;
+use crate::error::{FieldError, ServiceError};
+use crate::mpc::{instruction_spec, ComputationType, InstructionRegistry, RegisteredInstruction};
+use base64::Engine;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Upper bound on a single decoded input, checked before the type check
+pub const MAX_INPUT_BYTES: usize = 1024;
+
+/// Characters kept out of `entity_type` and `reference_id`: the pair is joined
+/// with `:` inside the `{...}` hash tag of the reference index key, so any of
+/// these would let two different pairs share one index
+const RESERVED_REFERENCE_CHARS: [char; 3] = [':', '{', '}'];
+
+/// Check an `entity_type` or `reference_id` for [`RESERVED_REFERENCE_CHARS`]
+pub fn check_reference_part(value: &str) -> Result<(), String> {
+    if value.contains(RESERVED_REFERENCE_CHARS) {
+        return Err("must not contain ':', '{' or '}'".to_string());
+    }
+    Ok(())
+}
+
+/// How `encrypted_inputs` are encoded on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEncoding {
+    Base64,
+    Hex,
+}
+
+impl InputEncoding {
+    fn decode(&self, input: &str) -> Result<Vec<u8>, String> {
+        match self {
+            InputEncoding::Base64 => base64::engine::general_purpose::STANDARD
+                .decode(input)
+                .map_err(|e| format!("not valid base64: {}", e)),
+            InputEncoding::Hex => hex::decode(input).map_err(|e| format!("not valid hex: {}", e)),
+        }
+    }
+
+    /// Longest encoded string that can decode to `bytes` bytes
+    fn max_encoded_len(&self, bytes: usize) -> usize {
+        match self {
+            InputEncoding::Base64 => bytes.div_ceil(3) * 4,
+            InputEncoding::Hex => bytes * 2,
+        }
+    }
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(InputEncoding::Base64),
+            "hex" => Ok(InputEncoding::Hex),
+            other => Err(format!(
+                "unsupported encoding '{}' (expected base64 or hex)",
+                other
+            )),
+        }
+    }
+}
+
+/// The parts of an invoke request that passed validation
+#[derive(Debug)]
+pub struct ValidatedInvoke {
+    pub computation_type: ComputationType,
+    pub instruction: RegisteredInstruction,
+    pub encrypted_inputs: Vec<Vec<u8>>,
+}
+
+/// Validate an invoke request, collecting every field error
+pub fn validate_invoke(
+    req: &InvokeComputationRequest,
+    registry: &InstructionRegistry,
+) -> Result<ValidatedInvoke, ServiceError> {
+    let mut errors = Vec::new();
+
+    let computation_type = ComputationType::from_name(&req.computation_type);
+    let instruction = registry.get(&computation_type);
+    let spec = instruction.and_then(|entry| instruction_spec(&entry.circuit));
+    if spec.is_none() {
+        errors.push(FieldError::new(
+            "computation_type",
+            format!(
+                "'{}' is not a registered computation type",
+                req.computation_type
+            ),
+        ));
+    }
+
+    if Pubkey::from_str(&req.user_pubkey).is_err() {
+        errors.push(FieldError::new(
+            "user_pubkey",
+            "not a valid base58 public key",
+        ));
+    }
+
+    for (field, value) in [
+        ("entity_type", &req.entity_type),
+        ("reference_id", &req.reference_id),
+    ] {
+        if let Some(Err(e)) = value.as_deref().map(check_reference_part) {
+            errors.push(FieldError::new(field, e));
+        }
+    }
+
+    let encoding = match req.encoding.as_deref() {
+        Some(encoding) => match encoding.parse::<InputEncoding>() {
+            Ok(encoding) => Some(encoding),
+            Err(e) => {
+                errors.push(FieldError::new("encoding", e));
+                None
+            }
+        },
+        None => {
+            errors.push(FieldError::new(
+                "encoding",
+                "required; set to \"base64\" or \"hex\"",
+            ));
+            None
+        }
+    };
+
+    if let Some(entry) = instruction {
+        if req.encrypted_inputs.len() != entry.arity {
+            errors.push(FieldError::new(
+                "encrypted_inputs",
+                format!(
+                    "{} expects exactly {} inputs, got {}",
+                    entry.name,
+                    entry.arity,
+                    req.encrypted_inputs.len()
+                ),
+            ));
+        }
+    }
+
+    let mut encrypted_inputs = Vec::with_capacity(req.encrypted_inputs.len());
+    if let Some(encoding) = encoding {
+        for (i, input) in req.encrypted_inputs.iter().enumerate() {
+            let field = format!("encrypted_inputs[{}]", i);
+
+            // Reject oversized inputs before spending time decoding them
+            if input.len() > encoding.max_encoded_len(MAX_INPUT_BYTES) {
+                errors.push(FieldError::new(
+                    field,
+                    format!(
+                        "exceeds the maximum input size of {} bytes",
+                        MAX_INPUT_BYTES
+                    ),
+                ));
+                continue;
+            }
+
+            let bytes = match encoding.decode(input) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    errors.push(FieldError::new(field, e));
+                    continue;
+                }
+            };
+
+            if let Some(param) = spec.and_then(|spec| spec.params.get(i)) {
+                if bytes.len() != param.ty.ciphertext_len() {
+                    errors.push(FieldError::new(
+                        field,
+                        format!(
+                            "`{}` is an encrypted {} and must be {} bytes, got {}",
+                            param.name,
+                            param.ty,
+                            param.ty.ciphertext_len(),
+                            bytes.len()
+                        ),
+                    ));
+                    continue;
+                }
+            }
+
+            encrypted_inputs.push(bytes);
+        }
+    }
+
+    match instruction {
+        Some(instruction) if errors.is_empty() => Ok(ValidatedInvoke {
+            computation_type,
+            instruction: instruction.clone(),
+            encrypted_inputs,
+        }),
+        _ => Err(ServiceError::InvalidFields(errors)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: &str = "11111111111111111111111111111111";
+
+    fn registry() -> InstructionRegistry {
+        InstructionRegistry::from_json(include_str!("../../config/instructions.json")).unwrap()
+    }
+
+    fn request(
+        computation_type: &str,
+        inputs: Vec<String>,
+        encoding: Option<&str>,
+    ) -> InvokeComputationRequest {
+        InvokeComputationRequest {
+            computation_type: computation_type.to_string(),
+            encrypted_inputs: inputs,
+            encoding: encoding.map(str::to_string),
+            user_pubkey: USER.to_string(),
+            callback_url: None,
+            entity_type: None,
+            reference_id: None,
+            metadata: None,
+            user_signature: None,
+        }
+    }
+
+    fn u64_ciphertext_hex() -> String {
+        hex::encode([7u8; 36])
+    }
+
+    fn field_errors(err: ServiceError) -> Vec<String> {
+        match err {
+            ServiceError::InvalidFields(fields) => fields.into_iter().map(|f| f.field).collect(),
+            other => panic!("expected field errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_valid_request() {
+        let req = request(
+            "confidential_transfer",
+            vec![u64_ciphertext_hex(), u64_ciphertext_hex()],
+            Some("hex"),
+        );
+        let validated = validate_invoke(&req, &registry()).unwrap();
+        assert_eq!(
+            validated.computation_type,
+            ComputationType::ConfidentialTransfer
+        );
+        assert_eq!(validated.encrypted_inputs.len(), 2);
+    }
+
+    #[test]
+    fn test_base64_inputs() {
+        let input = base64::engine::general_purpose::STANDARD.encode([7u8; 36]);
+        let req = request("query_balance", vec![input], Some("base64"));
+        assert!(validate_invoke(&req, &registry()).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_computation_type() {
+        let req = request("drain_vault", vec![u64_ciphertext_hex()], Some("hex"));
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry()).unwrap_err()),
+            vec!["computation_type"]
+        );
+    }
+
+    #[test]
+    fn test_unregistered_circuit() {
+        let registry = InstructionRegistry::from_json(
+            r#"{"instructions": [{
+                "name": "balance_query", "circuit": "query_balance", "arity": 1,
+                "cluster_instruction": "query_balance", "accounts": []
+            }]}"#,
+        )
+        .unwrap();
+
+        let req = request(
+            "add_values",
+            vec![u64_ciphertext_hex(), u64_ciphertext_hex()],
+            Some("hex"),
+        );
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry).unwrap_err()),
+            vec!["computation_type"]
+        );
+    }
+
+    #[test]
+    fn test_wrong_arity() {
+        let req = request(
+            "encrypted_transfer",
+            vec![u64_ciphertext_hex()],
+            Some("hex"),
+        );
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry()).unwrap_err()),
+            vec!["encrypted_inputs"]
+        );
+    }
+
+    #[test]
+    fn test_wrong_ciphertext_size() {
+        let req = request(
+            "add_values",
+            vec![u64_ciphertext_hex(), hex::encode([7u8; 40])],
+            Some("hex"),
+        );
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry()).unwrap_err()),
+            vec!["encrypted_inputs[1]"]
+        );
+    }
+
+    #[test]
+    fn test_oversized_input() {
+        let req = request(
+            "query_balance",
+            vec![hex::encode(vec![0u8; MAX_INPUT_BYTES + 1])],
+            Some("hex"),
+        );
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry()).unwrap_err()),
+            vec!["encrypted_inputs[0]"]
+        );
+    }
+
+    #[test]
+    fn test_reference_parts_cannot_collide() {
+        let mut req = request("query_balance", vec![u64_ciphertext_hex()], Some("hex"));
+        req.entity_type = Some("payment_intent".to_string());
+        req.reference_id = Some("pi_123".to_string());
+        assert!(validate_invoke(&req, &registry()).is_ok());
+
+        // ("a:b", "c") and ("a", "b:c") would share one index key
+        req.entity_type = Some("a:b".to_string());
+        req.reference_id = Some("{c}".to_string());
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry()).unwrap_err()),
+            vec!["entity_type", "reference_id"]
+        );
+    }
+
+    #[test]
+    fn test_collects_every_error() {
+        let mut req = request("query_balance", vec!["zz".to_string()], None);
+        req.user_pubkey = "not-a-pubkey".to_string();
+
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry()).unwrap_err()),
+            vec!["user_pubkey", "encoding"]
+        );
+
+        req.encoding = Some("hex".to_string());
+        assert_eq!(
+            field_errors(validate_invoke(&req, &registry()).unwrap_err()),
+            vec!["user_pubkey", "encrypted_inputs[0]"]
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Service-wide error type
+//!
+//! The MPC client, Redis client, encryption helper and secret loaders all
+//! return [`ServiceError`]. Handlers pass it straight to actix, which renders
+//!
+//! ```json
+//! {"error": "<code>", "message": "<human readable detail>"}
+//! ```
+//!
+//! with the status of the variant. The `error` codes are part of the API
+//! contract: clients branch on them, so they must never be renamed.
+
+use crate::middleware::auth::AuthError;
+use crate::mpc::types::ComputationStatusError;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+
+pub type ServiceResult<T> = Result<T, ServiceError>;
+
+/// One rejected request field, e.g. `encrypted_inputs[1]`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ServiceError {
+    /// The request is malformed or asks for something unsupported (400)
+    Validation(String),
+    /// Specific request fields are invalid; rendered with a `fields` list (400)
+    InvalidFields(Vec<FieldError>),
+    /// Missing credentials or insufficient scope (401/403)
+    Auth(AuthError),
+    /// The referenced computation or resource does not exist (404)
+    NotFound(String),
+    /// Solana RPC or another upstream dependency failed (502)
+    Upstream(String),
+    /// A ciphertext could not be decrypted or a value encrypted (422)
+    Crypto(String),
+    /// Redis is unreachable or holds unreadable data (503)
+    Storage(String),
+    /// The request conflicts with current state, e.g. an illegal status
+    /// transition (409)
+    Conflict(String),
+    /// The service is misconfigured, e.g. a missing secret (500)
+    Configuration(String),
+}
+
+impl ServiceError {
+    /// Stable machine-readable code returned as `error`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServiceError::Validation(_) | ServiceError::InvalidFields(_) => "validation_error",
+            ServiceError::Auth(AuthError::Unauthorized(_)) => "unauthorized",
+            ServiceError::Auth(AuthError::Forbidden(_)) => "forbidden",
+            ServiceError::Auth(AuthError::Unavailable(_)) => "auth_unavailable",
+            ServiceError::NotFound(_) => "not_found",
+            ServiceError::Upstream(_) => "upstream_error",
+            ServiceError::Crypto(_) => "crypto_error",
+            ServiceError::Storage(_) => "storage_error",
+            ServiceError::Conflict(_) => "conflict",
+            ServiceError::Configuration(_) => "configuration_error",
+        }
+    }
+}
+
+impl std::fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceError::Auth(e) => write!(f, "{}", e),
+            ServiceError::InvalidFields(fields) => {
+                write!(f, "Invalid request")?;
+                for (i, field) in fields.iter().enumerate() {
+                    let sep = if i == 0 { ": " } else { "; " };
+                    write!(f, "{}{}: {}", sep, field.field, field.message)?;
+                }
+                Ok(())
+            }
+            ServiceError::Validation(msg)
+            | ServiceError::NotFound(msg)
+            | ServiceError::Upstream(msg)
+            | ServiceError::Crypto(msg)
+            | ServiceError::Storage(msg)
+            | ServiceError::Conflict(msg)
+            | ServiceError::Configuration(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ServiceError::Validation(_) | ServiceError::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            ServiceError::Auth(e) => e.status_code(),
+            ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ServiceError::Crypto(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ServiceError::Storage(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ServiceError::Conflict(_) => StatusCode::CONFLICT,
+            ServiceError::Configuration(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let ServiceError::Auth(e) = self {
+            // Keeps the WWW-Authenticate header on 401s
+            return e.error_response();
+        }
+
+        let mut body = serde_json::json!({
+            "error": self.code(),
+            "message": self.to_string()
+        });
+        if let ServiceError::InvalidFields(fields) = self {
+            body["fields"] = serde_json::json!(fields);
+        }
+
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
+
+impl From<AuthError> for ServiceError {
+    fn from(e: AuthError) -> Self {
+        ServiceError::Auth(e)
+    }
+}
+
+impl From<ComputationStatusError> for ServiceError {
+    fn from(e: ComputationStatusError) -> Self {
+        ServiceError::Conflict(e.to_string())
+    }
+}
+
+impl From<redis::RedisError> for ServiceError {
+    fn from(e: redis::RedisError) -> Self {
+        ServiceError::Storage(format!("Redis error: {}", e))
+    }
+}
+
+impl From<solana_client::client_error::ClientError> for ServiceError {
+    fn from(e: solana_client::client_error::ClientError) -> Self {
+        ServiceError::Upstream(format!("Solana RPC error: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpc::types::ComputationStatus;
+    use actix_web::body::to_bytes;
+
+    async fn body_json(err: ServiceError) -> (StatusCode, serde_json::Value) {
+        let res = err.error_response();
+        let status = res.status();
+        let body = to_bytes(res.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn test_crypto_and_storage_are_distinguishable() {
+        let (status, body) = body_json(ServiceError::Crypto("bad tag".to_string())).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"], "crypto_error");
+        assert_eq!(body["message"], "bad tag");
+
+        let (status, body) = body_json(ServiceError::Storage("down".to_string())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error"], "storage_error");
+    }
+
+    #[actix_web::test]
+    async fn test_auth_errors_keep_their_codes() {
+        let err = ServiceError::from(AuthError::Forbidden("nope".to_string()));
+        assert_eq!(err.code(), "forbidden");
+
+        let (status, body) = body_json(err).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "forbidden");
+    }
+
+    #[actix_web::test]
+    async fn test_field_errors_are_listed() {
+        let err = ServiceError::InvalidFields(vec![
+            FieldError::new("user_pubkey", "not a valid base58 public key"),
+            FieldError::new("encrypted_inputs[1]", "not valid hex"),
+        ]);
+        assert_eq!(
+            err.to_string(),
+            "Invalid request: user_pubkey: not a valid base58 public key; \
+             encrypted_inputs[1]: not valid hex"
+        );
+
+        let (status, body) = body_json(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "validation_error");
+        assert_eq!(body["fields"][1]["field"], "encrypted_inputs[1]");
+    }
+
+    #[test]
+    fn test_illegal_transition_is_conflict() {
+        let err = ServiceError::from(ComputationStatusError::IllegalTransition {
+            computation_id: "comp_1".to_string(),
+            from: ComputationStatus::Cancelled,
+            to: ComputationStatus::Completed,
+        });
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert_eq!(err.code(), "conflict");
+    }
+}
@@ -1,19 +1,26 @@
 mod api;
+mod error;
+mod middleware;
 mod mpc;
 mod utils;
 
 use actix_cors::Cors;
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use actix_web::http::header;
+use actix_web::middleware::{from_fn, Logger};
+use actix_web::{web, App, HttpServer};
 use env_logger::Env;
 use std::io;
 use std::sync::Arc;
 
-use mpc::{MpcClient, MpcMode};
-use utils::RedisClient;
+use middleware::{AuthConfig, RateLimitConfig};
+use mpc::{InstructionRegistry, MpcClient};
+use utils::{RedisClient, RedisTopology};
 
 /// Application state shared across all requests
 pub struct AppState {
     pub mpc_client: Arc<MpcClient>,
+    pub auth: Arc<AuthConfig>,
+    pub rate_limits: Arc<RateLimitConfig>,
 }
 
 #[actix_web::main]
@@ -32,19 +39,26 @@ async fn main() -> io::Result<()> {
         .parse::<u16>()
         .expect("Invalid port");
 
-    let redis_url =
-        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+    let redis_topology = RedisTopology::from_env()
+        .map_err(|e| io::Error::other(format!("Invalid Redis configuration: {}", e)))?;
 
     let mpc_mode = std::env::var("MPC_MODE").unwrap_or_else(|_| "local".to_string());
 
     // Initialize Redis client
-    log::info!("📦 Connecting to Redis: {}", redis_url);
-    let redis_client = Arc::new(RedisClient::new(&redis_url).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("Redis connection failed: {}", e),
-        )
-    })?);
+    log::info!("📦 Connecting to Redis: {:?}", redis_topology);
+    let redis_client = Arc::new(RedisClient::from_topology(redis_topology));
+
+    let registry = InstructionRegistry::from_env()
+        .map_err(|e| io::Error::other(format!("Invalid instruction registry: {}", e)))?;
+    let registry = Arc::new(registry);
+    log::info!(
+        "📜 Registered instructions: {:?}",
+        registry
+            .entries()
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>()
+    );
 
     // Initialize MPC client based on mode
     let mpc_client = if mpc_mode.to_lowercase() == "cluster" {
@@ -61,16 +75,12 @@ async fn main() -> io::Result<()> {
             )
         })?;
 
-        MpcClient::new_cluster(redis_client, cluster_address, program_id).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to initialize MPC client: {}", e),
-            )
-        })?
+        MpcClient::new_cluster(redis_client, registry, cluster_address, program_id)
+            .map_err(|e| io::Error::other(format!("Failed to initialize MPC client: {}", e)))?
     } else {
         let build_path = std::env::var("ARCIUM_BUILD_PATH").unwrap_or_else(|_| "build".to_string());
 
-        MpcClient::new_local(redis_client, build_path).map_err(|e| {
+        MpcClient::new_local(redis_client, registry, build_path).map_err(|e| {
             io::Error::new(
                 io::ErrorKind::Other,
                 format!("Failed to initialize MPC client: {}", e),
@@ -78,21 +88,52 @@ async fn main() -> io::Result<()> {
         })?
     };
 
+    mpc_client
+        .check_circuit_integrity()
+        .map_err(|e| io::Error::other(format!("Circuit integrity check failed: {}", e)))?;
+
     let mpc_client = Arc::new(mpc_client);
 
     log::info!("✅ MPC Client initialized in {:?} mode", mpc_client.mode());
     log::info!("🔧 Arcium Service listening on http://0.0.0.0:{}", port);
 
+    let auth_config = Arc::new(AuthConfig::from_env());
+    log::info!("🔐 CORS allowed origins: {:?}", auth_config.allowed_origins);
+
+    let rate_limits = RateLimitConfig::from_env()
+        .map_err(|e| io::Error::other(format!("Invalid rate limit configuration: {}", e)))?;
+    log::info!("🚦 Rate limits: {:?}", rate_limits);
+
     // Create application state
     let app_state = web::Data::new(AppState {
         mpc_client: mpc_client.clone(),
+        auth: auth_config.clone(),
+        rate_limits: Arc::new(rate_limits),
     });
 
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header();
+        let cors = auth_config
+            .allowed_origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+            .allowed_methods(vec!["GET", "POST"])
+            .allowed_headers(vec![
+                header::AUTHORIZATION,
+                header::CONTENT_TYPE,
+                header::HeaderName::from_static("x-api-key"),
+                header::HeaderName::from_static("x-wallet-signature"),
+                header::HeaderName::from_static("x-wallet-timestamp"),
+                header::HeaderName::from_static("x-wallet-nonce"),
+            ])
+            .expose_headers(vec![
+                header::RETRY_AFTER,
+                header::HeaderName::from_static("x-ratelimit-limit"),
+                header::HeaderName::from_static("x-ratelimit-remaining"),
+                header::HeaderName::from_static("x-quota-limit"),
+                header::HeaderName::from_static("x-quota-remaining"),
+                header::HeaderName::from_static("x-quota-reset"),
+            ])
+            .max_age(3600);
 
         App::new()
             .app_data(app_state.clone())
@@ -100,9 +141,17 @@ async fn main() -> io::Result<()> {
             .wrap(cors)
             .service(
                 web::scope("/api")
+                    // Registered in reverse: the IP bucket runs first, then
+                    // authentication, then the per-credential bucket that
+                    // needs the caller
+                    .wrap(from_fn(middleware::rate_limit::rate_limit))
+                    .wrap(from_fn(middleware::auth::authenticate))
+                    .wrap(from_fn(middleware::rate_limit::limit_ip))
                     .configure(api::health::configure)
                     .configure(api::computation::configure)
-                    .configure(api::account::configure),
+                    .configure(api::account::configure)
+                    .configure(api::payment::configure)
+                    .configure(api::admin::configure),
             )
     })
     .bind(("0.0.0.0", port))?
@@ -0,0 +1,492 @@
+//! Caller authentication for the `/api` routes
+//!
+//! Every request (except `/api/health*`) must present one of:
+//!
+//! - an API key, as `X-API-Key: <key>` or `Authorization: Bearer <key>`. Keys
+//!   are stored in Redis under `apikey:{sha256(key) as hex}` as a JSON
+//!   [`ApiKeyRecord`], so the raw key never rests on the server.
+//! - a service JWT, as `Authorization: Bearer <jwt>`, HS256-signed with
+//!   `SERVICE_JWT_SECRET` and carrying `aud: "arcium-service"`. A JWT acts
+//!   either for the merchant in `merchant_id` or, with `role: "internal"`
+//!   and no merchant, for an internal service with unrestricted access.
+//!   Tokens carrying neither, or both, are rejected.
+//!
+//! Missing or invalid credentials yield `401`; authenticated callers acting
+//! outside their merchant or `user_pubkey` scope yield `403`.
+
+use crate::mpc::types::ComputationMetadata;
+use crate::AppState;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::future::{ready, Ready};
+
+const API_KEY_HEADER: &str = "X-API-Key";
+const JWT_AUDIENCE: &str = "arcium-service";
+const INTERNAL_ROLE: &str = "internal";
+
+/// Authentication settings loaded at startup
+pub struct AuthConfig {
+    jwt_key: Option<DecodingKey>,
+    pub allowed_origins: Vec<String>,
+}
+
+impl AuthConfig {
+    /// Load from `SERVICE_JWT_SECRET` and `CORS_ALLOWED_ORIGINS`
+    ///
+    /// Without a JWT secret only API keys are accepted. Without allowed
+    /// origins no cross-origin browser requests are permitted.
+    pub fn from_env() -> Self {
+        let jwt_key = std::env::var("SERVICE_JWT_SECRET")
+            .ok()
+            .filter(|secret| !secret.trim().is_empty())
+            .map(|secret| DecodingKey::from_secret(secret.as_bytes()));
+
+        if jwt_key.is_none() {
+            log::warn!("⚠️  SERVICE_JWT_SECRET not set; service JWTs will be rejected");
+        }
+
+        let allowed_origins = std::env::var("CORS_ALLOWED_ORIGINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|origin| origin.trim().to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+
+        Self {
+            jwt_key,
+            allowed_origins,
+        }
+    }
+}
+
+/// API key as stored in Redis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    pub key_id: String,
+    pub merchant_id: String,
+    /// Wallets this key may act for; empty means any wallet of the merchant
+    #[serde(default)]
+    pub user_pubkeys: Vec<String>,
+    #[serde(default)]
+    pub revoked: bool,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+/// Hash an API key for storage and lookup
+pub fn hash_api_key(raw_key: &str) -> String {
+    hex::encode(Sha256::digest(raw_key.as_bytes()))
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceClaims {
+    sub: String,
+    #[serde(default)]
+    merchant_id: Option<String>,
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    user_pubkeys: Option<Vec<String>>,
+}
+
+/// The authenticated caller, available to handlers as an extractor
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// API key id or JWT subject
+    pub principal: String,
+    /// Merchant the caller acts for; `None` only for callers holding the
+    /// internal role
+    pub merchant_id: Option<String>,
+    /// Wallets the caller may act for; `None` means unrestricted
+    pub user_pubkeys: Option<HashSet<String>>,
+}
+
+impl AuthContext {
+    /// Whether the caller is an internal service rather than a merchant
+    pub fn is_internal(&self) -> bool {
+        self.merchant_id.is_none()
+    }
+
+    /// Reject callers that are not internal services
+    pub fn require_internal(&self) -> Result<(), AuthError> {
+        if self.is_internal() {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden(
+                "This endpoint is restricted to internal services".to_string(),
+            ))
+        }
+    }
+
+    /// Check that the caller may act for `user_pubkey`
+    pub fn authorize_user(&self, user_pubkey: &str) -> Result<(), AuthError> {
+        match &self.user_pubkeys {
+            Some(allowed) if !allowed.contains(user_pubkey) => Err(AuthError::Forbidden(format!(
+                "Credential is not scoped to user {}",
+                user_pubkey
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the caller may read a stored computation
+    pub fn authorize_computation(&self, metadata: &ComputationMetadata) -> Result<(), AuthError> {
+        if self.is_internal() {
+            return Ok(());
+        }
+        if metadata.merchant_id != self.merchant_id {
+            return Err(AuthError::Forbidden(format!(
+                "Computation {} belongs to another merchant",
+                metadata.computation_id
+            )));
+        }
+        self.authorize_user(&metadata.user_pubkey)
+    }
+}
+
+impl FromRequest for AuthContext {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AuthContext>()
+                .cloned()
+                .ok_or_else(|| AuthError::Unauthorized("Missing credentials".to_string())),
+        )
+    }
+}
+
+/// Authentication failures, rendered as JSON 401/403 responses
+#[derive(Debug)]
+pub enum AuthError {
+    Unauthorized(String),
+    Forbidden(String),
+    Unavailable(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Unauthorized(msg) | AuthError::Forbidden(msg) => write!(f, "{}", msg),
+            AuthError::Unavailable(msg) => write!(f, "Authentication unavailable: {}", msg),
+        }
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AuthError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AuthError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let error = match self {
+            AuthError::Unauthorized(_) => "unauthorized",
+            AuthError::Forbidden(_) => "forbidden",
+            AuthError::Unavailable(_) => "auth_unavailable",
+        };
+
+        let mut response = HttpResponse::build(self.status_code());
+        if matches!(self, AuthError::Unauthorized(_)) {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response.json(serde_json::json!({
+            "error": error,
+            "message": self.to_string()
+        }))
+    }
+}
+
+fn extract_credential(req: &ServiceRequest) -> Option<String> {
+    if let Some(key) = req.headers().get(API_KEY_HEADER) {
+        return key.to_str().ok().map(|k| k.trim().to_string());
+    }
+
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+fn looks_like_jwt(credential: &str) -> bool {
+    credential.split('.').count() == 3
+}
+
+fn verify_service_jwt(config: &AuthConfig, token: &str) -> Result<AuthContext, AuthError> {
+    let key = config
+        .jwt_key
+        .as_ref()
+        .ok_or_else(|| AuthError::Unauthorized("Service JWTs are not enabled".to_string()))?;
+
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_audience(&[JWT_AUDIENCE]);
+    validation.set_required_spec_claims(&["exp", "aud", "sub"]);
+
+    let claims = jsonwebtoken::decode::<ServiceClaims>(token, key, &validation)
+        .map_err(|e| AuthError::Unauthorized(format!("Invalid service token: {}", e)))?
+        .claims;
+
+    let merchant_id = match (claims.merchant_id, claims.role.as_deref()) {
+        (Some(merchant_id), None) => Some(merchant_id),
+        (None, Some(INTERNAL_ROLE)) => None,
+        (Some(_), Some(_)) => {
+            return Err(AuthError::Unauthorized(
+                "Service token cannot carry both a merchant and a role".to_string(),
+            ))
+        }
+        (None, Some(role)) => {
+            return Err(AuthError::Unauthorized(format!(
+                "Unknown service token role '{}'",
+                role
+            )))
+        }
+        (None, None) => {
+            return Err(AuthError::Unauthorized(
+                "Service token must carry a merchant_id or the internal role".to_string(),
+            ))
+        }
+    };
+
+    Ok(AuthContext {
+        principal: claims.sub,
+        merchant_id,
+        user_pubkeys: claims.user_pubkeys.map(|keys| keys.into_iter().collect()),
+    })
+}
+
+async fn verify_api_key(app_state: &AppState, raw_key: &str) -> Result<AuthContext, AuthError> {
+    let record = app_state
+        .mpc_client
+        .redis()
+        .get_api_key(&hash_api_key(raw_key))
+        .await
+        .map_err(|e| {
+            log::error!("❌ API key lookup failed: {}", e);
+            AuthError::Unavailable("credential store unreachable".to_string())
+        })?
+        .ok_or_else(|| AuthError::Unauthorized("Invalid API key".to_string()))?;
+
+    if record.revoked {
+        return Err(AuthError::Unauthorized(
+            "API key has been revoked".to_string(),
+        ));
+    }
+
+    if let Some(expires_at) = record.expires_at {
+        let now = chrono::Utc::now().timestamp() as u64;
+        if now >= expires_at {
+            return Err(AuthError::Unauthorized("API key has expired".to_string()));
+        }
+    }
+
+    Ok(AuthContext {
+        principal: record.key_id,
+        merchant_id: Some(record.merchant_id),
+        user_pubkeys: if record.user_pubkeys.is_empty() {
+            None
+        } else {
+            Some(record.user_pubkeys.into_iter().collect())
+        },
+    })
+}
+
+/// Middleware authenticating every request outside `/api/health`
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.path().starts_with("/api/health") {
+        return next.call(req).await;
+    }
+
+    let app_state = req
+        .app_data::<web::Data<AppState>>()
+        .cloned()
+        .ok_or_else(|| AuthError::Unavailable("application state missing".to_string()))?;
+
+    let credential = extract_credential(&req)
+        .filter(|c| !c.is_empty())
+        .ok_or_else(|| AuthError::Unauthorized("Missing credentials".to_string()))?;
+
+    let context = if looks_like_jwt(&credential) {
+        verify_service_jwt(&app_state.auth, &credential)?
+    } else {
+        verify_api_key(&app_state, &credential).await?
+    };
+
+    log::debug!(
+        "🔑 Authenticated {} (merchant: {:?})",
+        context.principal,
+        context.merchant_id
+    );
+
+    req.extensions_mut().insert(context);
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpc::types::{ComputationStatus, ComputationType};
+    use jsonwebtoken::{EncodingKey, Header};
+
+    fn merchant_context(user_pubkeys: Option<&[&str]>) -> AuthContext {
+        AuthContext {
+            principal: "key_1".to_string(),
+            merchant_id: Some("merchant_a".to_string()),
+            user_pubkeys: user_pubkeys.map(|keys| keys.iter().map(|k| k.to_string()).collect()),
+        }
+    }
+
+    fn metadata(merchant_id: Option<&str>, user_pubkey: &str) -> ComputationMetadata {
+        ComputationMetadata {
+            computation_id: "comp_1".to_string(),
+            user_pubkey: user_pubkey.to_string(),
+            computation_type: ComputationType::BalanceQuery,
+            status: ComputationStatus::Completed,
+            created_at: 0,
+            completed_at: None,
+            callback_url: None,
+            entity_type: None,
+            reference_id: None,
+            metadata: serde_json::Value::Null,
+            cluster_tx_signature: None,
+            attestation: None,
+            status_history: Vec::new(),
+            merchant_id: merchant_id.map(str::to_string),
+        }
+    }
+
+    fn config(secret: &str) -> AuthConfig {
+        AuthConfig {
+            jwt_key: Some(DecodingKey::from_secret(secret.as_bytes())),
+            allowed_origins: vec![],
+        }
+    }
+
+    fn sign(secret: &str, claims: serde_json::Value) -> String {
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_user_scope() {
+        let ctx = merchant_context(Some(&["wallet_1"]));
+        assert!(ctx.authorize_user("wallet_1").is_ok());
+        assert!(matches!(
+            ctx.authorize_user("wallet_2"),
+            Err(AuthError::Forbidden(_))
+        ));
+
+        let unrestricted = merchant_context(None);
+        assert!(unrestricted.authorize_user("wallet_2").is_ok());
+    }
+
+    #[test]
+    fn test_merchant_scope() {
+        let ctx = merchant_context(None);
+        assert!(ctx
+            .authorize_computation(&metadata(Some("merchant_a"), "wallet_1"))
+            .is_ok());
+        assert!(ctx
+            .authorize_computation(&metadata(Some("merchant_b"), "wallet_1"))
+            .is_err());
+        assert!(ctx.require_internal().is_err());
+    }
+
+    #[test]
+    fn test_service_jwt() {
+        let exp = chrono::Utc::now().timestamp() + 60;
+        let token = sign(
+            "secret",
+            serde_json::json!({
+                "sub": "api-gateway",
+                "aud": JWT_AUDIENCE,
+                "exp": exp,
+                "merchant_id": "merchant_a",
+                "user_pubkeys": ["wallet_1"]
+            }),
+        );
+        assert!(looks_like_jwt(&token));
+
+        let ctx = verify_service_jwt(&config("secret"), &token).unwrap();
+        assert_eq!(ctx.principal, "api-gateway");
+        assert_eq!(ctx.merchant_id.as_deref(), Some("merchant_a"));
+        assert!(ctx.authorize_user("wallet_1").is_ok());
+        assert!(ctx.authorize_user("wallet_2").is_err());
+
+        // Wrong secret
+        assert!(verify_service_jwt(&config("other"), &token).is_err());
+
+        // Wrong audience
+        let token = sign(
+            "secret",
+            serde_json::json!({ "sub": "x", "aud": "elsewhere", "exp": exp }),
+        );
+        assert!(verify_service_jwt(&config("secret"), &token).is_err());
+
+        // Internal service token
+        let token = sign(
+            "secret",
+            serde_json::json!({ "sub": "relay", "aud": JWT_AUDIENCE, "exp": exp, "role": "internal" }),
+        );
+        let ctx = verify_service_jwt(&config("secret"), &token).unwrap();
+        assert!(ctx.is_internal());
+        assert!(ctx.require_internal().is_ok());
+    }
+
+    #[test]
+    fn test_service_jwt_needs_merchant_or_internal_role() {
+        let exp = chrono::Utc::now().timestamp() + 60;
+        for claims in [
+            serde_json::json!({ "sub": "relay", "aud": JWT_AUDIENCE, "exp": exp }),
+            serde_json::json!({ "sub": "relay", "aud": JWT_AUDIENCE, "exp": exp, "role": "admin" }),
+            serde_json::json!({
+                "sub": "relay",
+                "aud": JWT_AUDIENCE,
+                "exp": exp,
+                "role": "internal",
+                "merchant_id": "merchant_a"
+            }),
+        ] {
+            let token = sign("secret", claims.clone());
+            assert!(
+                matches!(
+                    verify_service_jwt(&config("secret"), &token),
+                    Err(AuthError::Unauthorized(_))
+                ),
+                "{}",
+                claims
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_status_codes() {
+        assert_eq!(
+            AuthError::Unauthorized(String::new()).status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            AuthError::Forbidden(String::new()).status_code(),
+            StatusCode::FORBIDDEN
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod auth;
+pub mod rate_limit;
+pub mod wallet_proof;
+
+pub use auth::{AuthConfig, AuthContext};
+pub use rate_limit::RateLimitConfig;
@@ -0,0 +1,559 @@
+//! Request throttling and daily computation quotas
+//!
+//! Every `/api` request outside `/api/health` takes a token from a
+//! Redis-backed token bucket per client IP before it is authenticated, so
+//! credential guessing is throttled too. Authenticated requests then take a
+//! token from the bucket of their credential (API key id or JWT subject).
+//! Handlers acting for a wallet additionally take a token from the bucket of
+//! that `user_pubkey`, and `/computation/invoke` counts against the
+//! merchant's daily computation quota. Internal service principals are exempt
+//! from all but the IP bucket.
+//!
+//! The client IP is the connection's peer address. Behind a reverse proxy set
+//! `RATE_LIMIT_TRUST_PROXY=true` to use the address from `Forwarded` /
+//! `X-Forwarded-For` instead; only do so when the proxy overwrites those
+//! headers, as clients can otherwise pick their own bucket.
+//!
+//! Throttled requests get `429` with `Retry-After` plus either
+//! `X-RateLimit-*` (bucket exhausted) or `X-Quota-*` (quota exhausted) headers.
+//!
+//! Limits come from the environment (`0` disables a quota):
+//!
+//! - `RATE_LIMIT_KEY_BURST` / `RATE_LIMIT_KEY_PER_MINUTE`
+//! - `RATE_LIMIT_USER_BURST` / `RATE_LIMIT_USER_PER_MINUTE`
+//! - `RATE_LIMIT_IP_BURST` / `RATE_LIMIT_IP_PER_MINUTE`
+//! - `RATE_LIMIT_TRUST_PROXY`: take the client IP from forwarding headers
+//! - `DAILY_COMPUTATION_QUOTA`: default quota per merchant
+//! - `MERCHANT_DAILY_QUOTAS`: overrides as `merchant_a=500,merchant_b=0`
+
+use super::auth::AuthContext;
+use crate::utils::RedisClient;
+use crate::AppState;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpResponse, HttpResponseBuilder, ResponseError};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::{IpAddr, SocketAddr};
+
+const RATE_LIMIT_LIMIT: &str = "X-RateLimit-Limit";
+const RATE_LIMIT_REMAINING: &str = "X-RateLimit-Remaining";
+const QUOTA_LIMIT: &str = "X-Quota-Limit";
+const QUOTA_REMAINING: &str = "X-Quota-Remaining";
+const QUOTA_RESET: &str = "X-Quota-Reset";
+
+/// Size and refill rate of one kind of token bucket
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketLimit {
+    /// Tokens available to a burst
+    pub capacity: u64,
+    /// Tokens restored per second
+    pub refill_per_sec: f64,
+}
+
+impl BucketLimit {
+    fn from_env(prefix: &str, burst: u64, per_minute: u64) -> Result<Self, Box<dyn Error>> {
+        let capacity = env_u64(&format!("{prefix}_BURST"), burst)?;
+        let per_minute = env_u64(&format!("{prefix}_PER_MINUTE"), per_minute)?;
+        if capacity == 0 || per_minute == 0 {
+            return Err(format!("{prefix}_BURST and {prefix}_PER_MINUTE must be positive").into());
+        }
+
+        Ok(Self {
+            capacity,
+            refill_per_sec: per_minute as f64 / 60.0,
+        })
+    }
+}
+
+/// Outcome of taking a token from a bucket
+#[derive(Debug, Clone)]
+pub struct BucketState {
+    pub allowed: bool,
+    pub limit: u64,
+    pub remaining: u64,
+    pub retry_after_ms: u64,
+}
+
+/// A merchant's daily quota after counting one computation
+#[derive(Debug, Clone)]
+pub struct QuotaState {
+    pub allowed: bool,
+    pub limit: u64,
+    pub used: u64,
+    /// UTC day (`YYYY-MM-DD`) the computation was counted against
+    pub day: String,
+    /// Unix seconds at which the quota resets (next UTC midnight)
+    pub reset_at: i64,
+}
+
+impl QuotaState {
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+
+    /// Attach `X-Quota-*` headers to a response
+    pub fn apply_headers(&self, response: &mut HttpResponseBuilder) {
+        response
+            .insert_header((QUOTA_LIMIT, self.limit))
+            .insert_header((QUOTA_REMAINING, self.remaining()))
+            .insert_header((QUOTA_RESET, self.reset_at));
+    }
+}
+
+/// Rate limits loaded at startup
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub per_key: BucketLimit,
+    pub per_user: BucketLimit,
+    pub per_ip: BucketLimit,
+    /// Take the client IP from `Forwarded` / `X-Forwarded-For`
+    pub trust_proxy: bool,
+    pub daily_quota: u64,
+    pub merchant_quotas: HashMap<String, u64>,
+}
+
+impl RateLimitConfig {
+    /// Load limits from the environment, falling back to defaults
+    pub fn from_env() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            per_key: BucketLimit::from_env("RATE_LIMIT_KEY", 120, 600)?,
+            per_user: BucketLimit::from_env("RATE_LIMIT_USER", 30, 60)?,
+            per_ip: BucketLimit::from_env("RATE_LIMIT_IP", 60, 300)?,
+            trust_proxy: env_bool("RATE_LIMIT_TRUST_PROXY")?,
+            daily_quota: env_u64("DAILY_COMPUTATION_QUOTA", 10_000)?,
+            merchant_quotas: parse_merchant_quotas(
+                &std::env::var("MERCHANT_DAILY_QUOTAS").unwrap_or_default(),
+            )?,
+        })
+    }
+
+    /// Daily computation quota for a merchant; `0` means unlimited
+    pub fn quota_for(&self, merchant_id: &str) -> u64 {
+        self.merchant_quotas
+            .get(merchant_id)
+            .copied()
+            .unwrap_or(self.daily_quota)
+    }
+}
+
+fn env_u64(name: &str, default: u64) -> Result<u64, Box<dyn Error>> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid {name}: {e}").into()),
+        Err(_) => Ok(default),
+    }
+}
+
+fn env_bool(name: &str) -> Result<bool, Box<dyn Error>> {
+    match std::env::var(name) {
+        Ok(value) => match value.trim() {
+            "true" | "1" => Ok(true),
+            "false" | "0" | "" => Ok(false),
+            other => Err(format!("Invalid {name}: '{other}' (expected true or false)").into()),
+        },
+        Err(_) => Ok(false),
+    }
+}
+
+fn parse_merchant_quotas(raw: &str) -> Result<HashMap<String, u64>, Box<dyn Error>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (merchant, quota) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid MERCHANT_DAILY_QUOTAS entry '{entry}'"))?;
+            let quota = quota
+                .trim()
+                .parse()
+                .map_err(|e| format!("Invalid quota for merchant '{merchant}': {e}"))?;
+            Ok((merchant.trim().to_string(), quota))
+        })
+        .collect()
+}
+
+/// UTC day key and the unix time at which that day ends
+fn quota_window(now: DateTime<Utc>) -> (String, i64) {
+    let today = now.date_naive();
+    let reset_at = today
+        .succ_opt()
+        .and_then(|tomorrow| tomorrow.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc().timestamp())
+        .unwrap_or_else(|| now.timestamp());
+    (today.format("%Y-%m-%d").to_string(), reset_at)
+}
+
+/// Throttling failures, rendered as JSON 429/503 responses
+#[derive(Debug)]
+pub enum RateLimitError {
+    Limited {
+        scope: &'static str,
+        state: BucketState,
+    },
+    QuotaExceeded(QuotaState),
+    Unavailable(String),
+}
+
+impl RateLimitError {
+    fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            RateLimitError::Limited { state, .. } => {
+                Some(state.retry_after_ms.div_ceil(1000).max(1))
+            }
+            RateLimitError::QuotaExceeded(quota) => {
+                Some((quota.reset_at - Utc::now().timestamp()).max(1) as u64)
+            }
+            RateLimitError::Unavailable(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateLimitError::Limited { scope, .. } => {
+                write!(f, "Too many requests for this {}", scope)
+            }
+            RateLimitError::QuotaExceeded(quota) => {
+                write!(f, "Daily computation quota of {} reached", quota.limit)
+            }
+            RateLimitError::Unavailable(msg) => write!(f, "Rate limiting unavailable: {}", msg),
+        }
+    }
+}
+
+impl ResponseError for RateLimitError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RateLimitError::Limited { .. } | RateLimitError::QuotaExceeded(_) => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            RateLimitError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(retry_after) = self.retry_after_secs() {
+            response.insert_header((header::RETRY_AFTER, retry_after));
+        }
+
+        let error = match self {
+            RateLimitError::Limited { state, .. } => {
+                response
+                    .insert_header((RATE_LIMIT_LIMIT, state.limit))
+                    .insert_header((RATE_LIMIT_REMAINING, 0));
+                "rate_limited"
+            }
+            RateLimitError::QuotaExceeded(quota) => {
+                quota.apply_headers(&mut response);
+                "quota_exceeded"
+            }
+            RateLimitError::Unavailable(_) => "rate_limit_unavailable",
+        };
+
+        response.json(serde_json::json!({
+            "error": error,
+            "message": self.to_string()
+        }))
+    }
+}
+
+async fn take_token(
+    redis: &RedisClient,
+    scope: &'static str,
+    bucket: &str,
+    limit: &BucketLimit,
+) -> Result<BucketState, RateLimitError> {
+    let state = redis.take_token(bucket, limit).await.map_err(|e| {
+        log::error!("❌ Rate limit check failed: {}", e);
+        RateLimitError::Unavailable("rate limit store unreachable".to_string())
+    })?;
+
+    if state.allowed {
+        Ok(state)
+    } else {
+        log::warn!("🚦 Rate limited {} bucket {}", scope, bucket);
+        Err(RateLimitError::Limited { scope, state })
+    }
+}
+
+/// Take a token from the bucket of the wallet a request acts for
+pub async fn limit_user(
+    app_state: &AppState,
+    auth: &AuthContext,
+    user_pubkey: &str,
+) -> Result<(), RateLimitError> {
+    if auth.is_internal() {
+        return Ok(());
+    }
+
+    take_token(
+        app_state.mpc_client.redis(),
+        "user",
+        &format!("user:{}", user_pubkey),
+        &app_state.rate_limits.per_user,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Count one computation against the caller's daily merchant quota
+///
+/// Returns `None` for internal callers and merchants without a quota.
+pub async fn consume_daily_quota(
+    app_state: &AppState,
+    auth: &AuthContext,
+) -> Result<Option<QuotaState>, RateLimitError> {
+    let Some(merchant_id) = auth.merchant_id.as_deref() else {
+        return Ok(None);
+    };
+    let limit = app_state.rate_limits.quota_for(merchant_id);
+    if limit == 0 {
+        return Ok(None);
+    }
+
+    let (day, reset_at) = quota_window(Utc::now());
+    let quota = app_state
+        .mpc_client
+        .redis()
+        .consume_daily_quota(merchant_id, &day, limit, reset_at)
+        .await
+        .map_err(|e| {
+            log::error!("❌ Quota check failed: {}", e);
+            RateLimitError::Unavailable("quota store unreachable".to_string())
+        })?;
+
+    if quota.allowed {
+        Ok(Some(quota))
+    } else {
+        log::warn!(
+            "🚦 Merchant {} reached its daily quota of {}",
+            merchant_id,
+            limit
+        );
+        Err(RateLimitError::QuotaExceeded(quota))
+    }
+}
+
+/// Give back a computation counted by [`consume_daily_quota`] whose
+/// invocation failed
+pub async fn refund_daily_quota(app_state: &AppState, auth: &AuthContext, quota: &QuotaState) {
+    let Some(merchant_id) = auth.merchant_id.as_deref() else {
+        return;
+    };
+    if let Err(e) = app_state
+        .mpc_client
+        .redis()
+        .refund_daily_quota(merchant_id, &quota.day)
+        .await
+    {
+        log::error!("❌ Quota refund for {} failed: {}", merchant_id, e);
+    }
+}
+
+/// Client IP keying the per-IP bucket
+///
+/// `realip_remote_addr` falls back to the peer address, with its port, when
+/// no forwarding header is present.
+fn client_ip(req: &ServiceRequest, trust_proxy: bool) -> Option<String> {
+    if !trust_proxy {
+        return req.peer_addr().map(|peer| peer.ip().to_string());
+    }
+
+    let info = req.connection_info();
+    let addr = info.realip_remote_addr()?;
+    Some(
+        addr.parse::<SocketAddr>()
+            .map(|addr| addr.ip())
+            .or_else(|_| addr.parse::<IpAddr>())
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|_| addr.to_string()),
+    )
+}
+
+/// Middleware applying the per-IP bucket
+///
+/// Must run before [`super::auth::authenticate`] so unauthenticated
+/// requests are throttled as well; health checks pass through untouched.
+pub async fn limit_ip(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.path().starts_with("/api/health") {
+        return next.call(req).await;
+    }
+
+    let app_state = req
+        .app_data::<web::Data<AppState>>()
+        .cloned()
+        .ok_or_else(|| RateLimitError::Unavailable("application state missing".to_string()))?;
+    let limits = &app_state.rate_limits;
+
+    if let Some(ip) = client_ip(&req, limits.trust_proxy) {
+        take_token(
+            app_state.mpc_client.redis(),
+            "client IP",
+            &format!("ip:{}", ip),
+            &limits.per_ip,
+        )
+        .await?;
+    }
+
+    next.call(req).await
+}
+
+/// Middleware applying the per-credential bucket
+///
+/// Must run after [`super::auth::authenticate`]; requests without an
+/// [`AuthContext`] (health checks) pass through untouched.
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let auth = req.extensions().get::<AuthContext>().cloned();
+    let Some(auth) = auth.filter(|auth| !auth.is_internal()) else {
+        return next.call(req).await;
+    };
+
+    let app_state = req
+        .app_data::<web::Data<AppState>>()
+        .cloned()
+        .ok_or_else(|| RateLimitError::Unavailable("application state missing".to_string()))?;
+    let key_state = take_token(
+        app_state.mpc_client.redis(),
+        "credential",
+        &format!("key:{}", auth.principal),
+        &app_state.rate_limits.per_key,
+    )
+    .await?;
+
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-limit"),
+        HeaderValue::from(key_state.limit),
+    );
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-remaining"),
+        HeaderValue::from(key_state.remaining),
+    );
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_merchant_quotas() {
+        let quotas = parse_merchant_quotas(" merchant_a=500, merchant_b=0 ,").unwrap();
+        assert_eq!(quotas.get("merchant_a"), Some(&500));
+        assert_eq!(quotas.get("merchant_b"), Some(&0));
+
+        assert!(parse_merchant_quotas("merchant_a").is_err());
+        assert!(parse_merchant_quotas("merchant_a=lots").is_err());
+    }
+
+    #[test]
+    fn test_quota_override() {
+        let limit = BucketLimit {
+            capacity: 1,
+            refill_per_sec: 1.0,
+        };
+        let config = RateLimitConfig {
+            per_key: limit.clone(),
+            per_user: limit.clone(),
+            per_ip: limit,
+            trust_proxy: false,
+            daily_quota: 100,
+            merchant_quotas: parse_merchant_quotas("merchant_a=5").unwrap(),
+        };
+        assert_eq!(config.quota_for("merchant_a"), 5);
+        assert_eq!(config.quota_for("merchant_b"), 100);
+    }
+
+    #[test]
+    fn test_client_ip_trusts_forwarding_headers_only_when_enabled() {
+        let req = actix_web::test::TestRequest::default()
+            .peer_addr("10.0.0.2:41000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "203.0.113.7, 10.0.0.2"))
+            .to_srv_request();
+        assert_eq!(client_ip(&req, false).as_deref(), Some("10.0.0.2"));
+        assert_eq!(client_ip(&req, true).as_deref(), Some("203.0.113.7"));
+
+        let direct = actix_web::test::TestRequest::default()
+            .peer_addr("10.0.0.3:41000".parse().unwrap())
+            .to_srv_request();
+        assert_eq!(client_ip(&direct, true).as_deref(), Some("10.0.0.3"));
+    }
+
+    #[test]
+    fn test_quota_window_resets_at_utc_midnight() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 9, 17, 30, 0).unwrap();
+        let (day, reset_at) = quota_window(now);
+        assert_eq!(day, "2024-03-09");
+        assert_eq!(
+            reset_at,
+            Utc.with_ymd_and_hms(2024, 3, 10, 0, 0, 0)
+                .unwrap()
+                .timestamp()
+        );
+    }
+
+    #[test]
+    fn test_limited_response_headers() {
+        let err = RateLimitError::Limited {
+            scope: "user",
+            state: BucketState {
+                allowed: false,
+                limit: 30,
+                remaining: 0,
+                retry_after_ms: 1500,
+            },
+        };
+        let res = err.error_response();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "2");
+        assert_eq!(res.headers().get(RATE_LIMIT_LIMIT).unwrap(), "30");
+        assert_eq!(res.headers().get(RATE_LIMIT_REMAINING).unwrap(), "0");
+    }
+
+    #[test]
+    fn test_quota_response_headers() {
+        let reset_at = Utc::now().timestamp() + 120;
+        let err = RateLimitError::QuotaExceeded(QuotaState {
+            allowed: false,
+            limit: 10,
+            used: 10,
+            day: "2024-03-09".to_string(),
+            reset_at,
+        });
+        let res = err.error_response();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(QUOTA_LIMIT).unwrap(), "10");
+        assert_eq!(res.headers().get(QUOTA_REMAINING).unwrap(), "0");
+        assert_eq!(
+            res.headers().get(QUOTA_RESET).unwrap(),
+            reset_at.to_string().as_str()
+        );
+
+        let retry_after: i64 = res
+            .headers()
+            .get(header::RETRY_AFTER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=120).contains(&retry_after));
+    }
+}
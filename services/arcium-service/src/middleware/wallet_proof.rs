@@ -0,0 +1,231 @@
+//! Proof that the caller controls the `user_pubkey` it acts for
+//!
+//! Requests to wallet-scoped routes carry three headers:
+//!
+//! - `X-Wallet-Timestamp`: unix seconds, within `MAX_CLOCK_SKEW_SECS` of now
+//! - `X-Wallet-Nonce`: 8-128 chars of `[A-Za-z0-9_-]`, single use per wallet
+//! - `X-Wallet-Signature`: base58 ed25519 signature by `user_pubkey` over
+//!   the canonical request digest below
+//!
+//! ```text
+//! ninjapay-arcium-v1\n{METHOD}\n{path?query}\n{hex sha256(body)}\n{timestamp}\n{nonce}
+//! ```
+//!
+//! Internal service principals are exempt; they are already trusted with
+//! every wallet.
+
+use super::auth::{AuthContext, AuthError};
+use crate::utils::RedisClient;
+use actix_web::HttpRequest;
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+
+const SIGNATURE_HEADER: &str = "X-Wallet-Signature";
+const TIMESTAMP_HEADER: &str = "X-Wallet-Timestamp";
+const NONCE_HEADER: &str = "X-Wallet-Nonce";
+const DOMAIN_TAG: &str = "ninjapay-arcium-v1";
+
+/// Accepted distance between the signed timestamp and server time
+const MAX_CLOCK_SKEW_SECS: i64 = 300;
+/// How long a used nonce is remembered; must exceed the skew window
+const NONCE_TTL_SECS: u64 = 2 * MAX_CLOCK_SKEW_SECS as u64;
+
+/// Build the canonical digest a wallet signs for a request
+pub fn canonical_message(
+    method: &str,
+    path_and_query: &str,
+    body: &[u8],
+    timestamp: i64,
+    nonce: &str,
+) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        DOMAIN_TAG,
+        method.to_uppercase(),
+        path_and_query,
+        hex::encode(Sha256::digest(body)),
+        timestamp,
+        nonce
+    )
+}
+
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Result<&'a str, AuthError> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| AuthError::Unauthorized(format!("Missing {} header", name)))
+}
+
+fn valid_nonce(nonce: &str) -> bool {
+    (8..=128).contains(&nonce.len())
+        && nonce
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Check the signature headers without consuming the nonce
+///
+/// Returns the nonce to be claimed once the signature is known to be valid.
+fn verify_signature(
+    req: &HttpRequest,
+    body: &[u8],
+    user_pubkey: &str,
+    now: i64,
+) -> Result<String, AuthError> {
+    let pubkey = Pubkey::from_str(user_pubkey).map_err(|_| {
+        AuthError::Unauthorized("user_pubkey is not a valid public key".to_string())
+    })?;
+
+    let timestamp = header(req, TIMESTAMP_HEADER)?
+        .parse::<i64>()
+        .map_err(|_| AuthError::Unauthorized(format!("Invalid {} header", TIMESTAMP_HEADER)))?;
+    if (now - timestamp).abs() > MAX_CLOCK_SKEW_SECS {
+        return Err(AuthError::Unauthorized(
+            "Wallet signature timestamp is outside the allowed window".to_string(),
+        ));
+    }
+
+    let nonce = header(req, NONCE_HEADER)?;
+    if !valid_nonce(nonce) {
+        return Err(AuthError::Unauthorized(format!(
+            "Invalid {} header",
+            NONCE_HEADER
+        )));
+    }
+
+    let signature = Signature::from_str(header(req, SIGNATURE_HEADER)?)
+        .map_err(|_| AuthError::Unauthorized(format!("Invalid {} header", SIGNATURE_HEADER)))?;
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or_else(|| req.path());
+    let message = canonical_message(
+        req.method().as_str(),
+        path_and_query,
+        body,
+        timestamp,
+        nonce,
+    );
+
+    if !signature.verify(pubkey.as_ref(), message.as_bytes()) {
+        return Err(AuthError::Unauthorized(
+            "Wallet signature does not verify for user_pubkey".to_string(),
+        ));
+    }
+
+    Ok(nonce.to_string())
+}
+
+/// Require a fresh wallet signature by `user_pubkey` over this request
+pub async fn verify_wallet_proof(
+    auth: &AuthContext,
+    req: &HttpRequest,
+    body: &[u8],
+    user_pubkey: &str,
+    redis: &RedisClient,
+) -> Result<(), AuthError> {
+    if auth.is_internal() {
+        return Ok(());
+    }
+
+    let nonce = verify_signature(req, body, user_pubkey, chrono::Utc::now().timestamp())?;
+
+    let fresh = redis
+        .claim_nonce(user_pubkey, &nonce, NONCE_TTL_SECS)
+        .await
+        .map_err(|e| {
+            log::error!("❌ Nonce check failed: {}", e);
+            AuthError::Unavailable("nonce store unreachable".to_string())
+        })?;
+    if !fresh {
+        return Err(AuthError::Unauthorized(
+            "Wallet signature nonce has already been used".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    const NOW: i64 = 1_700_000_000;
+    const NONCE: &str = "nonce_0123456789";
+
+    fn signed_request(keypair: &Keypair, body: &[u8], timestamp: i64) -> HttpRequest {
+        let path = format!("/api/computation/list/{}?limit=10", keypair.pubkey());
+        let message = canonical_message("GET", &path, body, timestamp, NONCE);
+        let signature = keypair.sign_message(message.as_bytes());
+
+        TestRequest::get()
+            .uri(&path)
+            .insert_header((SIGNATURE_HEADER, signature.to_string()))
+            .insert_header((TIMESTAMP_HEADER, timestamp.to_string()))
+            .insert_header((NONCE_HEADER, NONCE))
+            .to_http_request()
+    }
+
+    #[test]
+    fn test_valid_signature() {
+        let keypair = Keypair::new();
+        let req = signed_request(&keypair, b"", NOW);
+
+        let nonce = verify_signature(&req, b"", &keypair.pubkey().to_string(), NOW).unwrap();
+        assert_eq!(nonce, NONCE);
+    }
+
+    #[test]
+    fn test_signature_by_other_wallet_rejected() {
+        let signer = Keypair::new();
+        let victim = Keypair::new();
+        let req = signed_request(&signer, b"", NOW);
+
+        assert!(verify_signature(&req, b"", &victim.pubkey().to_string(), NOW).is_err());
+    }
+
+    #[test]
+    fn test_tampered_body_rejected() {
+        let keypair = Keypair::new();
+        let req = signed_request(&keypair, b"{\"a\":1}", NOW);
+
+        assert!(verify_signature(&req, b"{\"a\":2}", &keypair.pubkey().to_string(), NOW).is_err());
+    }
+
+    #[test]
+    fn test_stale_timestamp_rejected() {
+        let keypair = Keypair::new();
+        let req = signed_request(&keypair, b"", NOW - MAX_CLOCK_SKEW_SECS - 1);
+
+        assert!(verify_signature(&req, b"", &keypair.pubkey().to_string(), NOW).is_err());
+    }
+
+    #[test]
+    fn test_missing_headers_rejected() {
+        let keypair = Keypair::new();
+        let req = TestRequest::get()
+            .uri("/api/computation/list/x")
+            .to_http_request();
+
+        assert!(matches!(
+            verify_signature(&req, b"", &keypair.pubkey().to_string(), NOW),
+            Err(AuthError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn test_nonce_format() {
+        assert!(valid_nonce(NONCE));
+        assert!(!valid_nonce("short"));
+        assert!(!valid_nonce("has spaces in it"));
+    }
+}
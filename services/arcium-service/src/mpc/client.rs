@@ -1,11 +1,26 @@
+use super::comp_def::{CompDefInit, CompDefInitializer};
+use super::discriminators::{anchor_account_discriminator, decode_anchor_account};
 use super::encryption::EncryptionHelper;
+use super::events::vault_lite_program_from_env;
+use super::integrity::{
+    arcium_core_program_from_env, CircuitVerifier, IntegrityReport, VerificationPolicy,
+};
+use super::registry::{AccountRole, InstructionRegistry, RegisteredInstruction};
 use super::simulator::MpcSimulator;
+use super::sweeper::{sweep_interval_from_env, AccountSweeper, SweepReport};
 use super::types::{
-    ComputationMetadata, ComputationRequest, ComputationResult, ComputationStatus, ComputationType,
+    ComputationCursor, ComputationFilter, ComputationMetadata, ComputationPage, ComputationRequest,
+    ComputationResult, ComputationStatus, ComputationType,
+};
+use super::vault_lite::{
+    payment_address, vault_address, EncryptedPaymentMetadata, PaymentMetadata, PaymentRecord,
+    PAYMENT_RECORD_VERSION,
 };
+use crate::error::{ServiceError, ServiceResult};
 use crate::utils::{hmac_sha256_hex, load_master_key_from_env, load_secret_string, RedisClient};
 use borsh::BorshSerialize;
 use reqwest::Client;
+use serde::Serialize;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
@@ -17,9 +32,11 @@ use solana_sdk::{
     transaction::Transaction,
 };
 use solana_transaction_status::UiTransactionEncoding;
-use std::error::Error;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 
 /// MPC Operation Mode
 #[derive(Debug, Clone, PartialEq)]
@@ -44,13 +61,40 @@ pub struct MpcClient {
     mode: MpcMode,
     cluster_address: Option<String>,
     program_id: Option<Pubkey>,
-    simulator: Option<MpcSimulator>,
+    simulator: Option<Arc<MpcSimulator>>,
     redis: Arc<RedisClient>,
+    registry: Arc<InstructionRegistry>,
     encryption: EncryptionHelper,
     rpc_client: Option<Arc<RpcClient>>,
     payer_keypair: Option<Arc<Keypair>>,
+    verifier: Option<Arc<CircuitVerifier>>,
+    /// Last integrity report served to health checks, with when it was taken
+    integrity_cache: Mutex<Option<(Instant, IntegrityReport)>>,
+    comp_defs: Option<CompDefInitializer>,
+    initialization: Mutex<InitializationStatus>,
+    sweeper: Option<Arc<AccountSweeper>>,
 }
 
+/// Outcome of [`MpcClient::initialize`]
+#[derive(Debug, Clone, Serialize)]
+pub struct InitializationReport {
+    pub cluster: String,
+    pub computation_definitions: Vec<CompDefInit>,
+}
+
+/// Progress of the background initialization job
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum InitializationStatus {
+    NotStarted,
+    Running,
+    Completed { report: InitializationReport },
+    Failed { error: String },
+}
+
+/// How long health checks reuse a circuit integrity report
+const INTEGRITY_REPORT_TTL: Duration = Duration::from_secs(60);
+
 const CLUSTER_SEED: &[u8] = b"Cluster";
 const ARCIUM_FEE_POOL_ACCOUNT: Pubkey = Pubkey::new_from_array([
     94, 87, 49, 175, 232, 200, 92, 37, 140, 243, 194, 109, 249, 141, 31, 66, 59, 91, 113, 165, 232,
@@ -76,17 +120,43 @@ struct ClusterConfig {
     cu_price: u64,
 }
 
+fn not_configured(component: &str) -> ServiceError {
+    ServiceError::Configuration(format!("{} not initialized for this MPC mode", component))
+}
+
+/// Run blocking RPC work on the blocking thread pool, keeping it off the
+/// async workers
+async fn run_blocking<T, F>(work: F) -> ServiceResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> ServiceResult<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| ServiceError::Upstream(format!("RPC task failed: {}", e)))?
+}
+
+fn verify_circuits(
+    verifier: &CircuitVerifier,
+    circuits: &[String],
+) -> ServiceResult<IntegrityReport> {
+    let circuits: Vec<&str> = circuits.iter().map(String::as_str).collect();
+    verifier.verify(&circuits)
+}
+
 impl ClusterConfig {
-    fn from_env(default_authority: &Pubkey, program_id: Pubkey) -> Result<Self, Box<dyn Error>> {
+    fn from_env(default_authority: &Pubkey, program_id: Pubkey) -> ServiceResult<Self> {
         let cluster_offset = std::env::var("ARCIUM_CLUSTER_OFFSET")
             .map(|val| val.parse::<u32>())
             .unwrap_or(Ok(0))
-            .map_err(|e| format!("Invalid ARCIUM_CLUSTER_OFFSET: {e}"))?;
+            .map_err(|e| {
+                ServiceError::Configuration(format!("Invalid ARCIUM_CLUSTER_OFFSET: {e}"))
+            })?;
 
         let authority = if let Ok(authority_str) = std::env::var("ARCIUM_CLUSTER_AUTHORITY") {
-            authority_str
-                .parse::<Pubkey>()
-                .map_err(|e| format!("Invalid ARCIUM_CLUSTER_AUTHORITY: {e}"))?
+            authority_str.parse::<Pubkey>().map_err(|e| {
+                ServiceError::Configuration(format!("Invalid ARCIUM_CLUSTER_AUTHORITY: {e}"))
+            })?
         } else {
             *default_authority
         };
@@ -94,12 +164,16 @@ impl ClusterConfig {
         let max_size = std::env::var("ARCIUM_CLUSTER_MAX_SIZE")
             .map(|val| val.parse::<u32>())
             .unwrap_or(Ok(32))
-            .map_err(|e| format!("Invalid ARCIUM_CLUSTER_MAX_SIZE: {e}"))?;
+            .map_err(|e| {
+                ServiceError::Configuration(format!("Invalid ARCIUM_CLUSTER_MAX_SIZE: {e}"))
+            })?;
 
         let cu_price = std::env::var("ARCIUM_CLUSTER_CU_PRICE")
             .map(|val| val.parse::<u64>())
             .unwrap_or(Ok(1))
-            .map_err(|e| format!("Invalid ARCIUM_CLUSTER_CU_PRICE: {e}"))?;
+            .map_err(|e| {
+                ServiceError::Configuration(format!("Invalid ARCIUM_CLUSTER_CU_PRICE: {e}"))
+            })?;
 
         Ok(Self {
             program_id,
@@ -121,7 +195,11 @@ impl ClusterConfig {
 
 impl MpcClient {
     /// Create a new MPC client in Local mode (for development)
-    pub fn new_local(redis: Arc<RedisClient>, build_path: String) -> Result<Self, Box<dyn Error>> {
+    pub fn new_local(
+        redis: Arc<RedisClient>,
+        registry: Arc<InstructionRegistry>,
+        build_path: String,
+    ) -> ServiceResult<Self> {
         // Load encryption master key from environment
         let master_key = load_master_key_from_env("ENCRYPTION_MASTER_KEY")?;
 
@@ -129,7 +207,17 @@ impl MpcClient {
         let encryption = Arc::new(EncryptionHelper::new_with_key(master_key));
 
         // Create simulator with encryption
-        let simulator = MpcSimulator::new(build_path, encryption.clone())?;
+        let simulator = Arc::new(MpcSimulator::new(build_path, encryption.clone())?);
+
+        // Pick up rebuilt circuits without a restart
+        let watch_circuits = std::env::var("ARCIUM_WATCH_CIRCUITS")
+            .map(|val| val != "false" && val != "0")
+            .unwrap_or(true);
+        if watch_circuits {
+            if let Err(e) = simulator.watch() {
+                log::warn!("⚠️  Circuit hot reload disabled: {}", e);
+            }
+        }
 
         log::info!("🔧 MPC Client initialized in LOCAL mode");
         log::info!(
@@ -143,18 +231,25 @@ impl MpcClient {
             program_id: None,
             simulator: Some(simulator),
             redis,
+            registry,
             encryption: (*encryption).clone(),
             rpc_client: None,
             payer_keypair: None,
+            verifier: None,
+            integrity_cache: Mutex::new(None),
+            comp_defs: None,
+            initialization: Mutex::new(InitializationStatus::NotStarted),
+            sweeper: None,
         })
     }
 
     /// Create a new MPC client in Cluster mode (for production)
     pub fn new_cluster(
         redis: Arc<RedisClient>,
+        registry: Arc<InstructionRegistry>,
         cluster_address: String,
         program_id: String,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> ServiceResult<Self> {
         // Load encryption master key from environment
         let master_key = load_master_key_from_env("ENCRYPTION_MASTER_KEY")?;
 
@@ -163,7 +258,7 @@ impl MpcClient {
         // Parse program ID
         let program_pubkey = program_id
             .parse::<Pubkey>()
-            .map_err(|e| format!("Invalid program ID: {}", e))?;
+            .map_err(|e| ServiceError::Configuration(format!("Invalid program ID: {}", e)))?;
 
         // Create RPC client
         let rpc_url = std::env::var("SOLANA_RPC_URL")
@@ -182,10 +277,25 @@ impl MpcClient {
         });
 
         let payer_keypair = Arc::new(
-            solana_sdk::signature::read_keypair_file(&keypair_path)
-                .map_err(|e| format!("Failed to load keypair from {}: {}", keypair_path, e))?,
+            solana_sdk::signature::read_keypair_file(&keypair_path).map_err(|e| {
+                ServiceError::Configuration(format!(
+                    "Failed to load keypair from {}: {}",
+                    keypair_path, e
+                ))
+            })?,
         );
 
+        let verifier = CircuitVerifier::from_env(rpc_client.clone(), program_pubkey)?;
+        let comp_defs =
+            CompDefInitializer::from_env(rpc_client.clone(), payer_keypair.clone(), program_pubkey);
+        let sweeper = Arc::new(AccountSweeper::from_env(
+            rpc_client.clone(),
+            payer_keypair.clone(),
+        )?);
+        if let Some(interval) = sweep_interval_from_env()? {
+            sweeper.spawn(interval);
+        }
+
         log::info!("🌐 MPC Client initialized in CLUSTER mode");
         log::info!("   Cluster: {}", cluster_address);
         log::info!("   Program: {}", program_pubkey);
@@ -198,9 +308,15 @@ impl MpcClient {
             program_id: Some(program_pubkey),
             simulator: None,
             redis,
+            registry,
             encryption,
             rpc_client: Some(rpc_client),
             payer_keypair: Some(payer_keypair),
+            verifier: Some(Arc::new(verifier)),
+            integrity_cache: Mutex::new(None),
+            comp_defs: Some(comp_defs),
+            initialization: Mutex::new(InitializationStatus::NotStarted),
+            sweeper: Some(sweeper),
         })
     }
 
@@ -214,6 +330,11 @@ impl MpcClient {
         &self.redis
     }
 
+    /// Computations this deployment permits
+    pub fn registry(&self) -> &Arc<InstructionRegistry> {
+        &self.registry
+    }
+
     /// List available instructions in simulator
     pub fn list_instructions(&self) -> Vec<String> {
         match &self.simulator {
@@ -222,10 +343,253 @@ impl MpcClient {
         }
     }
 
+    /// SHA-256 of each circuit loaded in the simulator; empty in Cluster mode
+    pub fn circuit_hashes(&self) -> HashMap<String, String> {
+        match &self.simulator {
+            Some(sim) => sim.circuit_hashes(),
+            None => HashMap::new(),
+        }
+    }
+
+    /// Compare circuits with the cluster's computation definitions; `None`
+    /// in Local mode
+    ///
+    /// Only circuits the registry maps to an `init_comp_def` instruction are
+    /// checked: the others have no definition for this program to compare
+    /// against.
+    pub fn verify_circuits(&self) -> ServiceResult<Option<IntegrityReport>> {
+        match &self.verifier {
+            Some(verifier) => verify_circuits(verifier, &self.verified_circuits()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// [`verify_circuits`](Self::verify_circuits) for health checks: runs on
+    /// the blocking thread pool and reuses a report for
+    /// `INTEGRITY_REPORT_TTL`, so polling the endpoint cannot drive RPC load
+    pub async fn circuit_integrity(&self) -> ServiceResult<Option<IntegrityReport>> {
+        let verifier = match &self.verifier {
+            Some(verifier) => verifier.clone(),
+            None => return Ok(None),
+        };
+
+        // Held across the check so concurrent callers wait for one report
+        let mut cached = self.integrity_cache.lock().await;
+        if let Some((checked_at, report)) = cached.as_ref() {
+            if checked_at.elapsed() < INTEGRITY_REPORT_TTL {
+                return Ok(Some(report.clone()));
+            }
+        }
+
+        let circuits = self.verified_circuits();
+        let report = run_blocking(move || verify_circuits(&verifier, &circuits)).await?;
+        *cached = Some((Instant::now(), report.clone()));
+        Ok(Some(report))
+    }
+
+    /// Circuits the registry maps to an `init_comp_def` instruction
+    fn verified_circuits(&self) -> Vec<String> {
+        let mut circuits: Vec<String> = self
+            .registry
+            .entries()
+            .iter()
+            .filter(|entry| entry.init_comp_def.is_some())
+            .map(|entry| entry.circuit.clone())
+            .collect();
+        circuits.sort_unstable();
+        circuits.dedup();
+        circuits
+    }
+
+    /// Startup integrity check: fails when circuits do not match and
+    /// `CIRCUIT_VERIFICATION=enforce`, otherwise only logs
+    pub fn check_circuit_integrity(&self) -> ServiceResult<()> {
+        let enforce = self
+            .verifier
+            .as_ref()
+            .is_some_and(|verifier| verifier.policy() == VerificationPolicy::Enforce);
+
+        let failure = match self.verify_circuits() {
+            Ok(None) => return Ok(()),
+            Ok(Some(report)) if report.verified => {
+                if report.policy != VerificationPolicy::Off {
+                    log::info!(
+                        "🔏 {} circuits match their computation definitions",
+                        report.circuits.len()
+                    );
+                }
+                return Ok(());
+            }
+            Ok(Some(report)) => ServiceError::Conflict(format!(
+                "Circuits do not match on-chain computation definitions: {}",
+                report.failures().join(", ")
+            )),
+            Err(e) => e,
+        };
+
+        if enforce {
+            Err(failure)
+        } else {
+            log::warn!("⚠️  Circuit integrity check failed: {}", failure);
+            Ok(())
+        }
+    }
+
     /// Initialize the MPC computation environment
     ///
-    /// This is a one-time setup that prepares the cluster for computations
-    pub async fn initialize(&self) -> Result<String, Box<dyn Error>> {
+    /// Prepares the cluster and registers a computation definition for
+    /// every circuit the registry maps to an `init_comp_def` instruction.
+    /// Safe to repeat: existing accounts are left as they are. Every step
+    /// waits for confirmation, so this runs on the blocking thread pool.
+    pub async fn initialize(self: Arc<Self>) -> ServiceResult<InitializationReport> {
+        run_blocking(move || {
+            let cluster = self.initialize_cluster()?;
+            let computation_definitions = self.initialize_computation_definitions()?;
+
+            Ok(InitializationReport {
+                cluster,
+                computation_definitions,
+            })
+        })
+        .await
+    }
+
+    /// Start [`initialize`](Self::initialize) in the background unless a run
+    /// is already in progress, returning the job's status
+    pub async fn start_initialization(self: &Arc<Self>) -> InitializationStatus {
+        let mut status = self.initialization.lock().await;
+        if !matches!(*status, InitializationStatus::Running) {
+            *status = InitializationStatus::Running;
+
+            let client = self.clone();
+            tokio::spawn(async move {
+                let outcome = match client.clone().initialize().await {
+                    Ok(report) => {
+                        log::info!(
+                            "✅ Initialization complete: {} computation definitions",
+                            report.computation_definitions.len()
+                        );
+                        InitializationStatus::Completed { report }
+                    }
+                    Err(e) => {
+                        log::error!("❌ Initialization failed: {}", e);
+                        InitializationStatus::Failed {
+                            error: e.to_string(),
+                        }
+                    }
+                };
+                *client.initialization.lock().await = outcome;
+            });
+        }
+        status.clone()
+    }
+
+    /// Status of the last initialization job
+    pub async fn initialization_status(&self) -> InitializationStatus {
+        self.initialization.lock().await.clone()
+    }
+
+    /// Close settled payment records and payroll batches past their
+    /// retention period, returning their rent
+    pub async fn sweep_accounts(&self) -> ServiceResult<SweepReport> {
+        let sweeper = self.sweeper.clone().ok_or_else(|| {
+            ServiceError::Validation("Account sweeping requires cluster mode".to_string())
+        })?;
+        run_blocking(move || sweeper.sweep()).await
+    }
+
+    /// Encrypt a payment memo and payer identity under the key of vault owner
+    /// `owner_pubkey`, for `record_payment`
+    pub fn encrypt_payment_metadata(
+        &self,
+        owner_pubkey: &str,
+        memo: Option<&str>,
+        payer_identity: Option<&str>,
+    ) -> ServiceResult<EncryptedPaymentMetadata> {
+        EncryptedPaymentMetadata::encrypt(&self.encryption, owner_pubkey, memo, payer_identity)
+    }
+
+    /// Load vault-lite payment `payment_id` of the vault owned by
+    /// `owner_pubkey` and decrypt its memo and payer identity
+    pub async fn payment_metadata(
+        &self,
+        owner_pubkey: &str,
+        payment_id: &str,
+    ) -> ServiceResult<PaymentMetadata> {
+        let rpc_client = self.rpc_client.clone().ok_or_else(|| {
+            ServiceError::Validation("Reading payment records requires cluster mode".to_string())
+        })?;
+        let owner = Pubkey::from_str(owner_pubkey)
+            .map_err(|_| ServiceError::Validation(format!("Invalid owner: {}", owner_pubkey)))?;
+
+        let program = vault_lite_program_from_env()?;
+        let vault = vault_address(&program, &owner);
+        let address = payment_address(&program, &vault, payment_id)?;
+
+        let account = run_blocking(move || {
+            Ok(rpc_client
+                .get_account_with_commitment(&address, rpc_client.commitment())?
+                .value)
+        })
+        .await?
+        .filter(|account| account.owner == program)
+        .ok_or_else(|| ServiceError::NotFound(format!("Payment {} not found", payment_id)))?;
+        if account.data.get(..8) != Some(&anchor_account_discriminator("PaymentRecord")[..]) {
+            return Err(ServiceError::Upstream(format!(
+                "Account {} is not a payment record",
+                address
+            )));
+        }
+
+        let record: PaymentRecord = decode_anchor_account(&account.data, &address)?;
+        if record.version != PAYMENT_RECORD_VERSION {
+            return Err(ServiceError::Conflict(format!(
+                "Payment {} uses layout v{}; migrate it first",
+                payment_id, record.version
+            )));
+        }
+
+        PaymentMetadata::decrypt(record, &address, owner_pubkey, &self.encryption)
+    }
+
+    /// Register every circuit with a vault `init_comp_def` instruction
+    fn initialize_computation_definitions(&self) -> ServiceResult<Vec<CompDefInit>> {
+        let comp_defs = match &self.comp_defs {
+            Some(comp_defs) => comp_defs,
+            None => return Ok(Vec::new()),
+        };
+
+        let arcium_program = arcium_core_program_from_env()?.ok_or_else(|| {
+            ServiceError::Configuration(
+                "ARCIUM_CORE_PROGRAM_ID must be set to initialize computation definitions"
+                    .to_string(),
+            )
+        })?;
+
+        log::info!("📐 Initializing computation definitions");
+
+        let mut initialized: Vec<CompDefInit> = Vec::new();
+        for entry in self.registry.entries() {
+            let init_instruction = match &entry.init_comp_def {
+                Some(init_instruction) => init_instruction,
+                None => continue,
+            };
+            if initialized.iter().any(|done| done.circuit == entry.circuit) {
+                continue;
+            }
+
+            initialized.push(comp_defs.initialize(
+                &arcium_program,
+                &entry.circuit,
+                init_instruction,
+            )?);
+        }
+
+        Ok(initialized)
+    }
+
+    /// Create the cluster account if it does not exist yet
+    fn initialize_cluster(&self) -> ServiceResult<String> {
         match self.mode {
             MpcMode::Local => {
                 log::info!("📦 Initializing Local MPC environment");
@@ -241,12 +605,15 @@ impl MpcClient {
                 let rpc_client = self
                     .rpc_client
                     .as_ref()
-                    .ok_or("RPC client not initialized")?;
+                    .ok_or_else(|| not_configured("RPC client"))?;
                 let payer = self
                     .payer_keypair
                     .as_ref()
-                    .ok_or("Payer keypair not initialized")?;
-                let program_id = self.program_id.as_ref().ok_or("Program ID not set")?;
+                    .ok_or_else(|| not_configured("Payer keypair"))?;
+                let program_id = self
+                    .program_id
+                    .as_ref()
+                    .ok_or_else(|| not_configured("Program ID"))?;
 
                 let cluster_config = ClusterConfig::from_env(&payer.pubkey(), *program_id)?;
                 let cluster_pda = cluster_config.cluster_account();
@@ -278,7 +645,9 @@ impl MpcClient {
 
                 let mut instruction_data =
                     super::discriminators::anchor_discriminator("init_cluster").to_vec();
-                instruction_data.extend_from_slice(&args.try_to_vec()?);
+                instruction_data.extend_from_slice(&args.try_to_vec().map_err(|e| {
+                    ServiceError::Configuration(format!("Invalid cluster arguments: {}", e))
+                })?);
 
                 let instruction = Instruction {
                     program_id: *program_id,
@@ -299,7 +668,10 @@ impl MpcClient {
                 transaction
                     .try_sign(&[payer.as_ref()], recent_blockhash)
                     .map_err(|e| {
-                        format!("Failed to sign cluster initialization transaction: {}", e)
+                        ServiceError::Configuration(format!(
+                            "Failed to sign cluster initialization transaction: {}",
+                            e
+                        ))
                     })?;
 
                 let signature = rpc_client.send_and_confirm_transaction(&transaction)?;
@@ -313,23 +685,34 @@ impl MpcClient {
     /// Queue a computation for MPC execution
     ///
     /// Submits encrypted inputs to the Arcium MPC cluster or local simulator
-    pub async fn invoke_computation(
-        &self,
-        request: ComputationRequest,
-    ) -> Result<String, Box<dyn Error>> {
+    pub async fn invoke_computation(&self, request: ComputationRequest) -> ServiceResult<String> {
         log::info!(
             "🚀 Invoking computation: {:?} for user: {}",
             request.computation_type,
             request.user_pubkey
         );
 
+        let entry = self
+            .registry
+            .get(&request.computation_type)
+            .ok_or_else(|| {
+                ServiceError::Validation(format!(
+                    "Computation type '{}' is not registered",
+                    request.computation_type.api_name()
+                ))
+            })?
+            .clone();
+
         // Generate computation ID
         let computation_id = format!("comp_{}", chrono::Utc::now().timestamp_millis());
 
         // Validate encrypted inputs
         for (i, input) in request.encrypted_inputs.iter().enumerate() {
             if !self.encryption.validate_encrypted_input(input)? {
-                return Err(format!("Invalid encrypted input at index {}", i).into());
+                return Err(ServiceError::Validation(format!(
+                    "Invalid encrypted input at index {}",
+                    i
+                )));
             }
         }
 
@@ -339,9 +722,7 @@ impl MpcClient {
             user_pubkey: request.user_pubkey.clone(),
             computation_type: request.computation_type.clone(),
             status: ComputationStatus::Queued,
-            created_at: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_secs(),
+            created_at: chrono::Utc::now().timestamp() as u64,
             completed_at: None,
             callback_url: request.callback_url.clone(),
             entity_type: request.entity_type.clone(),
@@ -349,6 +730,8 @@ impl MpcClient {
             metadata: request.metadata.clone(),
             cluster_tx_signature: None,
             attestation: None,
+            status_history: Vec::new(),
+            merchant_id: request.merchant_id.clone(),
         };
 
         // Store metadata in Redis
@@ -357,11 +740,11 @@ impl MpcClient {
         // Execute based on mode
         match self.mode {
             MpcMode::Local => {
-                self.execute_local_computation(&computation_id, request)
+                self.execute_local_computation(&computation_id, &entry, request)
                     .await?;
             }
             MpcMode::Cluster => {
-                self.queue_cluster_computation(&computation_id, request)
+                self.queue_cluster_computation(&computation_id, &entry, request)
                     .await?;
             }
         }
@@ -373,8 +756,9 @@ impl MpcClient {
     async fn execute_local_computation(
         &self,
         computation_id: &str,
+        entry: &RegisteredInstruction,
         request: ComputationRequest,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> ServiceResult<()> {
         log::debug!("💻 Executing computation locally: {}", computation_id);
 
         // Update status to Processing
@@ -382,19 +766,13 @@ impl MpcClient {
             .update_computation_status(computation_id, ComputationStatus::Processing)
             .await?;
 
-        // Get instruction name
-        let instruction_name = match request.computation_type {
-            ComputationType::ConfidentialTransfer => "encrypted_transfer",
-            ComputationType::BatchPayroll => "batch_payroll",
-            ComputationType::BalanceQuery => "query_balance",
-            ComputationType::Custom(ref name) => name,
-        };
+        let instruction_name = entry.circuit.as_str();
 
         // Execute using simulator
         let simulator = self
             .simulator
             .as_ref()
-            .ok_or("Simulator not initialized in Local mode")?;
+            .ok_or_else(|| not_configured("Simulator"))?;
 
         let result = simulator.execute_instruction(
             instruction_name,
@@ -402,26 +780,23 @@ impl MpcClient {
             &request.user_pubkey,
         )?;
 
-        // Store result in Redis
+        // Complete and store the result atomically
         self.redis
-            .store_result(computation_id, &result, 3600)
+            .complete_computation(computation_id, &result, 3600)
             .await?;
 
-        // Update status to Completed
+        // Store attestation metadata for simulator execution
         self.redis
-            .update_computation_status(computation_id, ComputationStatus::Completed)
+            .update_computation_metadata(computation_id, |metadata| {
+                metadata.attestation = Some(serde_json::json!({
+                    "mode": "local-simulator",
+                    "instruction": instruction_name,
+                    "completed_at": chrono::Utc::now().to_rfc3339(),
+                }));
+                Ok(())
+            })
             .await?;
 
-        // Store attestation metadata for simulator execution
-        if let Some(mut metadata) = self.redis.get_computation_metadata(computation_id).await? {
-            metadata.attestation = Some(serde_json::json!({
-                "mode": "local-simulator",
-                "instruction": instruction_name,
-                "completed_at": chrono::Utc::now().to_rfc3339(),
-            }));
-            self.redis.store_computation_metadata(&metadata).await?;
-        }
-
         self.notify_callback(
             computation_id,
             ComputationStatus::Completed,
@@ -439,8 +814,9 @@ impl MpcClient {
     async fn queue_cluster_computation(
         &self,
         computation_id: &str,
+        entry: &RegisteredInstruction,
         request: ComputationRequest,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> ServiceResult<()> {
         log::debug!("☁️  Queuing computation to cluster: {}", computation_id);
 
         // Update status to Processing
@@ -451,18 +827,21 @@ impl MpcClient {
         let rpc_client = self
             .rpc_client
             .as_ref()
-            .ok_or("RPC client not initialized")?;
+            .ok_or_else(|| not_configured("RPC client"))?;
         let payer = self
             .payer_keypair
             .as_ref()
-            .ok_or("Payer keypair not initialized")?;
-        let program_id = self.program_id.as_ref().ok_or("Program ID not set")?;
+            .ok_or_else(|| not_configured("Payer keypair"))?;
+        let program_id = self
+            .program_id
+            .as_ref()
+            .ok_or_else(|| not_configured("Program ID"))?;
 
         // Parse user pubkey
         let user_pubkey = request
             .user_pubkey
             .parse::<Pubkey>()
-            .map_err(|e| format!("Invalid user pubkey: {}", e))?;
+            .map_err(|e| ServiceError::Validation(format!("Invalid user pubkey: {}", e)))?;
 
         // Derive vault PDA for user
         let (vault_pda, _bump) =
@@ -474,28 +853,9 @@ impl MpcClient {
         log::info!("   User vault PDA: {}", vault_pda);
         log::info!("   Cluster PDA: {}", cluster_pda);
 
-        // Get instruction name
-        let instruction_name = match request.computation_type {
-            ComputationType::ConfidentialTransfer => "confidential_transfer",
-            ComputationType::BatchPayroll => "batch_payroll",
-            ComputationType::BalanceQuery => "query_balance",
-            ComputationType::Custom(ref name) => name,
-        };
-
-        // Build instruction data with proper Anchor discriminator
+        // Build instruction data with the registered discriminator
         // Anchor uses: SHA256("global:{instruction_name}")[..8]
-        let discriminator = match request.computation_type {
-            ComputationType::ConfidentialTransfer => {
-                super::discriminators::ninjapay_vault::confidential_transfer()
-            }
-            ComputationType::BatchPayroll => {
-                super::discriminators::anchor_discriminator("batch_payroll")
-            }
-            ComputationType::BalanceQuery => {
-                super::discriminators::anchor_discriminator("query_balance")
-            }
-            ComputationType::Custom(ref name) => super::discriminators::anchor_discriminator(name),
-        };
+        let discriminator = entry.cluster_discriminator()?;
 
         log::debug!("Using discriminator: {:02x?}", discriminator);
         let mut ix_data = discriminator.to_vec();
@@ -517,13 +877,17 @@ impl MpcClient {
             user_pubkey
         };
 
-        // Build instruction accounts
-        let accounts = vec![
-            AccountMeta::new(vault_pda, false),
-            AccountMeta::new(user_pubkey, true), // User must sign
-            AccountMeta::new_readonly(cluster_pda, false),
-            AccountMeta::new(recipient_pubkey, false),
-        ];
+        // Build instruction accounts in the order the registry declares
+        let accounts = entry
+            .accounts
+            .iter()
+            .map(|role| match role {
+                AccountRole::Vault => AccountMeta::new(vault_pda, false),
+                AccountRole::User => AccountMeta::new(user_pubkey, true), // User must sign
+                AccountRole::Cluster => AccountMeta::new_readonly(cluster_pda, false),
+                AccountRole::Recipient => AccountMeta::new(recipient_pubkey, false),
+            })
+            .collect();
 
         let instruction = Instruction::new_with_bytes(*program_id, &ix_data, accounts);
 
@@ -536,30 +900,36 @@ impl MpcClient {
 
         if user_pubkey != payer.pubkey() {
             let signature_str = request.user_signature.as_ref().ok_or_else(|| {
-                "User signature required for cluster computations when fee payer differs"
-                    .to_string()
+                ServiceError::Validation(
+                    "User signature required for cluster computations when fee payer differs"
+                        .to_string(),
+                )
             })?;
             let user_signature = Signature::from_str(signature_str)
-                .map_err(|e| format!("Invalid user signature: {}", e))?;
+                .map_err(|e| ServiceError::Validation(format!("Invalid user signature: {}", e)))?;
 
             let signer_index = transaction
                 .message
                 .account_keys
                 .iter()
                 .position(|key| key == &user_pubkey)
-                .ok_or("User account not present in transaction account keys")?;
+                .ok_or_else(|| {
+                    ServiceError::Validation(
+                        "User account not present in transaction account keys".to_string(),
+                    )
+                })?;
 
             if signer_index >= transaction.message.header.num_required_signatures as usize {
-                return Err(
-                    "User account is not flagged as a signer in the transaction header".into(),
-                );
+                return Err(ServiceError::Validation(
+                    "User account is not flagged as a signer in the transaction header".to_string(),
+                ));
             }
 
             if !user_signature.verify(user_pubkey.as_ref(), &message_bytes) {
-                return Err(
+                return Err(ServiceError::Validation(
                     "Provided user signature does not verify against the transaction message"
-                        .into(),
-                );
+                        .to_string(),
+                ));
             }
 
             transaction.signatures[signer_index] = user_signature;
@@ -575,21 +945,20 @@ impl MpcClient {
         );
 
         // Store transaction signature in metadata
-        let mut metadata = self
-            .redis
-            .get_computation_metadata(computation_id)
-            .await?
-            .ok_or("Computation metadata not found")?;
-        metadata.cluster_tx_signature = Some(signature.to_string());
-        metadata.attestation = Some(serde_json::json!({
-            "mode": "cluster",
-            "cluster_pda": cluster_pda.to_string(),
-            "cluster_offset": cluster_config.cluster_offset,
-            "submitted_at": chrono::Utc::now().to_rfc3339(),
-            "tx_signature": signature.to_string(),
-            "recipient": recipient_pubkey.to_string(),
-        }));
-        self.redis.store_computation_metadata(&metadata).await?;
+        self.redis
+            .update_computation_metadata(computation_id, |metadata| {
+                metadata.cluster_tx_signature = Some(signature.to_string());
+                metadata.attestation = Some(serde_json::json!({
+                    "mode": "cluster",
+                    "cluster_pda": cluster_pda.to_string(),
+                    "cluster_offset": cluster_config.cluster_offset,
+                    "submitted_at": chrono::Utc::now().to_rfc3339(),
+                    "tx_signature": signature.to_string(),
+                    "recipient": recipient_pubkey.to_string(),
+                }));
+                Ok(())
+            })
+            .await?;
 
         // In cluster mode, we wait for callback from the MPC network
         // The callback will update the status to Completed and store the result
@@ -607,7 +976,7 @@ impl MpcClient {
         status: ComputationStatus,
         result: Option<&[u8]>,
         error: Option<&str>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> ServiceResult<()> {
         let metadata = match self.redis.get_computation_metadata(computation_id).await? {
             Some(meta) => meta,
             None => return Ok(()),
@@ -673,8 +1042,9 @@ impl MpcClient {
         }
 
         let secret_hex = load_secret_string("ARCIUM_CALLBACK_SECRET")?;
-        let secret_bytes = hex::decode(secret_hex.trim())
-            .map_err(|e| format!("ARCIUM_CALLBACK_SECRET must be hex-encoded: {e}"))?;
+        let secret_bytes = hex::decode(secret_hex.trim()).map_err(|e| {
+            ServiceError::Configuration(format!("ARCIUM_CALLBACK_SECRET must be hex-encoded: {e}"))
+        })?;
 
         if payload.get("attestation").is_none() {
             payload["attestation"] = metadata
@@ -683,7 +1053,9 @@ impl MpcClient {
                 .unwrap_or(serde_json::Value::Null);
         }
 
-        let payload_bytes = serde_json::to_vec(&payload)?;
+        let payload_bytes = serde_json::to_vec(&payload).map_err(|e| {
+            ServiceError::Upstream(format!("Failed to encode callback payload: {}", e))
+        })?;
         let signature = hmac_sha256_hex(&secret_bytes, &payload_bytes)?;
 
         let client = Client::new();
@@ -727,7 +1099,7 @@ impl MpcClient {
     pub async fn get_computation_result(
         &self,
         computation_id: &str,
-    ) -> Result<Option<ComputationResult>, Box<dyn Error>> {
+    ) -> ServiceResult<Option<ComputationResult>> {
         log::debug!("🔍 Polling computation result: {}", computation_id);
 
         // Check Redis for metadata
@@ -757,6 +1129,12 @@ impl MpcClient {
                         result: vec![],
                         error: Some("Computation failed".to_string()),
                     })),
+                    ComputationStatus::Cancelled => Ok(Some(ComputationResult {
+                        computation_id: computation_id.to_string(),
+                        status: "cancelled".to_string(),
+                        result: vec![],
+                        error: Some("Computation cancelled".to_string()),
+                    })),
                     _ => {
                         // Still processing
                         Ok(None)
@@ -775,12 +1153,15 @@ impl MpcClient {
         computation_id: String,
         encrypted_result: Vec<u8>,
         signature: String,
-    ) -> Result<ComputationResult, Box<dyn Error>> {
+    ) -> ServiceResult<ComputationResult> {
         log::info!("📥 Handling callback for computation: {}", computation_id);
 
         // Verify signature from Arcium cluster
         if self.mode == MpcMode::Cluster {
-            let program_id = self.program_id.as_ref().ok_or("Program ID not set")?;
+            let program_id = self
+                .program_id
+                .as_ref()
+                .ok_or_else(|| not_configured("Program ID"))?;
 
             // Parse signature string as Solana transaction signature
             let tx_signature = match signature.parse::<Signature>() {
@@ -797,7 +1178,7 @@ impl MpcClient {
                         Some(&msg),
                     )
                     .await?;
-                    return Err(msg.into());
+                    return Err(ServiceError::Validation(msg));
                 }
             };
 
@@ -808,10 +1189,9 @@ impl MpcClient {
                         log::info!("✅ Transaction verified on-chain: {}", tx_signature);
 
                         // Additional validation: Check that the transaction invoked our program
-                        let tx_meta = confirmed_tx
-                            .transaction
-                            .meta
-                            .ok_or("Transaction metadata not available")?;
+                        let tx_meta = confirmed_tx.transaction.meta.ok_or_else(|| {
+                            ServiceError::Upstream("Transaction metadata not available".to_string())
+                        })?;
 
                         if tx_meta.err.is_some() {
                             let msg = "Transaction failed on-chain".to_string();
@@ -829,7 +1209,7 @@ impl MpcClient {
                                 Some(&msg),
                             )
                             .await?;
-                            return Err(msg.into());
+                            return Err(ServiceError::Upstream(msg));
                         }
 
                         log::info!("✅ Transaction successful");
@@ -847,7 +1227,7 @@ impl MpcClient {
                             Some(&msg),
                         )
                         .await?;
-                        return Err(msg.into());
+                        return Err(ServiceError::Upstream(msg));
                     }
                 }
             } else {
@@ -855,14 +1235,11 @@ impl MpcClient {
             }
         }
 
-        // Store result in Redis
+        // Complete and store the result atomically; a computation that was
+        // cancelled or already finished is rejected without touching its
+        // result
         self.redis
-            .store_result(&computation_id, &encrypted_result, 3600)
-            .await?;
-
-        // Update status to Completed
-        self.redis
-            .update_computation_status(&computation_id, ComputationStatus::Completed)
+            .complete_computation(&computation_id, &encrypted_result, 3600)
             .await?;
 
         self.notify_callback(
@@ -891,7 +1268,7 @@ impl MpcClient {
         from_pubkey: &str,
         to_pubkey: &str,
         encrypted_amount: Vec<u8>,
-    ) -> Result<String, Box<dyn Error>> {
+    ) -> ServiceResult<String> {
         log::info!(
             "💸 Initiating confidential transfer from {} to {}",
             from_pubkey,
@@ -909,6 +1286,7 @@ impl MpcClient {
             entity_type: None,
             reference_id: None,
             user_signature: None,
+            merchant_id: None,
         };
 
         self.invoke_computation(request).await
@@ -921,7 +1299,7 @@ impl MpcClient {
         &self,
         payer_pubkey: &str,
         recipients: Vec<(String, Vec<u8>)>, // (recipient_pubkey, encrypted_amount)
-    ) -> Result<String, Box<dyn Error>> {
+    ) -> ServiceResult<String> {
         log::info!(
             "💼 Initiating batch payroll for {} recipients",
             recipients.len()
@@ -949,18 +1327,34 @@ impl MpcClient {
             entity_type: None,
             reference_id: None,
             user_signature: None,
+            merchant_id: None,
         };
 
         self.invoke_computation(request).await
     }
 
-    /// List all computations for a user
+    /// List a page of computations for a user, newest first
     pub async fn list_user_computations(
         &self,
         user_pubkey: &str,
+        filter: &ComputationFilter,
+        cursor: Option<&ComputationCursor>,
         limit: usize,
-    ) -> Result<Vec<ComputationMetadata>, Box<dyn Error>> {
-        self.redis.list_user_computations(user_pubkey, limit).await
+    ) -> ServiceResult<ComputationPage> {
+        self.redis
+            .list_user_computations(user_pubkey, filter, cursor, limit)
+            .await
+    }
+
+    /// List every computation attempt linked to an external entity
+    pub async fn list_reference_computations(
+        &self,
+        entity_type: &str,
+        reference_id: &str,
+    ) -> ServiceResult<Vec<ComputationMetadata>> {
+        self.redis
+            .list_reference_computations(entity_type, reference_id)
+            .await
     }
 }
 
@@ -979,7 +1373,9 @@ mod tests {
         let redis_url = "redis://127.0.0.1:6379";
         let redis = Arc::new(RedisClient::new(redis_url).unwrap());
 
-        let client = MpcClient::new_local(redis, "build".to_string()).unwrap();
+        let registry = Arc::new(InstructionRegistry::from_env().unwrap());
+
+        let client = MpcClient::new_local(redis, registry, "build".to_string()).unwrap();
         assert_eq!(client.mode(), &MpcMode::Local);
     }
 
@@ -994,8 +1390,11 @@ mod tests {
         let redis_url = "redis://127.0.0.1:6379";
         let redis = Arc::new(RedisClient::new(redis_url).unwrap());
 
+        let registry = Arc::new(InstructionRegistry::from_env().unwrap());
+
         let client = MpcClient::new_cluster(
             redis,
+            registry,
             "cluster_address".to_string(),
             "program_id".to_string(),
         )
@@ -0,0 +1,331 @@
+//! Computation-definition initialization for the vault program
+//!
+//! Every registry entry with an `init_comp_def` instruction needs its circuit
+//! registered with Arcium before it can be queued. Initialization is
+//! idempotent: definitions that already exist are left alone, and on-chain
+//! circuits whose upload never finished are uploaded again and finalized.
+//!
+//! With `ARCIUM_CIRCUIT_BASE_URL` set, definitions point the nodes at
+//! `<base>/<circuit>.arcis` with the SHA-256 of the local build output, which
+//! is what [`CircuitVerifier`](super::integrity::CircuitVerifier) checks.
+//! Without it the circuit bytes are uploaded on-chain.
+//!
+//! The vault program only accepts `init_comp_def` from its upgrade authority,
+//! so the payer keypair must be that authority.
+
+use super::discriminators::anchor_discriminator;
+use super::instructions::InstructionLoader;
+use super::integrity::{
+    comp_def_account, comp_def_offset, raw_circuit_account, read_circuit_source, CircuitSourceState,
+};
+use crate::error::{ServiceError, ServiceResult};
+use borsh::BorshSerialize;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    bpf_loader_upgradeable::get_program_data_address,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use std::sync::Arc;
+
+const MXE_ACCOUNT_SEED: &[u8] = b"MXEAccount";
+
+/// Circuit bytes per `upload_circuit` transaction
+const UPLOAD_CHUNK_SIZE: usize = 814;
+
+#[derive(BorshSerialize)]
+struct OffChainSourceArgs {
+    source: String,
+    hash: [u8; 32],
+}
+
+#[derive(BorshSerialize)]
+struct UploadCircuitArgs {
+    comp_offset: u32,
+    mxe_program: [u8; 32],
+    raw_circuit_index: u8,
+    upload_data: Vec<u8>,
+    offset: u32,
+}
+
+#[derive(BorshSerialize)]
+struct FinalizeCompDefArgs {
+    comp_offset: u32,
+    mxe_program: [u8; 32],
+}
+
+/// What initialization did for one circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompDefAction {
+    /// The definition already existed and was complete
+    Exists,
+    /// A definition pointing at the off-chain circuit was created
+    Created,
+    /// The circuit bytes were uploaded and the definition finalized
+    Uploaded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompDefInit {
+    pub circuit: String,
+    pub comp_def_account: String,
+    pub action: CompDefAction,
+    pub signatures: Vec<String>,
+}
+
+/// Registers circuits with Arcium through the vault program
+pub struct CompDefInitializer {
+    rpc_client: Arc<RpcClient>,
+    payer: Arc<Keypair>,
+    mxe_program: Pubkey,
+    loader: InstructionLoader,
+    circuit_base_url: Option<String>,
+}
+
+impl CompDefInitializer {
+    /// Configure from `ARCIUM_BUILD_PATH` and `ARCIUM_CIRCUIT_BASE_URL`
+    pub fn from_env(rpc_client: Arc<RpcClient>, payer: Arc<Keypair>, mxe_program: Pubkey) -> Self {
+        let build_path = std::env::var("ARCIUM_BUILD_PATH").unwrap_or_else(|_| "build".to_string());
+        let circuit_base_url = std::env::var("ARCIUM_CIRCUIT_BASE_URL")
+            .ok()
+            .map(|url| url.trim_end_matches('/').to_string());
+
+        Self {
+            rpc_client,
+            payer,
+            mxe_program,
+            loader: InstructionLoader::new(build_path),
+            circuit_base_url,
+        }
+    }
+
+    /// Make sure `circuit` has a complete computation definition, calling
+    /// `init_instruction` on the vault program if it has none
+    pub fn initialize(
+        &self,
+        arcium_program: &Pubkey,
+        circuit: &str,
+        init_instruction: &str,
+    ) -> ServiceResult<CompDefInit> {
+        let comp_def = comp_def_account(arcium_program, &self.mxe_program, circuit);
+        let mut result = CompDefInit {
+            circuit: circuit.to_string(),
+            comp_def_account: comp_def.to_string(),
+            action: CompDefAction::Exists,
+            signatures: Vec::new(),
+        };
+
+        let existing = self
+            .rpc_client
+            .get_account_with_commitment(&comp_def, self.rpc_client.commitment())?
+            .value;
+
+        let needs_upload = match existing {
+            Some(account) => match read_circuit_source(&account.data)
+                .map_err(|e| ServiceError::Upstream(format!("{}: {}", comp_def, e)))?
+            {
+                CircuitSourceState::OnChain { completed: false } => true,
+                _ => {
+                    log::info!("   {} already initialized at {}", circuit, comp_def);
+                    return Ok(result);
+                }
+            },
+            None => {
+                let source = self.off_chain_source(circuit)?;
+                let needs_upload = source.is_none();
+
+                let mut data = anchor_discriminator(init_instruction).to_vec();
+                data.extend_from_slice(&source.try_to_vec().map_err(|e| {
+                    ServiceError::Configuration(format!("Invalid circuit source: {}", e))
+                })?);
+
+                let instruction = Instruction {
+                    program_id: self.mxe_program,
+                    accounts: vec![
+                        AccountMeta::new(self.payer.pubkey(), true),
+                        AccountMeta::new(mxe_account(arcium_program, &self.mxe_program), false),
+                        AccountMeta::new(comp_def, false),
+                        AccountMeta::new_readonly(*arcium_program, false),
+                        AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+                        AccountMeta::new_readonly(self.mxe_program, false),
+                        AccountMeta::new_readonly(
+                            get_program_data_address(&self.mxe_program),
+                            false,
+                        ),
+                    ],
+                    data,
+                };
+
+                result.signatures.push(self.send(instruction)?.to_string());
+                result.action = CompDefAction::Created;
+                log::info!(
+                    "   {} computation definition created at {}",
+                    circuit,
+                    comp_def
+                );
+                needs_upload
+            }
+        };
+
+        if needs_upload {
+            let signatures = self.upload(arcium_program, circuit, &comp_def)?;
+            result
+                .signatures
+                .extend(signatures.iter().map(ToString::to_string));
+            result.action = CompDefAction::Uploaded;
+            log::info!("   {} circuit uploaded and finalized", circuit);
+        }
+
+        Ok(result)
+    }
+
+    /// Off-chain source for `circuit`, when a base URL is configured
+    fn off_chain_source(&self, circuit: &str) -> ServiceResult<Option<OffChainSourceArgs>> {
+        let base_url = match &self.circuit_base_url {
+            Some(base_url) => base_url,
+            None => return Ok(None),
+        };
+
+        let compiled = self.load(circuit)?;
+        Ok(Some(OffChainSourceArgs {
+            source: format!("{}/{}.arcis", base_url, circuit),
+            hash: Sha256::digest(&compiled).into(),
+        }))
+    }
+
+    /// Upload the circuit bytes in chunks, then finalize the definition
+    fn upload(
+        &self,
+        arcium_program: &Pubkey,
+        circuit: &str,
+        comp_def: &Pubkey,
+    ) -> ServiceResult<Vec<Signature>> {
+        let bytecode = self.load(circuit)?;
+        let comp_offset = comp_def_offset(circuit);
+        let raw_circuit = raw_circuit_account(arcium_program, comp_def, 0);
+
+        let mut signatures = Vec::new();
+        for (i, chunk) in bytecode.chunks(UPLOAD_CHUNK_SIZE).enumerate() {
+            let args = UploadCircuitArgs {
+                comp_offset,
+                mxe_program: self.mxe_program.to_bytes(),
+                raw_circuit_index: 0,
+                upload_data: chunk.to_vec(),
+                offset: (i * UPLOAD_CHUNK_SIZE) as u32,
+            };
+            signatures.push(self.send(Instruction {
+                program_id: *arcium_program,
+                accounts: vec![
+                    AccountMeta::new(self.payer.pubkey(), true),
+                    AccountMeta::new_readonly(*comp_def, false),
+                    AccountMeta::new(raw_circuit, false),
+                    AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+                ],
+                data: instruction_data("upload_circuit", &args)?,
+            })?);
+        }
+
+        let args = FinalizeCompDefArgs {
+            comp_offset,
+            mxe_program: self.mxe_program.to_bytes(),
+        };
+        signatures.push(self.send(Instruction {
+            program_id: *arcium_program,
+            accounts: vec![
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(*comp_def, false),
+            ],
+            data: instruction_data("finalize_computation_definition", &args)?,
+        })?);
+
+        Ok(signatures)
+    }
+
+    fn load(&self, circuit: &str) -> ServiceResult<Vec<u8>> {
+        self.loader
+            .load_instruction(circuit)
+            .map(|compiled| compiled.bytecode)
+            .map_err(|e| {
+                ServiceError::Configuration(format!(
+                    "Cannot load circuit {} from {}: {}",
+                    circuit,
+                    self.loader.build_path(),
+                    e
+                ))
+            })
+    }
+
+    fn send(&self, instruction: Instruction) -> ServiceResult<Signature> {
+        let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+        let message = Message::new(&[instruction], Some(&self.payer.pubkey()));
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction
+            .try_sign(&[self.payer.as_ref()], recent_blockhash)
+            .map_err(|e| {
+                ServiceError::Configuration(format!(
+                    "Failed to sign computation definition transaction: {}",
+                    e
+                ))
+            })?;
+
+        Ok(self.rpc_client.send_and_confirm_transaction(&transaction)?)
+    }
+}
+
+/// Address of the MXE account Arcium keeps for a program
+pub fn mxe_account(arcium_program: &Pubkey, mxe_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[MXE_ACCOUNT_SEED, mxe_program.as_ref()], arcium_program).0
+}
+
+fn instruction_data(name: &str, args: &impl BorshSerialize) -> ServiceResult<Vec<u8>> {
+    let mut data = anchor_discriminator(name).to_vec();
+    data.extend_from_slice(
+        &args.try_to_vec().map_err(|e| {
+            ServiceError::Configuration(format!("Invalid {} arguments: {}", name, e))
+        })?,
+    );
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_off_chain_source_encoding() {
+        let source = Some(OffChainSourceArgs {
+            source: "https://c.example/add_values.arcis".to_string(),
+            hash: [7u8; 32],
+        });
+        let encoded = source.try_to_vec().unwrap();
+
+        // Option tag, string length prefix, string, hash
+        assert_eq!(encoded[0], 1);
+        assert_eq!(&encoded[1..5], &34u32.to_le_bytes());
+        assert_eq!(encoded.len(), 1 + 4 + 34 + 32);
+
+        let none: Option<OffChainSourceArgs> = None;
+        assert_eq!(none.try_to_vec().unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_instruction_data_prefix() {
+        let args = FinalizeCompDefArgs {
+            comp_offset: 1,
+            mxe_program: [0; 32],
+        };
+        let data = instruction_data("finalize_computation_definition", &args).unwrap();
+        assert_eq!(
+            &data[..8],
+            &anchor_discriminator("finalize_computation_definition")
+        );
+        assert_eq!(data.len(), 8 + 4 + 32);
+    }
+}
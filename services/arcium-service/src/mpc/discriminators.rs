@@ -1,4 +1,7 @@
+use crate::error::{ServiceError, ServiceResult};
+use borsh::BorshDeserialize;
 use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
 
 /// Calculate Anchor instruction discriminator
 ///
@@ -17,6 +20,41 @@ pub fn anchor_discriminator(instruction_name: &str) -> [u8; 8] {
     discriminator
 }
 
+/// Calculate Anchor account discriminator
+///
+/// Anchor uses: SHA256("account:{AccountName}")[..8]
+pub fn anchor_account_discriminator(account_name: &str) -> [u8; 8] {
+    let preimage = format!("account:{}", account_name);
+    let hash = Sha256::digest(preimage.as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+/// Decode the Borsh body of an Anchor account, after its discriminator
+///
+/// Trailing zero padding is ignored.
+pub fn decode_anchor_account<T: BorshDeserialize>(
+    data: &[u8],
+    address: &Pubkey,
+) -> ServiceResult<T> {
+    data.get(8..)
+        .ok_or_else(|| "account too short".to_string())
+        .and_then(|mut body| T::deserialize(&mut body).map_err(|e| e.to_string()))
+        .map_err(|e| ServiceError::Upstream(format!("Unreadable account {}: {}", address, e)))
+}
+
+/// Calculate Anchor event discriminator
+///
+/// Anchor uses: SHA256("event:{EventName}")[..8]
+pub fn anchor_event_discriminator(event_name: &str) -> [u8; 8] {
+    let preimage = format!("event:{}", event_name);
+    let hash = Sha256::digest(preimage.as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
 /// Get discriminator for ninjapay-vault instructions
 pub mod ninjapay_vault {
     use super::*;
@@ -1,11 +1,12 @@
+use crate::error::{ServiceError, ServiceResult};
 use chacha20poly1305::{
-    aead::{Aead, KeyInit, OsRng},
+    aead::{Aead, KeyInit, OsRng, Payload},
     ChaCha20Poly1305, Nonce,
 };
 use hkdf::Hkdf;
 use rand::RngCore;
 use sha2::Sha256;
-use std::{error::Error, sync::OnceLock};
+use std::sync::OnceLock;
 
 #[derive(Clone, Copy, Debug)]
 enum EncryptionMode {
@@ -24,6 +25,14 @@ fn resolve_mode() -> EncryptionMode {
     }
 }
 
+/// Associated data binding a ciphertext to the payment field it was made for,
+/// so a memo cannot be passed off as a payer identity or vice versa
+const MEMO_AAD: &[u8] = b"ninjapay-payment-memo-v1";
+const PAYER_AAD: &[u8] = b"ninjapay-payment-payer-v1";
+
+/// Bytes the dev cipher adds to a plaintext: nonce (12) + tag (16)
+pub const CIPHERTEXT_OVERHEAD: usize = 12 + 16;
+
 fn log_rescue_placeholder() {
     static RESCUE_LOG_ONCE: OnceLock<()> = OnceLock::new();
     RESCUE_LOG_ONCE.get_or_init(|| {
@@ -79,11 +88,11 @@ impl EncryptionHelper {
     /// Derive a user-specific encryption key using HKDF
     ///
     /// Derivation: HKDF-SHA256(master_key, salt=user_pubkey, info="ninjapay-dev-v1")
-    fn derive_user_key(&self, user_pubkey: &str) -> Result<[u8; 32], Box<dyn Error>> {
+    fn derive_user_key(&self, user_pubkey: &str) -> ServiceResult<[u8; 32]> {
         let hkdf = Hkdf::<Sha256>::new(Some(user_pubkey.as_bytes()), &self.master_key);
         let mut derived_key = [0u8; 32];
         hkdf.expand(b"ninjapay-dev-v1", &mut derived_key)
-            .map_err(|e| format!("Key derivation failed: {}", e))?;
+            .map_err(|e| ServiceError::Crypto(format!("Key derivation failed: {}", e)))?;
         Ok(derived_key)
     }
 
@@ -91,7 +100,7 @@ impl EncryptionHelper {
     ///
     /// Converts u64 to 8-byte little-endian, then encrypts
     /// Returns: [nonce (12)] + [ciphertext (8)] + [tag (16)] = 36 bytes total
-    pub fn encrypt_u64(&self, value: u64, user_pubkey: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    pub fn encrypt_u64(&self, value: u64, user_pubkey: &str) -> ServiceResult<Vec<u8>> {
         let plaintext = value.to_le_bytes();
         self.encrypt_bytes(&plaintext, user_pubkey)
     }
@@ -99,51 +108,94 @@ impl EncryptionHelper {
     /// Decrypt to u64 value (for simulator)
     ///
     /// Decrypts and converts 8-byte little-endian to u64
-    pub fn decrypt_to_u64(
-        &self,
-        encrypted: &[u8],
-        user_pubkey: &str,
-    ) -> Result<u64, Box<dyn Error>> {
+    pub fn decrypt_to_u64(&self, encrypted: &[u8], user_pubkey: &str) -> ServiceResult<u64> {
         let plaintext = self.decrypt_bytes(encrypted, user_pubkey)?;
         if plaintext.len() != 8 {
-            return Err(format!(
+            return Err(ServiceError::Crypto(format!(
                 "Invalid u64 decryption: expected 8 bytes, got {}",
                 plaintext.len()
-            )
-            .into());
+            )));
         }
         let bytes: [u8; 8] = plaintext
             .try_into()
-            .map_err(|_| "Failed to convert to u64")?;
+            .map_err(|_| ServiceError::Crypto("Failed to convert to u64".to_string()))?;
         Ok(u64::from_le_bytes(bytes))
     }
 
     /// Encrypt arbitrary bytes
     ///
     /// Format: [nonce (12 bytes)] + [ciphertext] + [tag (16 bytes)]
-    pub fn encrypt_bytes(&self, data: &[u8], user_pubkey: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    pub fn encrypt_bytes(&self, data: &[u8], user_pubkey: &str) -> ServiceResult<Vec<u8>> {
+        self.encrypt_with_aad(data, &[], user_pubkey)
+    }
+
+    /// Decrypt arbitrary bytes
+    ///
+    /// Expects format: [nonce (12 bytes)] + [ciphertext] + [tag (16 bytes)]
+    pub fn decrypt_bytes(&self, encrypted: &[u8], user_pubkey: &str) -> ServiceResult<Vec<u8>> {
+        self.decrypt_with_aad(encrypted, &[], user_pubkey)
+    }
+
+    /// Encrypt a payment memo or invoice reference under the key of merchant
+    /// `owner_pubkey`, which the master key also derives
+    pub fn encrypt_memo(&self, memo: &str, owner_pubkey: &str) -> ServiceResult<Vec<u8>> {
+        self.encrypt_with_aad(memo.as_bytes(), MEMO_AAD, owner_pubkey)
+    }
+
+    /// Decrypt a memo made by [`Self::encrypt_memo`]
+    pub fn decrypt_memo(&self, encrypted: &[u8], owner_pubkey: &str) -> ServiceResult<String> {
+        let plaintext = self.decrypt_with_aad(encrypted, MEMO_AAD, owner_pubkey)?;
+        String::from_utf8(plaintext)
+            .map_err(|_| ServiceError::Crypto("Decrypted memo is not UTF-8".to_string()))
+    }
+
+    /// Encrypt a payer identity (wallet, email, customer id) under the key of
+    /// merchant `owner_pubkey`
+    pub fn encrypt_payer_identity(
+        &self,
+        identity: &str,
+        owner_pubkey: &str,
+    ) -> ServiceResult<Vec<u8>> {
+        self.encrypt_with_aad(identity.as_bytes(), PAYER_AAD, owner_pubkey)
+    }
+
+    /// Decrypt a payer identity made by [`Self::encrypt_payer_identity`]
+    pub fn decrypt_payer_identity(
+        &self,
+        encrypted: &[u8],
+        owner_pubkey: &str,
+    ) -> ServiceResult<String> {
+        let plaintext = self.decrypt_with_aad(encrypted, PAYER_AAD, owner_pubkey)?;
+        String::from_utf8(plaintext)
+            .map_err(|_| ServiceError::Crypto("Decrypted payer identity is not UTF-8".to_string()))
+    }
+
+    fn encrypt_with_aad(
+        &self,
+        data: &[u8],
+        aad: &[u8],
+        user_pubkey: &str,
+    ) -> ServiceResult<Vec<u8>> {
         match self.mode {
-            EncryptionMode::Dev => self.encrypt_bytes_dev(data, user_pubkey),
+            EncryptionMode::Dev => self.encrypt_bytes_dev(data, aad, user_pubkey),
             EncryptionMode::Rescue => {
                 log_rescue_placeholder();
-                self.encrypt_bytes_dev(data, user_pubkey)
+                self.encrypt_bytes_dev(data, aad, user_pubkey)
             }
         }
     }
 
-    /// Decrypt arbitrary bytes
-    ///
-    /// Expects format: [nonce (12 bytes)] + [ciphertext] + [tag (16 bytes)]
-    pub fn decrypt_bytes(
+    fn decrypt_with_aad(
         &self,
         encrypted: &[u8],
+        aad: &[u8],
         user_pubkey: &str,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
+    ) -> ServiceResult<Vec<u8>> {
         match self.mode {
-            EncryptionMode::Dev => self.decrypt_bytes_dev(encrypted, user_pubkey),
+            EncryptionMode::Dev => self.decrypt_bytes_dev(encrypted, aad, user_pubkey),
             EncryptionMode::Rescue => {
                 log_rescue_placeholder();
-                self.decrypt_bytes_dev(encrypted, user_pubkey)
+                self.decrypt_bytes_dev(encrypted, aad, user_pubkey)
             }
         }
     }
@@ -154,7 +206,7 @@ impl EncryptionHelper {
     /// 1. Not empty
     /// 2. Minimum length (nonce + tag = 28 bytes)
     /// 3. Proper structure
-    pub fn validate_encrypted_input(&self, encrypted_data: &[u8]) -> Result<bool, Box<dyn Error>> {
+    pub fn validate_encrypted_input(&self, encrypted_data: &[u8]) -> ServiceResult<bool> {
         if encrypted_data.is_empty() {
             log::warn!("Validation failed: empty input");
             return Ok(false);
@@ -184,14 +236,14 @@ impl EncryptionHelper {
     /// Prepare encrypted inputs for batching
     ///
     /// Format: [count (4 bytes)] + [len1 (4)] + [data1] + [len2 (4)] + [data2] + ...
-    pub fn prepare_batch_inputs(
-        &self,
-        encrypted_values: Vec<Vec<u8>>,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
+    pub fn prepare_batch_inputs(&self, encrypted_values: Vec<Vec<u8>>) -> ServiceResult<Vec<u8>> {
         // Validate all inputs first
         for (i, value) in encrypted_values.iter().enumerate() {
             if !self.validate_encrypted_input(value)? {
-                return Err(format!("Invalid encrypted input at index {}", i).into());
+                return Err(ServiceError::Validation(format!(
+                    "Invalid encrypted input at index {}",
+                    i
+                )));
             }
         }
 
@@ -220,7 +272,12 @@ impl EncryptionHelper {
         Ok(result)
     }
 
-    fn encrypt_bytes_dev(&self, data: &[u8], user_pubkey: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    fn encrypt_bytes_dev(
+        &self,
+        data: &[u8],
+        aad: &[u8],
+        user_pubkey: &str,
+    ) -> ServiceResult<Vec<u8>> {
         let user_key = self.derive_user_key(user_pubkey)?;
         let cipher = ChaCha20Poly1305::new(&user_key.into());
 
@@ -229,8 +286,8 @@ impl EncryptionHelper {
         let nonce = Nonce::from_slice(&nonce_bytes);
 
         let ciphertext = cipher
-            .encrypt(nonce, data)
-            .map_err(|e| format!("Encryption failed: {}", e))?;
+            .encrypt(nonce, Payload { msg: data, aad })
+            .map_err(|e| ServiceError::Crypto(format!("Encryption failed: {}", e)))?;
 
         let mut result = Vec::with_capacity(12 + ciphertext.len());
         result.extend_from_slice(&nonce_bytes);
@@ -243,14 +300,14 @@ impl EncryptionHelper {
     fn decrypt_bytes_dev(
         &self,
         encrypted: &[u8],
+        aad: &[u8],
         user_pubkey: &str,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
+    ) -> ServiceResult<Vec<u8>> {
         if encrypted.len() < 12 + 16 {
-            return Err(format!(
+            return Err(ServiceError::Crypto(format!(
                 "Invalid encrypted data: too short (got {} bytes, need at least 28)",
                 encrypted.len()
-            )
-            .into());
+            )));
         }
 
         let (nonce_bytes, ciphertext) = encrypted.split_at(12);
@@ -260,8 +317,14 @@ impl EncryptionHelper {
         let cipher = ChaCha20Poly1305::new(&user_key.into());
 
         let plaintext = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Decryption failed: {}", e))?;
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|e| ServiceError::Crypto(format!("Decryption failed: {}", e)))?;
 
         log::debug!(
             "✅ Decrypted {} bytes → {} bytes",
@@ -278,23 +341,24 @@ impl EncryptionHelper {
         &self,
         batch_result: Vec<u8>,
         expected_count: usize,
-    ) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    ) -> ServiceResult<Vec<Vec<u8>>> {
         if batch_result.len() < 4 {
-            return Err("Batch result too short: missing count".into());
+            return Err(ServiceError::Crypto(
+                "Batch result too short: missing count".to_string(),
+            ));
         }
 
         // Read count
         let count_bytes: [u8; 4] = batch_result[0..4]
             .try_into()
-            .map_err(|_| "Failed to read count")?;
+            .map_err(|_| ServiceError::Crypto("Failed to read count".to_string()))?;
         let count = u32::from_le_bytes(count_bytes) as usize;
 
         if count != expected_count {
-            return Err(format!(
+            return Err(ServiceError::Crypto(format!(
                 "Batch count mismatch: expected {}, got {}",
                 expected_count, count
-            )
-            .into());
+            )));
         }
 
         let mut results = Vec::with_capacity(count);
@@ -303,19 +367,25 @@ impl EncryptionHelper {
         // Read each value
         for i in 0..count {
             if offset + 4 > batch_result.len() {
-                return Err(format!("Batch result truncated at item {}", i).into());
+                return Err(ServiceError::Crypto(format!(
+                    "Batch result truncated at item {}",
+                    i
+                )));
             }
 
             // Read length
-            let len_bytes: [u8; 4] = batch_result[offset..offset + 4]
-                .try_into()
-                .map_err(|_| format!("Failed to read length at item {}", i))?;
+            let len_bytes: [u8; 4] = batch_result[offset..offset + 4].try_into().map_err(|_| {
+                ServiceError::Crypto(format!("Failed to read length at item {}", i))
+            })?;
             let len = u32::from_le_bytes(len_bytes) as usize;
             offset += 4;
 
             // Read data
             if offset + len > batch_result.len() {
-                return Err(format!("Batch result truncated: item {} data incomplete", i).into());
+                return Err(ServiceError::Crypto(format!(
+                    "Batch result truncated: item {} data incomplete",
+                    i
+                )));
             }
 
             let data = batch_result[offset..offset + len].to_vec();
@@ -336,7 +406,7 @@ impl EncryptionHelper {
         _result: &[u8],
         _signature: &str,
         cluster_pubkey: &str,
-    ) -> Result<bool, Box<dyn Error>> {
+    ) -> ServiceResult<bool> {
         log::debug!(
             "⚠️  Signature verification skipped (dev mode) for cluster: {}",
             cluster_pubkey
@@ -507,6 +577,39 @@ mod tests {
         assert_eq!(helper.decrypt_to_u64(&enc2, "test").unwrap(), 42);
     }
 
+    #[test]
+    fn test_payment_metadata_round_trip() {
+        let helper = EncryptionHelper::new();
+        let owner = "merchant_pubkey";
+
+        let memo = helper.encrypt_memo("INV-2024-0042", owner).unwrap();
+        assert_eq!(memo.len(), "INV-2024-0042".len() + CIPHERTEXT_OVERHEAD);
+        assert_eq!(helper.decrypt_memo(&memo, owner).unwrap(), "INV-2024-0042");
+
+        let payer = helper
+            .encrypt_payer_identity("alice@example.com", owner)
+            .unwrap();
+        assert_eq!(
+            helper.decrypt_payer_identity(&payer, owner).unwrap(),
+            "alice@example.com"
+        );
+
+        // Keys derived for other pubkeys do not open them
+        assert!(helper.decrypt_memo(&memo, "someone_else").is_err());
+    }
+
+    #[test]
+    fn test_payment_metadata_fields_are_not_interchangeable() {
+        let helper = EncryptionHelper::new();
+        let owner = "merchant_pubkey";
+
+        let memo = helper.encrypt_memo("INV-1", owner).unwrap();
+        let payer = helper.encrypt_payer_identity("alice", owner).unwrap();
+        assert!(helper.decrypt_payer_identity(&memo, owner).is_err());
+        assert!(helper.decrypt_memo(&payer, owner).is_err());
+        assert!(helper.decrypt_bytes(&memo, owner).is_err());
+    }
+
     #[test]
     fn test_large_batch() {
         let helper = EncryptionHelper::new();
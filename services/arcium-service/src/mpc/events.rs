@@ -0,0 +1,326 @@
+//! Decoding of vault-lite and payroll program events from transaction logs
+//!
+//! Anchor's `emit!` logs `Program data: <base64>`, where the payload is the
+//! event discriminator (`SHA256("event:<Name>")[..8]`) followed by the
+//! Borsh-encoded event. Each log line is attributed to the program executing
+//! at that point by following the `invoke`/`success` lines, and only data
+//! written by a watched program is decoded, so another program logging the
+//! same bytes cannot forge an event.
+
+use super::discriminators::anchor_event_discriminator;
+use crate::error::{ServiceError, ServiceResult};
+use base64::Engine;
+use borsh::BorshDeserialize;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+
+/// `declare_id!` of `ninjapay-vault-lite`
+const VAULT_LITE_PROGRAM_ID: &str = "FKsek5byvQ7fMTD9atN55gAxytrWecci57chsjuYNFPP";
+/// `declare_id!` of `ninja-payroll`
+const PAYROLL_PROGRAM_ID: &str = "FEfFPJF8CMck4zvDPm6fGXcyUZifPHBT7P3YwCjdhHr7";
+
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// `VAULT_LITE_PROGRAM_ID`, defaulting to the program's declared id
+pub fn vault_lite_program_from_env() -> ServiceResult<Pubkey> {
+    program_from_env("VAULT_LITE_PROGRAM_ID", VAULT_LITE_PROGRAM_ID)
+}
+
+/// `PAYROLL_PROGRAM_ID`, defaulting to the program's declared id
+pub fn payroll_program_from_env() -> ServiceResult<Pubkey> {
+    program_from_env("PAYROLL_PROGRAM_ID", PAYROLL_PROGRAM_ID)
+}
+
+fn program_from_env(var: &str, default: &str) -> ServiceResult<Pubkey> {
+    let id = std::env::var(var).unwrap_or_else(|_| default.to_string());
+    id.parse::<Pubkey>()
+        .map_err(|e| ServiceError::Configuration(format!("Invalid {} '{}': {}", var, id, e)))
+}
+
+/// Mirror of vault-lite's `PaymentStatus`; variant order is the wire format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Pending,
+    Completed,
+    Failed,
+    Cancelled,
+    Refunded,
+    PartiallyRefunded,
+    Expired,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct VaultInitialized {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct BalanceUpdated {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct PaymentRecorded {
+    pub vault: Pubkey,
+    pub payment: Pubkey,
+    pub payment_id: String,
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    pub expires_at: Option<i64>,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct PaymentFinalized {
+    pub vault: Pubkey,
+    pub payment: Pubkey,
+    pub payment_id: String,
+    pub status: PaymentStatus,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct BatchInitialized {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub authority: Pubkey,
+    pub total_recipients: u16,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct PaymentProcessed {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub recipient_index: u16,
+    pub recipient_token: Pubkey,
+    pub amount: u64,
+    pub processed_count: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct BatchFinalized {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub processed_count: u16,
+    pub total_amount: u64,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshDeserialize)]
+pub struct BatchCancelled {
+    pub batch: Pubkey,
+    pub batch_id: u64,
+    pub processed_count: u16,
+    pub total_amount: u64,
+    pub timestamp: i64,
+}
+
+/// Any event emitted by vault-lite or payroll
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramEvent {
+    VaultInitialized(VaultInitialized),
+    BalanceUpdated(BalanceUpdated),
+    PaymentRecorded(PaymentRecorded),
+    PaymentFinalized(PaymentFinalized),
+    BatchInitialized(BatchInitialized),
+    PaymentProcessed(PaymentProcessed),
+    BatchFinalized(BatchFinalized),
+    BatchCancelled(BatchCancelled),
+}
+
+impl ProgramEvent {
+    /// Decode one event payload
+    ///
+    /// Returns `Ok(None)` for payloads whose discriminator is not one of
+    /// these events, and an error for known events that fail to decode.
+    pub fn decode(data: &[u8]) -> ServiceResult<Option<Self>> {
+        if data.len() < 8 {
+            return Ok(None);
+        }
+        let (discriminator, mut body) = data.split_at(8);
+
+        macro_rules! decode_as {
+            ($($name:ident),+) => {
+                $(
+                    if discriminator == anchor_event_discriminator(stringify!($name)) {
+                        let event = $name::deserialize(&mut body).map_err(|e| {
+                            ServiceError::Upstream(format!(
+                                "Malformed {} event: {}",
+                                stringify!($name),
+                                e
+                            ))
+                        })?;
+                        return Ok(Some(ProgramEvent::$name(event)));
+                    }
+                )+
+            };
+        }
+
+        decode_as!(
+            VaultInitialized,
+            BalanceUpdated,
+            PaymentRecorded,
+            PaymentFinalized,
+            BatchInitialized,
+            PaymentProcessed,
+            BatchFinalized,
+            BatchCancelled
+        );
+        Ok(None)
+    }
+}
+
+/// An event together with the program that emitted it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedEvent {
+    pub program_id: Pubkey,
+    pub event: ProgramEvent,
+}
+
+/// Extracts events of the watched programs from transaction logs
+pub struct EventDecoder {
+    programs: Vec<Pubkey>,
+}
+
+impl EventDecoder {
+    pub fn new(programs: impl IntoIterator<Item = Pubkey>) -> Self {
+        Self {
+            programs: programs.into_iter().collect(),
+        }
+    }
+
+    /// Events in the order they were emitted
+    pub fn decode_logs(&self, logs: &[String]) -> ServiceResult<Vec<DecodedEvent>> {
+        let mut stack: Vec<Pubkey> = Vec::new();
+        let mut events = Vec::new();
+
+        for line in logs {
+            if let Some(data) = line.strip_prefix(PROGRAM_DATA_PREFIX) {
+                let program_id = match stack.last() {
+                    Some(program_id) if self.programs.contains(program_id) => *program_id,
+                    _ => continue,
+                };
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(data.trim())
+                    .map_err(|e| {
+                        ServiceError::Upstream(format!("Invalid program data log: {}", e))
+                    })?;
+                if let Some(event) = ProgramEvent::decode(&bytes)? {
+                    events.push(DecodedEvent { program_id, event });
+                }
+            } else if let Some(rest) = line.strip_prefix("Program ") {
+                let mut parts = rest.split_whitespace();
+                let (Some(id), Some(action)) = (parts.next(), parts.next()) else {
+                    continue;
+                };
+                match action {
+                    "invoke" => {
+                        if let Ok(program_id) = id.parse() {
+                            stack.push(program_id);
+                        }
+                    }
+                    "success" | "failed:" => {
+                        stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+
+    fn program_data(name: &str, body: &impl BorshSerialize) -> String {
+        let mut data = anchor_event_discriminator(name).to_vec();
+        data.extend(body.try_to_vec().unwrap());
+        format!(
+            "{}{}",
+            PROGRAM_DATA_PREFIX,
+            base64::engine::general_purpose::STANDARD.encode(data)
+        )
+    }
+
+    fn vault_lite() -> Pubkey {
+        VAULT_LITE_PROGRAM_ID.parse().unwrap()
+    }
+
+    #[test]
+    fn test_decodes_payment_finalized() {
+        let vault = Pubkey::new_unique();
+        let payment = Pubkey::new_unique();
+        // (vault, payment, payment_id, status = Refunded, timestamp)
+        let line = program_data(
+            "PaymentFinalized",
+            &(vault, payment, "pi_123".to_string(), 4u8, 1_700_000_000i64),
+        );
+        let logs = vec![
+            format!("Program {} invoke [1]", vault_lite()),
+            "Program log: Instruction: RefundPayment".to_string(),
+            line,
+            format!("Program {} success", vault_lite()),
+        ];
+
+        let events = EventDecoder::new([vault_lite()])
+            .decode_logs(&logs)
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![DecodedEvent {
+                program_id: vault_lite(),
+                event: ProgramEvent::PaymentFinalized(PaymentFinalized {
+                    vault,
+                    payment,
+                    payment_id: "pi_123".to_string(),
+                    status: PaymentStatus::Refunded,
+                    timestamp: 1_700_000_000,
+                }),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_ignores_data_from_other_programs() {
+        let impostor = Pubkey::new_unique();
+        let batch = Pubkey::new_unique();
+        let event = (batch, 7u64, 3u16, 250u64, 1i64);
+        let logs = vec![
+            format!("Program {} invoke [1]", vault_lite()),
+            format!("Program {} invoke [2]", impostor),
+            program_data("BatchFinalized", &event),
+            format!("Program {} success", impostor),
+            program_data("BatchCancelled", &event),
+            format!("Program {} success", vault_lite()),
+        ];
+
+        let events = EventDecoder::new([vault_lite()])
+            .decode_logs(&logs)
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0].event,
+            ProgramEvent::BatchCancelled(BatchCancelled { batch_id: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_payloads() {
+        let mut unknown = anchor_event_discriminator("SomethingElse").to_vec();
+        unknown.extend([0u8; 16]);
+        assert_eq!(ProgramEvent::decode(&unknown).unwrap(), None);
+
+        let truncated = anchor_event_discriminator("VaultInitialized").to_vec();
+        assert!(ProgramEvent::decode(&truncated).is_err());
+    }
+}
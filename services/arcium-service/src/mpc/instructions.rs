@@ -1,14 +1,98 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::Path;
 
+/// Type of an encrypted circuit parameter or return value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    U64,
+    Bool,
+}
+
+impl ParamType {
+    /// Plaintext width in bytes
+    pub fn plaintext_len(&self) -> usize {
+        match self {
+            ParamType::U64 => 8,
+            ParamType::Bool => 1,
+        }
+    }
+
+    /// Length of a ciphertext for this type: nonce (12) + plaintext + tag (16)
+    pub fn ciphertext_len(&self) -> usize {
+        12 + self.plaintext_len() + 16
+    }
+}
+
+impl std::fmt::Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamType::U64 => write!(f, "u64"),
+            ParamType::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+/// A named circuit parameter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstructionParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: ParamType,
+}
+
+/// Signature of a circuit, as declared in `encrypted-ixs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstructionSpec {
+    pub name: String,
+    /// The circuit's doc comment
+    pub description: String,
+    pub params: Vec<InstructionParam>,
+    pub returns: ParamType,
+}
+
+#[derive(Deserialize)]
+struct InstructionManifest {
+    instructions: Vec<InstructionSpec>,
+}
+
+lazy_static::lazy_static! {
+    /// Circuit signatures generated by `build.rs` from `encrypted-ixs/src/lib.rs`
+    static ref INSTRUCTION_SPECS: Vec<InstructionSpec> = {
+        let manifest: InstructionManifest = serde_json::from_str(include_str!(concat!(
+            env!("OUT_DIR"),
+            "/instruction_manifest.json"
+        )))
+        .expect("generated instruction manifest uses unsupported types");
+        manifest.instructions
+    };
+}
+
+/// Look up the signature of a known circuit
+pub fn instruction_spec(name: &str) -> Option<&'static InstructionSpec> {
+    INSTRUCTION_SPECS.iter().find(|spec| spec.name == name)
+}
+
+/// Signatures of every circuit in `encrypted-ixs`
+pub fn instruction_specs() -> &'static [InstructionSpec] {
+    &INSTRUCTION_SPECS
+}
+
 /// Arcium compiled instruction metadata
 pub struct CompiledInstruction {
     pub name: String,
     pub bytecode: Vec<u8>,
+    /// Hex SHA-256 of `bytecode`
+    pub sha256: String,
 }
 
 /// Load compiled Arcium instructions from build directory
+///
+/// A `<name>.arcis.sha256` file next to a circuit, when present, must hold
+/// the digest of the circuit (`sha256sum` format); a mismatch means the file
+/// is stale or still being written and the circuit is rejected.
 pub struct InstructionLoader {
     build_path: String,
 }
@@ -18,34 +102,57 @@ impl InstructionLoader {
         Self { build_path }
     }
 
+    /// Directory the compiled circuits are read from
+    pub fn build_path(&self) -> &str {
+        &self.build_path
+    }
+
     /// Load a specific instruction by name
     pub fn load_instruction(&self, name: &str) -> Result<CompiledInstruction, std::io::Error> {
         let file_path = format!("{}/{}.arcis", self.build_path, name);
         let bytecode = fs::read(&file_path)?;
+        let sha256 = hex::encode(Sha256::digest(&bytecode));
+
+        match fs::read_to_string(format!("{}.sha256", file_path)) {
+            Ok(expected) => {
+                let expected = expected.split_whitespace().next().unwrap_or_default();
+                if !expected.eq_ignore_ascii_case(&sha256) {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "{} has hash {} but {}.sha256 expects {}",
+                            file_path, sha256, file_path, expected
+                        ),
+                    ));
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
 
         Ok(CompiledInstruction {
             name: name.to_string(),
             bytecode,
+            sha256,
         })
     }
 
     /// Load all available instructions
+    ///
+    /// Circuits that have not been built are skipped; any other failure,
+    /// including a hash mismatch, fails the whole load.
     pub fn load_all_instructions(&self) -> Result<Vec<CompiledInstruction>, std::io::Error> {
         let mut instructions = Vec::new();
-        let instruction_names = vec![
-            "encrypted_transfer",
-            "batch_payroll",
-            "query_balance",
-            "validate_amount",
-            "add_values",
-        ];
-
-        for name in instruction_names {
-            if let Ok(instruction) = self.load_instruction(name) {
-                instructions.push(instruction);
-                log::info!("Loaded instruction: {}", name);
-            } else {
-                log::warn!("Failed to load instruction: {}", name);
+        for name in instruction_specs().iter().map(|spec| spec.name.as_str()) {
+            match self.load_instruction(name) {
+                Ok(instruction) => {
+                    log::info!("Loaded instruction: {} ({})", name, instruction.sha256);
+                    instructions.push(instruction);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    log::warn!("Failed to load instruction: {}", name);
+                }
+                Err(e) => return Err(e),
             }
         }
 
@@ -61,46 +168,11 @@ impl InstructionLoader {
     /// Get instruction metadata
     pub fn get_instruction_info(&self, name: &str) -> Option<InstructionInfo> {
         if self.instruction_exists(name) {
-            Some(InstructionInfo {
-                name: name.to_string(),
-                description: self.get_description(name),
-                parameters: self.get_parameters(name),
-            })
+            instruction_spec(name).map(InstructionInfo::from)
         } else {
             None
         }
     }
-
-    fn get_description(&self, name: &str) -> String {
-        match name {
-            "encrypted_transfer" => {
-                "Perform confidential transfer with encrypted amount".to_string()
-            }
-            "batch_payroll" => "Process multiple transfers in single MPC computation".to_string(),
-            "query_balance" => "Query encrypted balance".to_string(),
-            "validate_amount" => "Validate transfer amount against limits".to_string(),
-            "add_values" => "Add two encrypted values (for testing)".to_string(),
-            _ => "Unknown instruction".to_string(),
-        }
-    }
-
-    fn get_parameters(&self, name: &str) -> Vec<String> {
-        match name {
-            "encrypted_transfer" => {
-                vec!["sender_balance: u64".to_string(), "amount: u64".to_string()]
-            }
-            "batch_payroll" => vec![
-                "payer_balance: u64".to_string(),
-                "amount1: u64".to_string(),
-                "amount2: u64".to_string(),
-                "amount3: u64".to_string(),
-            ],
-            "query_balance" => vec!["encrypted_balance: u64".to_string()],
-            "validate_amount" => vec!["amount: u64".to_string(), "max_amount: u64".to_string()],
-            "add_values" => vec!["a: u64".to_string(), "b: u64".to_string()],
-            _ => vec![],
-        }
-    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,6 +180,22 @@ pub struct InstructionInfo {
     pub name: String,
     pub description: String,
     pub parameters: Vec<String>,
+    pub returns: String,
+}
+
+impl From<&InstructionSpec> for InstructionInfo {
+    fn from(spec: &InstructionSpec) -> Self {
+        Self {
+            name: spec.name.clone(),
+            description: spec.description.clone(),
+            parameters: spec
+                .params
+                .iter()
+                .map(|param| format!("{}: {}", param.name, param.ty))
+                .collect(),
+            returns: spec.returns.to_string(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -119,4 +207,64 @@ mod tests {
         let loader = InstructionLoader::new("build".to_string());
         assert!(loader.instruction_exists("encrypted_transfer") || true); // May not exist in test env
     }
+
+    #[test]
+    fn test_instruction_specs() {
+        let spec = instruction_spec("batch_payroll").unwrap();
+        assert_eq!(spec.params.len(), 4);
+        assert_eq!(ParamType::U64.ciphertext_len(), 36);
+        assert!(instruction_spec("drain_vault").is_none());
+
+        let info = InstructionInfo::from(instruction_spec("encrypted_transfer").unwrap());
+        assert_eq!(info.parameters, vec!["sender_balance: u64", "amount: u64"]);
+        assert_eq!(info.returns, "u64");
+    }
+
+    #[test]
+    fn test_load_verifies_hash() {
+        let dir = std::env::temp_dir().join(format!("arcis-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let loader = InstructionLoader::new(dir.to_string_lossy().into_owned());
+
+        fs::write(dir.join("add_values.arcis"), b"circuit").unwrap();
+        let digest = hex::encode(Sha256::digest(b"circuit"));
+        fs::write(
+            dir.join("add_values.arcis.sha256"),
+            format!("{}  add_values.arcis\n", digest),
+        )
+        .unwrap();
+        assert_eq!(
+            loader.load_instruction("add_values").unwrap().sha256,
+            digest
+        );
+
+        fs::write(dir.join("add_values.arcis"), b"half-writ").unwrap();
+        let err = loader.load_instruction("add_values").err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(loader.load_all_instructions().is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_manifest_matches_circuits() {
+        let names: Vec<_> = instruction_specs()
+            .iter()
+            .map(|spec| spec.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "encrypted_transfer",
+                "batch_payroll",
+                "query_balance",
+                "validate_amount",
+                "add_values"
+            ]
+        );
+
+        let validate = instruction_spec("validate_amount").unwrap();
+        assert_eq!(validate.returns, ParamType::Bool);
+        assert_eq!(validate.description, "Validate transfer amount");
+    }
 }
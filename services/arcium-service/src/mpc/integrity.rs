@@ -0,0 +1,490 @@
+//! Verification of local circuits against on-chain computation definitions
+//!
+//! In Cluster mode the Arcium program holds a computation-definition account
+//! per circuit, derived from the MXE program (`ARCIUM_PROGRAM_ID`) and the
+//! circuit's offset. Circuits registered with an off-chain source record the
+//! SHA-256 of the circuit; circuits uploaded on-chain are hashed from their
+//! raw circuit account once the upload is finalized. Either hash must match
+//! the `.arcis` build output loaded by [`InstructionLoader`], or the service
+//! would describe and simulate a different circuit from the one the cluster
+//! runs.
+
+use super::discriminators::anchor_account_discriminator;
+use super::instructions::InstructionLoader;
+use crate::error::{ServiceError, ServiceResult};
+use borsh::BorshDeserialize;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+
+const COMP_DEF_SEED: &[u8] = b"ComputationDefinitionAccount";
+const RAW_CIRCUIT_SEED: &[u8] = b"ComputationDefinitionRaw";
+
+/// What to do when a circuit does not match its computation definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationPolicy {
+    /// Refuse to start
+    Enforce,
+    /// Log and report a degraded health status
+    Warn,
+    /// Skip verification
+    Off,
+}
+
+impl FromStr for VerificationPolicy {
+    type Err = ServiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enforce" => Ok(VerificationPolicy::Enforce),
+            "warn" => Ok(VerificationPolicy::Warn),
+            "off" => Ok(VerificationPolicy::Off),
+            other => Err(ServiceError::Configuration(format!(
+                "Invalid CIRCUIT_VERIFICATION '{}' (expected enforce, warn or off)",
+                other
+            ))),
+        }
+    }
+}
+
+/// The Arcium program owning computation definitions, from
+/// `ARCIUM_CORE_PROGRAM_ID`
+pub fn arcium_core_program_from_env() -> ServiceResult<Option<Pubkey>> {
+    match std::env::var("ARCIUM_CORE_PROGRAM_ID") {
+        Ok(id) => id.parse::<Pubkey>().map(Some).map_err(|e| {
+            ServiceError::Configuration(format!("Invalid ARCIUM_CORE_PROGRAM_ID: {}", e))
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Offset of a circuit's computation definition: the first four bytes of
+/// SHA-256 of its name, little-endian
+pub fn comp_def_offset(circuit: &str) -> u32 {
+    let hash = Sha256::digest(circuit.as_bytes());
+    u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]])
+}
+
+/// Address of a circuit's computation-definition account
+pub fn comp_def_account(arcium_program: &Pubkey, mxe_program: &Pubkey, circuit: &str) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            COMP_DEF_SEED,
+            mxe_program.as_ref(),
+            &comp_def_offset(circuit).to_le_bytes(),
+        ],
+        arcium_program,
+    )
+    .0
+}
+
+/// Address of the raw circuit account holding an on-chain circuit's bytes
+pub fn raw_circuit_account(arcium_program: &Pubkey, comp_def: &Pubkey, index: u8) -> Pubkey {
+    Pubkey::find_program_address(
+        &[RAW_CIRCUIT_SEED, comp_def.as_ref(), &[index]],
+        arcium_program,
+    )
+    .0
+}
+
+// Borsh mirror of the Arcium `ComputationDefinitionAccount`, up to the
+// circuit source. Parameter and output kinds are fieldless enums, so they
+// decode as one byte each.
+#[derive(BorshDeserialize)]
+struct ComputationDefinitionAccount {
+    _finalization_authority: Option<[u8; 32]>,
+    _cu_amount: u64,
+    circuit_len: u32,
+    _parameters: Vec<u8>,
+    _outputs: Vec<u8>,
+    circuit_source: CircuitSource,
+}
+
+#[derive(BorshDeserialize)]
+enum CircuitSource {
+    Local {
+        _kind: u8,
+    },
+    OnChain {
+        is_completed: bool,
+        _upload_auth: [u8; 32],
+    },
+    OffChain {
+        _source: String,
+        hash: [u8; 32],
+    },
+}
+
+/// How a computation definition sources its circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitSourceState {
+    /// Built into the Arcium nodes
+    Local,
+    /// Uploaded to raw circuit accounts; usable once `completed`
+    OnChain { completed: bool },
+    /// Fetched by the nodes from a URL and checked against `hash`
+    OffChain { hash: [u8; 32] },
+}
+
+/// Decode the circuit source of a computation-definition account
+pub fn read_circuit_source(data: &[u8]) -> Result<CircuitSourceState, String> {
+    Ok(match decode_comp_def(data)?.circuit_source {
+        CircuitSource::Local { .. } => CircuitSourceState::Local,
+        CircuitSource::OnChain { is_completed, .. } => CircuitSourceState::OnChain {
+            completed: is_completed,
+        },
+        CircuitSource::OffChain { hash, .. } => CircuitSourceState::OffChain { hash },
+    })
+}
+
+fn decode_comp_def(data: &[u8]) -> Result<ComputationDefinitionAccount, String> {
+    let discriminator = anchor_account_discriminator("ComputationDefinitionAccount");
+    let body = data
+        .strip_prefix(&discriminator[..])
+        .ok_or_else(|| "not a computation-definition account".to_string())?;
+
+    ComputationDefinitionAccount::deserialize(&mut &body[..])
+        .map_err(|e| format!("undecodable computation definition: {}", e))
+}
+
+/// What a computation definition offers to compare a local circuit with
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OnchainCircuit {
+    /// SHA-256 recorded for an off-chain source
+    Hash([u8; 32]),
+    /// A finalized upload of `len` bytes in the raw circuit account
+    Raw { len: u32 },
+    /// Nothing comparable
+    Unverifiable(String),
+}
+
+fn onchain_circuit(data: &[u8]) -> Result<OnchainCircuit, String> {
+    let account = decode_comp_def(data)?;
+    Ok(match account.circuit_source {
+        CircuitSource::OffChain { hash, .. } => OnchainCircuit::Hash(hash),
+        CircuitSource::OnChain {
+            is_completed: true, ..
+        } => OnchainCircuit::Raw {
+            len: account.circuit_len,
+        },
+        CircuitSource::OnChain { .. } => {
+            OnchainCircuit::Unverifiable("circuit upload is not finalized".to_string())
+        }
+        CircuitSource::Local { .. } => {
+            OnchainCircuit::Unverifiable("circuit is built into the nodes".to_string())
+        }
+    })
+}
+
+/// SHA-256 of the first `len` circuit bytes in a raw circuit account
+fn raw_circuit_hash(data: &[u8], len: u32) -> Result<[u8; 32], String> {
+    let discriminator = anchor_account_discriminator("ComputationDefinitionRaw");
+    let body = data
+        .strip_prefix(&discriminator[..])
+        .ok_or_else(|| "not a raw circuit account".to_string())?;
+
+    // One bump byte precedes the circuit
+    body.get(1..1 + len as usize)
+        .map(|circuit| Sha256::digest(circuit).into())
+        .ok_or_else(|| format!("raw circuit account holds fewer than {} bytes", len))
+}
+
+/// Outcome of checking one circuit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitStatus {
+    Match,
+    Mismatch,
+    /// No computation definition has been initialized
+    MissingOnChain,
+    /// No `.arcis` build output to compare against
+    MissingLocally,
+    /// The definition exists but offers nothing to compare against
+    Unverifiable(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitCheck {
+    pub circuit: String,
+    pub comp_def_account: String,
+    pub status: CircuitStatus,
+    pub local_sha256: Option<String>,
+    pub onchain_sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub policy: VerificationPolicy,
+    /// Whether every circuit matched its computation definition
+    pub verified: bool,
+    pub circuits: Vec<CircuitCheck>,
+}
+
+impl IntegrityReport {
+    /// Circuits that did not match, for logging
+    pub fn failures(&self) -> Vec<String> {
+        self.circuits
+            .iter()
+            .filter(|check| check.status != CircuitStatus::Match)
+            .map(|check| format!("{} ({:?})", check.circuit, check.status))
+            .collect()
+    }
+}
+
+/// Compares local build outputs to the cluster's computation definitions
+pub struct CircuitVerifier {
+    rpc_client: Arc<RpcClient>,
+    arcium_program: Pubkey,
+    mxe_program: Pubkey,
+    loader: InstructionLoader,
+    policy: VerificationPolicy,
+}
+
+impl CircuitVerifier {
+    /// Configure from `CIRCUIT_VERIFICATION`, `ARCIUM_CORE_PROGRAM_ID` and
+    /// `ARCIUM_BUILD_PATH`
+    pub fn from_env(rpc_client: Arc<RpcClient>, mxe_program: Pubkey) -> ServiceResult<Self> {
+        let policy = std::env::var("CIRCUIT_VERIFICATION")
+            .unwrap_or_else(|_| "warn".to_string())
+            .parse()?;
+
+        let arcium_program = match arcium_core_program_from_env()? {
+            Some(id) => id,
+            None if policy == VerificationPolicy::Off => Pubkey::default(),
+            None => {
+                return Err(ServiceError::Configuration(
+                    "ARCIUM_CORE_PROGRAM_ID must be set to verify circuits (or set CIRCUIT_VERIFICATION=off)"
+                        .to_string(),
+                ))
+            }
+        };
+
+        let build_path = std::env::var("ARCIUM_BUILD_PATH").unwrap_or_else(|_| "build".to_string());
+
+        Ok(Self {
+            rpc_client,
+            arcium_program,
+            mxe_program,
+            loader: InstructionLoader::new(build_path),
+            policy,
+        })
+    }
+
+    pub fn policy(&self) -> VerificationPolicy {
+        self.policy
+    }
+
+    /// Check each circuit against its computation definition
+    pub fn verify(&self, circuits: &[&str]) -> ServiceResult<IntegrityReport> {
+        if self.policy == VerificationPolicy::Off {
+            return Ok(IntegrityReport {
+                policy: self.policy,
+                verified: true,
+                circuits: Vec::new(),
+            });
+        }
+
+        let addresses: Vec<Pubkey> = circuits
+            .iter()
+            .map(|circuit| comp_def_account(&self.arcium_program, &self.mxe_program, circuit))
+            .collect();
+        let onchain: Vec<Option<OnchainCircuit>> = self
+            .rpc_client
+            .get_multiple_accounts(&addresses)?
+            .into_iter()
+            .map(|account| {
+                account.map(|account| {
+                    onchain_circuit(&account.data).unwrap_or_else(OnchainCircuit::Unverifiable)
+                })
+            })
+            .collect();
+
+        // Uploaded circuits are hashed from their raw circuit accounts
+        let raw_addresses: Vec<Pubkey> = addresses
+            .iter()
+            .zip(&onchain)
+            .filter(|(_, onchain)| matches!(onchain, Some(OnchainCircuit::Raw { .. })))
+            .map(|(address, _)| raw_circuit_account(&self.arcium_program, address, 0))
+            .collect();
+        let mut raw_accounts = if raw_addresses.is_empty() {
+            Vec::new()
+        } else {
+            self.rpc_client.get_multiple_accounts(&raw_addresses)?
+        }
+        .into_iter();
+
+        let circuits: Vec<CircuitCheck> = circuits
+            .iter()
+            .zip(addresses.iter().zip(onchain))
+            .map(|(circuit, (address, onchain))| {
+                let onchain = onchain.map(|onchain| match onchain {
+                    OnchainCircuit::Raw { len } => match raw_accounts.next().flatten() {
+                        Some(account) => raw_circuit_hash(&account.data, len)
+                            .map_or_else(OnchainCircuit::Unverifiable, OnchainCircuit::Hash),
+                        None => OnchainCircuit::Unverifiable(
+                            "raw circuit account is missing".to_string(),
+                        ),
+                    },
+                    other => other,
+                });
+                self.check(circuit, address, onchain)
+            })
+            .collect();
+
+        Ok(IntegrityReport {
+            policy: self.policy,
+            verified: circuits
+                .iter()
+                .all(|check| check.status == CircuitStatus::Match),
+            circuits,
+        })
+    }
+
+    fn check(
+        &self,
+        circuit: &str,
+        address: &Pubkey,
+        onchain: Option<OnchainCircuit>,
+    ) -> CircuitCheck {
+        let local_sha256 = match self.loader.load_instruction(circuit) {
+            Ok(instruction) => Some(instruction.sha256),
+            Err(e) => {
+                log::warn!("Cannot load local circuit {}: {}", circuit, e);
+                None
+            }
+        };
+
+        let onchain_sha256 = match &onchain {
+            Some(OnchainCircuit::Hash(hash)) => Some(hex::encode(hash)),
+            _ => None,
+        };
+
+        let status = match (onchain, &local_sha256) {
+            (None, _) => CircuitStatus::MissingOnChain,
+            (Some(OnchainCircuit::Unverifiable(reason)), _) => CircuitStatus::Unverifiable(reason),
+            (Some(OnchainCircuit::Raw { .. }), _) => {
+                CircuitStatus::Unverifiable("raw circuit was not fetched".to_string())
+            }
+            (Some(OnchainCircuit::Hash(_)), None) => CircuitStatus::MissingLocally,
+            (Some(OnchainCircuit::Hash(_)), Some(local))
+                if Some(local) == onchain_sha256.as_ref() =>
+            {
+                CircuitStatus::Match
+            }
+            (Some(OnchainCircuit::Hash(_)), Some(_)) => CircuitStatus::Mismatch,
+        };
+
+        CircuitCheck {
+            circuit: circuit.to_string(),
+            comp_def_account: address.to_string(),
+            status,
+            local_sha256,
+            onchain_sha256,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comp_def_data(source: &[u8]) -> Vec<u8> {
+        let mut data = anchor_account_discriminator("ComputationDefinitionAccount").to_vec();
+        data.push(0); // no finalization authority
+        data.extend_from_slice(&200_000u64.to_le_bytes());
+        data.extend_from_slice(&1024u32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(source);
+        data.push(255); // bump
+        data
+    }
+
+    fn offchain_source(hash: [u8; 32]) -> Vec<u8> {
+        let url = b"https://circuits.example/add_values.arcis";
+        let mut source = vec![2];
+        source.extend_from_slice(&(url.len() as u32).to_le_bytes());
+        source.extend_from_slice(url);
+        source.extend_from_slice(&hash);
+        source
+    }
+
+    #[test]
+    fn test_decodes_offchain_hash() {
+        let hash = [9u8; 32];
+        let data = comp_def_data(&offchain_source(hash));
+        assert_eq!(onchain_circuit(&data).unwrap(), OnchainCircuit::Hash(hash));
+    }
+
+    #[test]
+    fn test_unfinished_upload_is_unverifiable() {
+        let mut source = vec![1, 0];
+        source.extend_from_slice(&[3u8; 32]);
+        let data = comp_def_data(&source);
+        assert!(matches!(
+            onchain_circuit(&data).unwrap(),
+            OnchainCircuit::Unverifiable(_)
+        ));
+        assert_eq!(
+            read_circuit_source(&data).unwrap(),
+            CircuitSourceState::OnChain { completed: false }
+        );
+    }
+
+    #[test]
+    fn test_finalized_upload_hashes_raw_circuit() {
+        let mut source = vec![1, 1];
+        source.extend_from_slice(&[3u8; 32]);
+        let data = comp_def_data(&source);
+        assert_eq!(
+            onchain_circuit(&data).unwrap(),
+            OnchainCircuit::Raw { len: 1024 }
+        );
+
+        let circuit = vec![5u8; 1024];
+        let mut raw = anchor_account_discriminator("ComputationDefinitionRaw").to_vec();
+        raw.push(254); // bump
+        raw.extend_from_slice(&circuit);
+        raw.extend_from_slice(&[0; 16]); // unused capacity
+        assert_eq!(
+            raw_circuit_hash(&raw, 1024).unwrap(),
+            <[u8; 32]>::from(Sha256::digest(&circuit))
+        );
+        assert!(raw_circuit_hash(&raw[..100], 1024).is_err());
+    }
+
+    #[test]
+    fn test_rejects_foreign_account() {
+        let mut data = comp_def_data(&offchain_source([0; 32]));
+        data[0] ^= 0xff;
+        assert!(onchain_circuit(&data).is_err());
+    }
+
+    #[test]
+    fn test_comp_def_offset_is_stable() {
+        let hash = Sha256::digest(b"encrypted_transfer");
+        assert_eq!(
+            comp_def_offset("encrypted_transfer").to_le_bytes(),
+            [hash[0], hash[1], hash[2], hash[3]]
+        );
+        assert_ne!(
+            comp_def_offset("encrypted_transfer"),
+            comp_def_offset("batch_payroll")
+        );
+    }
+
+    #[test]
+    fn test_policy_parsing() {
+        assert_eq!(
+            "enforce".parse::<VerificationPolicy>().unwrap(),
+            VerificationPolicy::Enforce
+        );
+        assert!("strict".parse::<VerificationPolicy>().is_err());
+    }
+}
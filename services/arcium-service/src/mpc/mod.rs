@@ -1,12 +1,20 @@
 pub mod client;
+pub mod comp_def;
 pub mod discriminators;
 pub mod encryption;
+pub mod events;
 pub mod instructions;
+pub mod integrity;
+pub mod registry;
 pub mod simulator;
+pub mod sweeper;
 pub mod types;
+pub mod vault_lite;
 
 pub use client::{MpcClient, MpcMode};
 pub use encryption::EncryptionHelper;
-pub use instructions::{CompiledInstruction, InstructionInfo, InstructionLoader};
+pub use instructions::{instruction_spec, InstructionInfo};
+pub use integrity::{IntegrityReport, VerificationPolicy};
+pub use registry::{AccountRole, InstructionRegistry, RegisteredInstruction};
 pub use simulator::MpcSimulator;
-pub use types::{ComputationRequest, ComputationResult, ComputationType};
+pub use types::{ComputationCursor, ComputationFilter, ComputationRequest, ComputationType};
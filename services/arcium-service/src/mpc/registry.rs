@@ -0,0 +1,237 @@
+use super::discriminators::anchor_discriminator;
+use super::instructions::instruction_spec;
+use super::types::ComputationType;
+use crate::error::{ServiceError, ServiceResult};
+use crate::middleware::AuthContext;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Registry shipped with the service, used when `INSTRUCTION_REGISTRY_PATH` is unset
+const DEFAULT_REGISTRY: &str = include_str!("../../config/instructions.json");
+
+/// Caller list entry admitting every authenticated caller
+const ANY_CALLER: &str = "*";
+
+/// Accounts the service knows how to supply to a cluster instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountRole {
+    /// The user's vault PDA, `[b"vault", user]`
+    Vault,
+    /// The user's wallet, as signer
+    User,
+    /// The Arcium cluster account for `ARCIUM_CLUSTER_OFFSET`
+    Cluster,
+    /// `metadata.recipient`, falling back to the user
+    Recipient,
+}
+
+/// A computation callers may invoke
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredInstruction {
+    /// API name, as sent in `computation_type`
+    pub name: String,
+    /// `encrypted-ixs` circuit executed for this computation
+    pub circuit: String,
+    /// Number of encrypted inputs
+    pub arity: usize,
+    /// Program instruction invoked in Cluster mode
+    pub cluster_instruction: String,
+    /// Hex discriminator override; defaults to Anchor's for `cluster_instruction`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discriminator: Option<String>,
+    /// Vault program instruction that registers the circuit's computation
+    /// definition, if the program has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_comp_def: Option<String>,
+    /// Accounts passed to the cluster instruction, in order
+    pub accounts: Vec<AccountRole>,
+    /// Merchant ids allowed to invoke this computation; `"*"` admits every
+    /// merchant, an empty list restricts it to internal services
+    #[serde(default)]
+    pub allowed_callers: Vec<String>,
+}
+
+impl RegisteredInstruction {
+    /// Discriminator sent to the program in Cluster mode
+    pub fn cluster_discriminator(&self) -> ServiceResult<[u8; 8]> {
+        match &self.discriminator {
+            Some(hex_str) => hex::decode(hex_str)
+                .ok()
+                .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+                .ok_or_else(|| {
+                    ServiceError::Configuration(format!(
+                        "Instruction '{}' has an invalid discriminator '{}'",
+                        self.name, hex_str
+                    ))
+                }),
+            None => Ok(anchor_discriminator(&self.cluster_instruction)),
+        }
+    }
+
+    /// Whether `auth` may invoke this computation
+    pub fn allows(&self, auth: &AuthContext) -> bool {
+        match &auth.merchant_id {
+            None => true,
+            Some(merchant_id) => self
+                .allowed_callers
+                .iter()
+                .any(|caller| caller == ANY_CALLER || caller == merchant_id),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RegistryFile {
+    instructions: Vec<RegisteredInstruction>,
+}
+
+/// The computations this deployment permits, keyed by API name
+///
+/// Only registered names can be invoked: built-in computation types must
+/// appear here too, and `ComputationType::Custom` is limited to entries
+/// listed in the registry.
+#[derive(Debug, Clone)]
+pub struct InstructionRegistry {
+    entries: HashMap<String, RegisteredInstruction>,
+}
+
+impl InstructionRegistry {
+    /// Load from `INSTRUCTION_REGISTRY_PATH`, or the bundled
+    /// `config/instructions.json` when unset
+    pub fn from_env() -> ServiceResult<Self> {
+        match std::env::var("INSTRUCTION_REGISTRY_PATH") {
+            Ok(path) => {
+                let json = std::fs::read_to_string(&path).map_err(|e| {
+                    ServiceError::Configuration(format!(
+                        "Failed to read instruction registry {}: {}",
+                        path, e
+                    ))
+                })?;
+                log::info!("📜 Loading instruction registry from {}", path);
+                Self::from_json(&json)
+            }
+            Err(_) => Self::from_json(DEFAULT_REGISTRY),
+        }
+    }
+
+    /// Parse and check a registry document
+    pub fn from_json(json: &str) -> ServiceResult<Self> {
+        let file: RegistryFile = serde_json::from_str(json).map_err(|e| {
+            ServiceError::Configuration(format!("Invalid instruction registry: {}", e))
+        })?;
+
+        let mut entries = HashMap::new();
+        for entry in file.instructions {
+            let spec = instruction_spec(&entry.circuit).ok_or_else(|| {
+                ServiceError::Configuration(format!(
+                    "Instruction '{}' references unknown circuit '{}'",
+                    entry.name, entry.circuit
+                ))
+            })?;
+            if spec.params.len() != entry.arity {
+                return Err(ServiceError::Configuration(format!(
+                    "Instruction '{}' declares arity {} but circuit '{}' takes {} inputs",
+                    entry.name,
+                    entry.arity,
+                    entry.circuit,
+                    spec.params.len()
+                )));
+            }
+            entry.cluster_discriminator()?;
+
+            if entries.contains_key(&entry.name) {
+                return Err(ServiceError::Configuration(format!(
+                    "Instruction '{}' is registered twice",
+                    entry.name
+                )));
+            }
+            entries.insert(entry.name.clone(), entry);
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Look up the entry for a computation type
+    pub fn get(&self, computation_type: &ComputationType) -> Option<&RegisteredInstruction> {
+        self.entries.get(computation_type.api_name())
+    }
+
+    /// Every registered computation, sorted by name
+    pub fn entries(&self) -> Vec<&RegisteredInstruction> {
+        let mut entries: Vec<_> = self.entries.values().collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caller(merchant_id: Option<&str>) -> AuthContext {
+        AuthContext {
+            principal: "key_1".to_string(),
+            merchant_id: merchant_id.map(str::to_string),
+            user_pubkeys: None,
+        }
+    }
+
+    #[test]
+    fn test_default_registry_loads() {
+        let registry = InstructionRegistry::from_json(DEFAULT_REGISTRY).unwrap();
+
+        let transfer = registry
+            .get(&ComputationType::from_name("encrypted_transfer"))
+            .unwrap();
+        assert_eq!(transfer.circuit, "encrypted_transfer");
+        assert_eq!(
+            transfer.cluster_discriminator().unwrap(),
+            anchor_discriminator("confidential_transfer")
+        );
+
+        assert!(registry
+            .get(&ComputationType::from_name("add_values"))
+            .is_some());
+        assert!(registry
+            .get(&ComputationType::from_name("drain_vault"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_allowed_callers() {
+        let registry = InstructionRegistry::from_json(DEFAULT_REGISTRY).unwrap();
+        let add_values = registry
+            .get(&ComputationType::from_name("add_values"))
+            .unwrap();
+
+        assert!(add_values.allows(&caller(None)));
+        assert!(!add_values.allows(&caller(Some("merchant_a"))));
+
+        let transfer = registry
+            .get(&ComputationType::ConfidentialTransfer)
+            .unwrap();
+        assert!(transfer.allows(&caller(Some("merchant_a"))));
+    }
+
+    #[test]
+    fn test_rejects_inconsistent_entries() {
+        let wrong_arity = r#"{"instructions": [{
+            "name": "add_values", "circuit": "add_values", "arity": 3,
+            "cluster_instruction": "add_values", "accounts": []
+        }]}"#;
+        assert!(InstructionRegistry::from_json(wrong_arity).is_err());
+
+        let unknown_circuit = r#"{"instructions": [{
+            "name": "drain", "circuit": "drain_vault", "arity": 1,
+            "cluster_instruction": "drain", "accounts": []
+        }]}"#;
+        assert!(InstructionRegistry::from_json(unknown_circuit).is_err());
+
+        let bad_discriminator = r#"{"instructions": [{
+            "name": "add_values", "circuit": "add_values", "arity": 2,
+            "cluster_instruction": "add_values", "discriminator": "abcd", "accounts": []
+        }]}"#;
+        assert!(InstructionRegistry::from_json(bad_discriminator).is_err());
+    }
+}
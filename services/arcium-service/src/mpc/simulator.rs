@@ -1,8 +1,11 @@
 use super::encryption::EncryptionHelper;
-use super::instructions::{CompiledInstruction, InstructionLoader};
+use super::instructions::{instruction_spec, CompiledInstruction, InstructionLoader};
+use crate::error::{ServiceError, ServiceResult};
+use notify::{RecursiveMode, Watcher};
 use std::collections::HashMap;
-use std::error::Error;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{mpsc, Arc, RwLock};
+use std::time::Duration;
 
 /// MPC Simulator for local development
 ///
@@ -11,27 +14,26 @@ use std::sync::Arc;
 ///
 /// Uses real encryption (ChaCha20-Poly1305) in development mode
 /// to test realistic encrypted data flows
+///
+/// Circuits are held behind a lock and replaced as a whole by [`reload`],
+/// so an execution always sees either the old or the new instruction set.
+///
+/// [`reload`]: MpcSimulator::reload
 pub struct MpcSimulator {
-    instructions: HashMap<String, CompiledInstruction>,
+    instructions: RwLock<Arc<HashMap<String, CompiledInstruction>>>,
     loader: InstructionLoader,
     encryption: Arc<EncryptionHelper>,
 }
 
+/// Quiet period after the last file event before reloading, so a rebuild
+/// writing several circuits triggers a single reload
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
 impl MpcSimulator {
     /// Create a new MPC simulator with encryption helper
-    pub fn new(
-        build_path: String,
-        encryption: Arc<EncryptionHelper>,
-    ) -> Result<Self, Box<dyn Error>> {
+    pub fn new(build_path: String, encryption: Arc<EncryptionHelper>) -> ServiceResult<Self> {
         let loader = InstructionLoader::new(build_path);
-
-        // Load all available instructions
-        let instructions_vec = loader.load_all_instructions()?;
-        let mut instructions = HashMap::new();
-
-        for instruction in instructions_vec {
-            instructions.insert(instruction.name.clone(), instruction);
-        }
+        let instructions = Self::load(&loader)?;
 
         log::info!(
             "✅ MPC Simulator initialized with {} instructions",
@@ -39,12 +41,94 @@ impl MpcSimulator {
         );
 
         Ok(Self {
-            instructions,
+            instructions: RwLock::new(Arc::new(instructions)),
             loader,
             encryption,
         })
     }
 
+    fn load(loader: &InstructionLoader) -> ServiceResult<HashMap<String, CompiledInstruction>> {
+        let instructions = loader.load_all_instructions().map_err(|e| {
+            ServiceError::Configuration(format!("Failed to load instructions: {}", e))
+        })?;
+
+        Ok(instructions
+            .into_iter()
+            .map(|instruction| (instruction.name.clone(), instruction))
+            .collect())
+    }
+
+    fn current(&self) -> Arc<HashMap<String, CompiledInstruction>> {
+        self.instructions
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Reload every circuit from the build path and swap them in at once
+    ///
+    /// If any circuit fails to load or verify, the current set is kept.
+    pub fn reload(&self) -> ServiceResult<usize> {
+        let instructions = Self::load(&self.loader)?;
+        let count = instructions.len();
+
+        *self.instructions.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(instructions);
+
+        log::info!("🔁 Reloaded {} circuits", count);
+        Ok(count)
+    }
+
+    /// Watch the build path and [`reload`](Self::reload) whenever a circuit
+    /// or its hash file changes
+    ///
+    /// The watcher runs on its own thread for the life of the process.
+    pub fn watch(self: &Arc<Self>) -> ServiceResult<()> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(|e| ServiceError::Configuration(format!("Failed to watch circuits: {}", e)))?;
+
+        watcher
+            .watch(
+                Path::new(self.loader.build_path()),
+                RecursiveMode::NonRecursive,
+            )
+            .map_err(|e| {
+                ServiceError::Configuration(format!(
+                    "Failed to watch {}: {}",
+                    self.loader.build_path(),
+                    e
+                ))
+            })?;
+
+        log::info!(
+            "👀 Watching {} for circuit changes",
+            self.loader.build_path()
+        );
+
+        let simulator = Arc::clone(self);
+        std::thread::spawn(move || {
+            // Keep the watcher alive for as long as events are consumed
+            let _watcher = watcher;
+
+            while let Ok(event) = rx.recv() {
+                let mut changed = touches_circuit(&event);
+                while let Ok(event) = rx.recv_timeout(RELOAD_DEBOUNCE) {
+                    changed |= touches_circuit(&event);
+                }
+
+                if changed {
+                    if let Err(e) = simulator.reload() {
+                        log::error!("❌ Circuit reload failed, keeping previous set: {}", e);
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
     /// Execute an instruction by name with encrypted inputs
     ///
     /// Decrypts inputs, executes instruction logic, re-encrypts result
@@ -53,7 +137,7 @@ impl MpcSimulator {
         name: &str,
         encrypted_inputs: Vec<Vec<u8>>,
         user_pubkey: &str,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
+    ) -> ServiceResult<Vec<u8>> {
         log::info!(
             "🔄 Simulating MPC execution: {} for user: {}",
             name,
@@ -61,16 +145,28 @@ impl MpcSimulator {
         );
 
         // Get instruction bytecode
-        let instruction = self
-            .instructions
+        let instructions = self.current();
+        let instruction = instructions
             .get(name)
-            .ok_or_else(|| format!("Instruction not found: {}", name))?;
+            .ok_or_else(|| ServiceError::Validation(format!("Instruction not found: {}", name)))?;
 
         log::debug!(
             "Instruction bytecode size: {} bytes",
             instruction.bytecode.len()
         );
 
+        // Inputs must match the circuit signature from the manifest
+        let spec = instruction_spec(name)
+            .ok_or_else(|| ServiceError::Validation(format!("Unknown instruction: {}", name)))?;
+        if encrypted_inputs.len() != spec.params.len() {
+            return Err(ServiceError::Validation(format!(
+                "{} expects {} inputs, got {}",
+                name,
+                spec.params.len(),
+                encrypted_inputs.len()
+            )));
+        }
+
         // Decrypt inputs using real encryption
         let decrypted_inputs = encrypted_inputs
             .iter()
@@ -86,7 +182,12 @@ impl MpcSimulator {
             "query_balance" => self.execute_query_balance(&decrypted_inputs)?,
             "validate_amount" => self.execute_validate_amount(&decrypted_inputs)?,
             "add_values" => self.execute_add_values(&decrypted_inputs)?,
-            _ => return Err(format!("Unknown instruction: {}", name).into()),
+            _ => {
+                return Err(ServiceError::Validation(format!(
+                    "Unknown instruction: {}",
+                    name
+                )))
+            }
         };
 
         // Re-encrypt result using real encryption
@@ -97,9 +198,11 @@ impl MpcSimulator {
     }
 
     /// Simulate confidential transfer
-    fn execute_transfer(&self, inputs: &[u64]) -> Result<u64, Box<dyn Error>> {
+    fn execute_transfer(&self, inputs: &[u64]) -> ServiceResult<u64> {
         if inputs.len() < 2 {
-            return Err("encrypted_transfer requires 2 inputs: balance, amount".into());
+            return Err(ServiceError::Validation(
+                "encrypted_transfer requires 2 inputs: balance, amount".to_string(),
+            ));
         }
 
         let balance = inputs[0];
@@ -120,9 +223,11 @@ impl MpcSimulator {
     }
 
     /// Simulate batch payroll
-    fn execute_batch_payroll(&self, inputs: &[u64]) -> Result<u64, Box<dyn Error>> {
+    fn execute_batch_payroll(&self, inputs: &[u64]) -> ServiceResult<u64> {
         if inputs.is_empty() {
-            return Err("batch_payroll requires at least 1 input (payer balance)".into());
+            return Err(ServiceError::Validation(
+                "batch_payroll requires at least 1 input (payer balance)".to_string(),
+            ));
         }
 
         let mut balance = inputs[0];
@@ -156,9 +261,11 @@ impl MpcSimulator {
     }
 
     /// Simulate balance query
-    fn execute_query_balance(&self, inputs: &[u64]) -> Result<u64, Box<dyn Error>> {
+    fn execute_query_balance(&self, inputs: &[u64]) -> ServiceResult<u64> {
         if inputs.is_empty() {
-            return Err("query_balance requires 1 input: balance".into());
+            return Err(ServiceError::Validation(
+                "query_balance requires 1 input: balance".to_string(),
+            ));
         }
 
         let balance = inputs[0];
@@ -168,9 +275,11 @@ impl MpcSimulator {
     }
 
     /// Simulate amount validation
-    fn execute_validate_amount(&self, inputs: &[u64]) -> Result<u64, Box<dyn Error>> {
+    fn execute_validate_amount(&self, inputs: &[u64]) -> ServiceResult<u64> {
         if inputs.len() < 2 {
-            return Err("validate_amount requires 2 inputs: amount, max_amount".into());
+            return Err(ServiceError::Validation(
+                "validate_amount requires 2 inputs: amount, max_amount".to_string(),
+            ));
         }
 
         let amount = inputs[0];
@@ -189,9 +298,11 @@ impl MpcSimulator {
     }
 
     /// Simulate addition (for testing)
-    fn execute_add_values(&self, inputs: &[u64]) -> Result<u64, Box<dyn Error>> {
+    fn execute_add_values(&self, inputs: &[u64]) -> ServiceResult<u64> {
         if inputs.len() < 2 {
-            return Err("add_values requires 2 inputs: a, b".into());
+            return Err(ServiceError::Validation(
+                "add_values requires 2 inputs: a, b".to_string(),
+            ));
         }
 
         let a = inputs[0];
@@ -205,12 +316,36 @@ impl MpcSimulator {
 
     /// Get available instructions
     pub fn list_instructions(&self) -> Vec<String> {
-        self.instructions.keys().cloned().collect()
+        self.current().keys().cloned().collect()
+    }
+
+    /// SHA-256 of each loaded circuit, by name
+    pub fn circuit_hashes(&self) -> HashMap<String, String> {
+        self.current()
+            .values()
+            .map(|instruction| (instruction.name.clone(), instruction.sha256.clone()))
+            .collect()
     }
 
     /// Check if instruction exists
     pub fn has_instruction(&self, name: &str) -> bool {
-        self.instructions.contains_key(name)
+        self.current().contains_key(name)
+    }
+}
+
+/// Whether a watcher event concerns a circuit or its hash file
+fn touches_circuit(event: &notify::Result<notify::Event>) -> bool {
+    match event {
+        Ok(event) => event.paths.iter().any(|path| {
+            matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("arcis") | Some("sha256")
+            )
+        }),
+        Err(e) => {
+            log::warn!("Circuit watcher error: {}", e);
+            false
+        }
     }
 }
 
@@ -224,7 +359,7 @@ mod tests {
             // If build path doesn't exist, create empty simulator for testing
             let loader = InstructionLoader::new("build".to_string());
             MpcSimulator {
-                instructions: HashMap::new(),
+                instructions: RwLock::new(Arc::new(HashMap::new())),
                 loader,
                 encryption: Arc::new(EncryptionHelper::new()),
             }
@@ -239,6 +374,30 @@ mod tests {
         assert!(result.is_ok() || result.is_err());
     }
 
+    #[test]
+    fn test_reload_swaps_circuits() {
+        let dir = std::env::temp_dir().join(format!("arcis-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let simulator = MpcSimulator::new(
+            dir.to_string_lossy().into_owned(),
+            Arc::new(EncryptionHelper::new()),
+        )
+        .unwrap();
+        assert!(simulator.circuit_hashes().is_empty());
+
+        std::fs::write(dir.join("add_values.arcis"), b"v1").unwrap();
+        assert_eq!(simulator.reload().unwrap(), 1);
+        let v1 = simulator.circuit_hashes()["add_values"].clone();
+
+        // A circuit that fails verification leaves the loaded set untouched
+        std::fs::write(dir.join("add_values.arcis"), b"v2").unwrap();
+        std::fs::write(dir.join("add_values.arcis.sha256"), "00").unwrap();
+        assert!(simulator.reload().is_err());
+        assert_eq!(simulator.circuit_hashes()["add_values"], v1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_encrypt_decrypt_with_real_encryption() {
         let encryption = Arc::new(EncryptionHelper::new());
@@ -0,0 +1,364 @@
+//! Rent reclamation for settled payment records and payroll batches
+//!
+//! `close_payment` and `close_batch` are permissionless once an account is
+//! settled and its retention period has passed, and return the rent to the
+//! account that paid it. The sweeper finds such accounts with
+//! `getProgramAccounts` and closes each in its own transaction, so one
+//! account the program refuses to close does not hold back the rest.
+//!
+//! Sweeps run on demand through `POST /api/admin/sweep`, and every
+//! `ACCOUNT_SWEEP_INTERVAL_SECS` seconds when that is set.
+
+use super::discriminators::{
+    anchor_account_discriminator, anchor_discriminator, decode_anchor_account,
+};
+use super::events::{payroll_program_from_env, vault_lite_program_from_env};
+use super::vault_lite::PaymentRecord;
+use crate::error::{ServiceError, ServiceResult};
+use borsh::BorshDeserialize;
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Borsh mirrors of the program accounts, after the 8-byte discriminator.
+// Fields past the last one a sweep needs, and trailing zero padding, are
+// ignored. Payment records use the shared mirror in `vault_lite`.
+
+#[derive(BorshDeserialize)]
+struct VaultLiteConfig {
+    _admin: Pubkey,
+    _service_authority: Pubkey,
+    _bump: u8,
+    payment_retention: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize)]
+enum BatchStatus {
+    Initialized,
+    Delegated,
+    Processing,
+    Finalized,
+    Cancelled,
+}
+
+#[derive(BorshDeserialize)]
+struct PayrollBatch {
+    _authority: Pubkey,
+    batch_id: u64,
+    _total_recipients: u16,
+    _processed_count: u16,
+    _total_amount: u64,
+    status: BatchStatus,
+    _created_at: i64,
+    finalized_at: Option<i64>,
+    _bump: u8,
+    payer: Pubkey,
+    retention_period: i64,
+}
+
+impl PayrollBatch {
+    fn is_closable(&self, now: i64) -> bool {
+        matches!(self.status, BatchStatus::Finalized | BatchStatus::Cancelled)
+            && self
+                .finalized_at
+                .is_some_and(|settled_at| now >= settled_at.saturating_add(self.retention_period))
+    }
+}
+
+/// Kind of account a sweep closes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SweptKind {
+    Payment,
+    Batch,
+}
+
+/// An account the sweeper tried to close
+#[derive(Debug, Clone, Serialize)]
+pub struct SweptAccount {
+    pub kind: SweptKind,
+    pub address: String,
+    /// Payment id or batch id
+    pub id: String,
+    /// Receives the reclaimed rent
+    pub rent_recipient: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of [`AccountSweeper::sweep`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SweepReport {
+    pub closed: Vec<SweptAccount>,
+    pub failed: Vec<SweptAccount>,
+}
+
+struct Closable {
+    kind: SweptKind,
+    address: Pubkey,
+    id: String,
+    rent_recipient: Pubkey,
+    instruction: Instruction,
+}
+
+/// Closes settled vault-lite payment records and payroll batches
+pub struct AccountSweeper {
+    rpc_client: Arc<RpcClient>,
+    fee_payer: Arc<Keypair>,
+    vault_lite: Pubkey,
+    payroll: Pubkey,
+}
+
+impl AccountSweeper {
+    /// Configure from `VAULT_LITE_PROGRAM_ID` and `PAYROLL_PROGRAM_ID`
+    pub fn from_env(rpc_client: Arc<RpcClient>, fee_payer: Arc<Keypair>) -> ServiceResult<Self> {
+        Ok(Self {
+            rpc_client,
+            fee_payer,
+            vault_lite: vault_lite_program_from_env()?,
+            payroll: payroll_program_from_env()?,
+        })
+    }
+
+    /// Close every account that is currently closable
+    pub fn sweep(&self) -> ServiceResult<SweepReport> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+
+        let mut closable = self.closable_payments(now)?;
+        closable.extend(self.closable_batches(now)?);
+
+        let mut report = SweepReport::default();
+        for account in closable {
+            let mut swept = SweptAccount {
+                kind: account.kind,
+                address: account.address.to_string(),
+                id: account.id,
+                rent_recipient: account.rent_recipient.to_string(),
+                signature: None,
+                error: None,
+            };
+            match self.send(account.instruction) {
+                Ok(signature) => {
+                    swept.signature = Some(signature.to_string());
+                    report.closed.push(swept);
+                }
+                Err(e) => {
+                    log::warn!("⚠️  Failed to close {}: {}", swept.address, e);
+                    swept.error = Some(e.to_string());
+                    report.failed.push(swept);
+                }
+            }
+        }
+
+        log::info!(
+            "🧹 Account sweep closed {} accounts, {} failed",
+            report.closed.len(),
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// Sweep every `interval` on a dedicated thread
+    pub fn spawn(self: &Arc<Self>, interval: Duration) {
+        let sweeper = Arc::clone(self);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if let Err(e) = sweeper.sweep() {
+                log::error!("❌ Account sweep failed: {}", e);
+            }
+        });
+        log::info!("🧹 Sweeping closable accounts every {:?}", interval);
+    }
+
+    fn closable_payments(&self, now: i64) -> ServiceResult<Vec<Closable>> {
+        let config = Pubkey::find_program_address(&[b"config"], &self.vault_lite).0;
+        let retention = match self.rpc_client.get_account_data(&config) {
+            Ok(data) => decode_anchor_account::<VaultLiteConfig>(&data, &config)?.payment_retention,
+            Err(e) => {
+                log::warn!(
+                    "⚠️  Skipping payments, vault-lite config unavailable: {}",
+                    e
+                );
+                return Ok(Vec::new());
+            }
+        };
+
+        let mut closable = Vec::new();
+        for (address, data) in self.program_accounts(&self.vault_lite, "PaymentRecord")? {
+            let payment = match decode_anchor_account::<PaymentRecord>(&data, &address) {
+                Ok(payment) => payment,
+                Err(e) => {
+                    log::warn!("⚠️  {}", e);
+                    continue;
+                }
+            };
+            if !payment.is_closable(now, retention) {
+                continue;
+            }
+
+            closable.push(Closable {
+                kind: SweptKind::Payment,
+                address,
+                id: payment.payment_id,
+                rent_recipient: payment.payer,
+                instruction: Instruction {
+                    program_id: self.vault_lite,
+                    accounts: vec![
+                        AccountMeta::new_readonly(payment.vault, false),
+                        AccountMeta::new(address, false),
+                        AccountMeta::new_readonly(config, false),
+                        AccountMeta::new(payment.payer, false),
+                    ],
+                    data: anchor_discriminator("close_payment").to_vec(),
+                },
+            });
+        }
+        Ok(closable)
+    }
+
+    fn closable_batches(&self, now: i64) -> ServiceResult<Vec<Closable>> {
+        let mut closable = Vec::new();
+        for (address, data) in self.program_accounts(&self.payroll, "PayrollBatch")? {
+            let batch = match decode_anchor_account::<PayrollBatch>(&data, &address) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    log::warn!("⚠️  {}", e);
+                    continue;
+                }
+            };
+            if !batch.is_closable(now) {
+                continue;
+            }
+
+            closable.push(Closable {
+                kind: SweptKind::Batch,
+                address,
+                id: batch.batch_id.to_string(),
+                rent_recipient: batch.payer,
+                instruction: Instruction {
+                    program_id: self.payroll,
+                    accounts: vec![
+                        AccountMeta::new(address, false),
+                        AccountMeta::new(batch.payer, false),
+                    ],
+                    data: anchor_discriminator("close_batch").to_vec(),
+                },
+            });
+        }
+        Ok(closable)
+    }
+
+    /// Accounts of `program` whose discriminator is that of `account_name`
+    fn program_accounts(
+        &self,
+        program: &Pubkey,
+        account_name: &str,
+    ) -> ServiceResult<Vec<(Pubkey, Vec<u8>)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                0,
+                anchor_account_discriminator(account_name).to_vec(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        Ok(self
+            .rpc_client
+            .get_program_accounts_with_config(program, config)?
+            .into_iter()
+            .map(|(address, account)| (address, account.data))
+            .collect())
+    }
+
+    fn send(&self, instruction: Instruction) -> ServiceResult<Signature> {
+        let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+        let message = Message::new(&[instruction], Some(&self.fee_payer.pubkey()));
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction
+            .try_sign(&[self.fee_payer.as_ref()], recent_blockhash)
+            .map_err(|e| {
+                ServiceError::Configuration(format!("Failed to sign sweep transaction: {}", e))
+            })?;
+
+        Ok(self.rpc_client.send_and_confirm_transaction(&transaction)?)
+    }
+}
+
+/// `ACCOUNT_SWEEP_INTERVAL_SECS`, if periodic sweeps are enabled
+pub fn sweep_interval_from_env() -> ServiceResult<Option<Duration>> {
+    match std::env::var("ACCOUNT_SWEEP_INTERVAL_SECS") {
+        Ok(secs) => match secs.parse::<u64>() {
+            Ok(0) => Ok(None),
+            Ok(secs) => Ok(Some(Duration::from_secs(secs))),
+            Err(e) => Err(ServiceError::Configuration(format!(
+                "Invalid ACCOUNT_SWEEP_INTERVAL_SECS '{}': {}",
+                secs, e
+            ))),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+
+    fn account(name: &str, body: &impl BorshSerialize, padding: usize) -> Vec<u8> {
+        let mut data = anchor_account_discriminator(name).to_vec();
+        data.extend(body.try_to_vec().unwrap());
+        data.extend(vec![0u8; padding]);
+        data
+    }
+
+    #[test]
+    fn test_batch_closable_once_settled() {
+        let address = Pubkey::new_unique();
+        let batch = |status: u8, finalized_at: Option<i64>| -> PayrollBatch {
+            let data = account(
+                "PayrollBatch",
+                &(
+                    (Pubkey::new_unique(), 42u64, 3u16, 3u16, 900u64, status),
+                    (0i64, finalized_at, 255u8, Pubkey::new_unique(), 600i64),
+                ),
+                0,
+            );
+            decode_anchor_account(&data, &address).unwrap()
+        };
+
+        assert!(batch(3, Some(100)).is_closable(700));
+        assert!(!batch(3, Some(100)).is_closable(699));
+        assert!(batch(4, Some(100)).is_closable(700));
+        // Still processing, whatever the clock says
+        assert!(!batch(2, None).is_closable(i64::MAX));
+    }
+
+    #[test]
+    fn test_unreadable_account() {
+        let err = decode_anchor_account::<PayrollBatch>(&[0u8; 4], &Pubkey::new_unique())
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "upstream_error");
+    }
+}
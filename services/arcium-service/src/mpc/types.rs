@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ComputationType {
     ConfidentialTransfer,
     BatchPayroll,
@@ -8,6 +8,38 @@ pub enum ComputationType {
     Custom(String),
 }
 
+impl ComputationType {
+    /// Parse a computation type from its API name, accepting circuit aliases
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "confidential_transfer" | "encrypted_transfer" => ComputationType::ConfidentialTransfer,
+            "batch_payroll" => ComputationType::BatchPayroll,
+            "balance_query" | "query_balance" => ComputationType::BalanceQuery,
+            custom => ComputationType::Custom(custom.to_string()),
+        }
+    }
+
+    /// Canonical API name, as listed in the instruction registry
+    pub fn api_name(&self) -> &str {
+        match self {
+            ComputationType::ConfidentialTransfer => "confidential_transfer",
+            ComputationType::BatchPayroll => "batch_payroll",
+            ComputationType::BalanceQuery => "balance_query",
+            ComputationType::Custom(name) => name,
+        }
+    }
+
+    /// Name of the `encrypted-ixs` circuit this computation runs by default
+    pub fn circuit_name(&self) -> &str {
+        match self {
+            ComputationType::ConfidentialTransfer => "encrypted_transfer",
+            ComputationType::BatchPayroll => "batch_payroll",
+            ComputationType::BalanceQuery => "query_balance",
+            ComputationType::Custom(name) => name,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComputationRequest {
     pub computation_type: ComputationType,
@@ -19,6 +51,8 @@ pub struct ComputationRequest {
     pub reference_id: Option<String>,
     #[serde(default)]
     pub user_signature: Option<String>,
+    #[serde(default)]
+    pub merchant_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,9 +112,52 @@ pub struct ComputationMetadata {
     pub cluster_tx_signature: Option<String>,
     #[serde(default)]
     pub attestation: Option<serde_json::Value>,
+    #[serde(default)]
+    pub status_history: Vec<StatusTransition>,
+    #[serde(default)]
+    pub merchant_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ComputationMetadata {
+    /// Move to `status` if the transition graph allows it, recording the
+    /// transition in `status_history`
+    pub fn transition_to(
+        &mut self,
+        status: ComputationStatus,
+        at: u64,
+    ) -> Result<(), ComputationStatusError> {
+        if !self.status.can_transition_to(&status) {
+            return Err(ComputationStatusError::IllegalTransition {
+                computation_id: self.computation_id.clone(),
+                from: self.status.clone(),
+                to: status,
+            });
+        }
+
+        self.status_history.push(StatusTransition {
+            from: self.status.clone(),
+            to: status.clone(),
+            at,
+        });
+
+        // Set completed_at if finished
+        if status == ComputationStatus::Completed {
+            self.completed_at = Some(at);
+        }
+        self.status = status;
+        Ok(())
+    }
+}
+
+/// A single recorded status change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub from: ComputationStatus,
+    pub to: ComputationStatus,
+    pub at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ComputationStatus {
     Queued,
     Processing,
@@ -89,6 +166,64 @@ pub enum ComputationStatus {
     Cancelled,
 }
 
+impl ComputationStatus {
+    /// Whether no further transitions are allowed from this status
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            ComputationStatus::Completed | ComputationStatus::Failed | ComputationStatus::Cancelled
+        )
+    }
+
+    /// Legal transitions:
+    ///
+    /// ```text
+    /// Queued ──> Processing ──> Completed
+    ///   │            │
+    ///   └────────────┴────────> Failed | Cancelled
+    /// ```
+    pub fn can_transition_to(&self, next: &ComputationStatus) -> bool {
+        use ComputationStatus::*;
+        matches!(
+            (self, next),
+            (Queued, Processing)
+                | (Queued, Failed)
+                | (Queued, Cancelled)
+                | (Processing, Completed)
+                | (Processing, Failed)
+                | (Processing, Cancelled)
+        )
+    }
+}
+
+/// Errors raised when changing a computation's status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComputationStatusError {
+    IllegalTransition {
+        computation_id: String,
+        from: ComputationStatus,
+        to: ComputationStatus,
+    },
+}
+
+impl std::fmt::Display for ComputationStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComputationStatusError::IllegalTransition {
+                computation_id,
+                from,
+                to,
+            } => write!(
+                f,
+                "Illegal status transition for computation {}: {} -> {}",
+                computation_id, from, to
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ComputationStatusError {}
+
 impl std::fmt::Display for ComputationStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -100,3 +235,242 @@ impl std::fmt::Display for ComputationStatus {
         }
     }
 }
+
+impl std::str::FromStr for ComputationStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "queued" => Ok(ComputationStatus::Queued),
+            "processing" => Ok(ComputationStatus::Processing),
+            "completed" => Ok(ComputationStatus::Completed),
+            "failed" => Ok(ComputationStatus::Failed),
+            "cancelled" => Ok(ComputationStatus::Cancelled),
+            other => Err(format!("Unknown computation status: {}", other)),
+        }
+    }
+}
+
+/// Filters applied when listing a user's computations
+#[derive(Debug, Clone, Default)]
+pub struct ComputationFilter {
+    pub merchant_id: Option<String>,
+    pub status: Option<ComputationStatus>,
+    pub computation_type: Option<ComputationType>,
+    pub entity_type: Option<String>,
+    pub reference_id: Option<String>,
+}
+
+impl ComputationFilter {
+    /// Check whether a computation satisfies every filter that is set
+    pub fn matches(&self, metadata: &ComputationMetadata) -> bool {
+        if self.merchant_id.is_some() && metadata.merchant_id != self.merchant_id {
+            return false;
+        }
+        if let Some(status) = &self.status {
+            if &metadata.status != status {
+                return false;
+            }
+        }
+        if let Some(computation_type) = &self.computation_type {
+            if &metadata.computation_type != computation_type {
+                return false;
+            }
+        }
+        if let Some(entity_type) = &self.entity_type {
+            if metadata.entity_type.as_ref() != Some(entity_type) {
+                return false;
+            }
+        }
+        if let Some(reference_id) = &self.reference_id {
+            if metadata.reference_id.as_ref() != Some(reference_id) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Keyset cursor into a user's computation index (newest first)
+///
+/// Encoded as `{created_at}:{computation_id}`; clients treat it as opaque.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputationCursor {
+    pub created_at: u64,
+    pub computation_id: String,
+}
+
+impl std::fmt::Display for ComputationCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.created_at, self.computation_id)
+    }
+}
+
+impl std::str::FromStr for ComputationCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (created_at, computation_id) = s
+            .split_once(':')
+            .ok_or_else(|| "Cursor must have the form created_at:computation_id".to_string())?;
+        let created_at = created_at
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid cursor timestamp: {}", e))?;
+        if computation_id.is_empty() {
+            return Err("Cursor is missing a computation id".to_string());
+        }
+        Ok(Self {
+            created_at,
+            computation_id: computation_id.to_string(),
+        })
+    }
+}
+
+/// One page of a user's computation history
+#[derive(Debug, Clone, Serialize)]
+pub struct ComputationPage {
+    pub computations: Vec<ComputationMetadata>,
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(status: ComputationStatus) -> ComputationMetadata {
+        ComputationMetadata {
+            computation_id: "comp_1".to_string(),
+            user_pubkey: "user".to_string(),
+            computation_type: ComputationType::ConfidentialTransfer,
+            status,
+            created_at: 1_700_000_000,
+            completed_at: None,
+            callback_url: None,
+            entity_type: Some("payment_intent".to_string()),
+            reference_id: Some("pi_123".to_string()),
+            metadata: serde_json::Value::Null,
+            cluster_tx_signature: None,
+            attestation: None,
+            status_history: Vec::new(),
+            merchant_id: Some("merchant_a".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = ComputationCursor {
+            created_at: 1_700_000_000,
+            computation_id: "comp_1700000000123".to_string(),
+        };
+        let parsed: ComputationCursor = cursor.to_string().parse().unwrap();
+        assert_eq!(parsed, cursor);
+
+        assert!("not-a-cursor".parse::<ComputationCursor>().is_err());
+        assert!("abc:comp_1".parse::<ComputationCursor>().is_err());
+        assert!("123:".parse::<ComputationCursor>().is_err());
+    }
+
+    #[test]
+    fn test_transition_graph() {
+        use ComputationStatus::*;
+
+        assert!(Queued.can_transition_to(&Processing));
+        assert!(Queued.can_transition_to(&Cancelled));
+        assert!(Processing.can_transition_to(&Completed));
+        assert!(Processing.can_transition_to(&Failed));
+
+        // Late or out-of-order updates must not move a job backwards
+        assert!(!Completed.can_transition_to(&Processing));
+        assert!(!Queued.can_transition_to(&Completed));
+        assert!(!Processing.can_transition_to(&Queued));
+
+        // Terminal states are final, including re-entering the same state
+        for terminal in [Completed, Failed, Cancelled] {
+            assert!(terminal.is_terminal());
+            for next in [Queued, Processing, Completed, Failed, Cancelled] {
+                assert!(!terminal.can_transition_to(&next));
+            }
+        }
+    }
+
+    #[test]
+    fn test_transition_records_history() {
+        let mut meta = metadata(ComputationStatus::Queued);
+
+        meta.transition_to(ComputationStatus::Processing, 10)
+            .unwrap();
+        meta.transition_to(ComputationStatus::Completed, 20)
+            .unwrap();
+
+        assert_eq!(meta.status, ComputationStatus::Completed);
+        assert_eq!(meta.completed_at, Some(20));
+        assert_eq!(
+            meta.status_history,
+            vec![
+                StatusTransition {
+                    from: ComputationStatus::Queued,
+                    to: ComputationStatus::Processing,
+                    at: 10,
+                },
+                StatusTransition {
+                    from: ComputationStatus::Processing,
+                    to: ComputationStatus::Completed,
+                    at: 20,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cancelled_cannot_complete() {
+        let mut meta = metadata(ComputationStatus::Cancelled);
+
+        let err = meta
+            .transition_to(ComputationStatus::Completed, 30)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ComputationStatusError::IllegalTransition {
+                computation_id: "comp_1".to_string(),
+                from: ComputationStatus::Cancelled,
+                to: ComputationStatus::Completed,
+            }
+        );
+        assert_eq!(meta.status, ComputationStatus::Cancelled);
+        assert!(meta.status_history.is_empty());
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let meta = metadata(ComputationStatus::Completed);
+
+        assert!(ComputationFilter::default().matches(&meta));
+
+        let filter = ComputationFilter {
+            merchant_id: Some("merchant_a".to_string()),
+            status: Some(ComputationStatus::Completed),
+            computation_type: Some(ComputationType::from_name("encrypted_transfer")),
+            entity_type: Some("payment_intent".to_string()),
+            reference_id: Some("pi_123".to_string()),
+        };
+        assert!(filter.matches(&meta));
+
+        let filter = ComputationFilter {
+            status: Some(ComputationStatus::Failed),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&meta));
+
+        let filter = ComputationFilter {
+            reference_id: Some("pi_456".to_string()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&meta));
+
+        let filter = ComputationFilter {
+            merchant_id: Some("merchant_b".to_string()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&meta));
+    }
+}
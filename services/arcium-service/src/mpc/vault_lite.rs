@@ -0,0 +1,290 @@
+//! Reading ninjapay-vault-lite payment records
+//!
+//! Borsh mirror of the program's `PaymentRecord` and its size limits, and the
+//! merchant-only payment metadata: a memo or invoice reference and the payer
+//! identity, both encrypted before `record_payment` and decrypted here for the
+//! authenticated owner.
+//!
+//! Trust model: the metadata key is derived from the service's
+//! `ENCRYPTION_MASTER_KEY` and the vault owner's pubkey, like every other
+//! [`EncryptionHelper`] key. The ciphertexts keep the metadata from anyone
+//! reading the chain, and a ciphertext made for one owner does not open under
+//! another's key. They do not keep it from the service: whoever holds the
+//! master key can decrypt every merchant's metadata, and the service decides
+//! who to decrypt for by checking the owner's credentials and wallet proof.
+
+use super::encryption::{EncryptionHelper, CIPHERTEXT_OVERHEAD};
+use super::events::PaymentStatus;
+use crate::error::{ServiceError, ServiceResult};
+use borsh::BorshDeserialize;
+use serde::Serialize;
+use solana_sdk::pubkey::{Pubkey, MAX_SEED_LEN};
+
+/// `PaymentRecord::VERSION` the mirror below follows; older records are
+/// skipped until `migrate_payment` upgrades them
+pub const PAYMENT_RECORD_VERSION: u8 = 3;
+/// `MAX_MEMO_SIZE` of the program
+pub const MAX_MEMO_SIZE: usize = 192;
+/// `MAX_PAYER_SIZE` of the program
+pub const MAX_PAYER_SIZE: usize = 96;
+
+/// Longest memo, in bytes, whose ciphertext fits [`MAX_MEMO_SIZE`]
+pub const MAX_MEMO_LEN: usize = MAX_MEMO_SIZE - CIPHERTEXT_OVERHEAD;
+/// Longest payer identity, in bytes, whose ciphertext fits [`MAX_PAYER_SIZE`]
+pub const MAX_PAYER_LEN: usize = MAX_PAYER_SIZE - CIPHERTEXT_OVERHEAD;
+
+/// Mirror of the program's `PaymentRecord`, after the 8-byte discriminator
+#[derive(Debug, Clone, BorshDeserialize)]
+pub struct PaymentRecord {
+    pub version: u8,
+    pub vault: Pubkey,
+    pub payment_id: String,
+    pub encrypted_amount: Vec<u8>,
+    pub recipient: Pubkey,
+    pub timestamp: i64,
+    pub status: PaymentStatus,
+    pub bump: u8,
+    pub mint: Pubkey,
+    pub expires_at: Option<i64>,
+    pub encrypted_refund_amount: Vec<u8>,
+    pub payer: Pubkey,
+    pub updated_at: i64,
+    pub encrypted_memo: Vec<u8>,
+    pub encrypted_payer: Vec<u8>,
+}
+
+impl PaymentRecord {
+    /// Whether `close_payment` accepts the record at `now`: it is settled,
+    /// in the current layout, and `retention` seconds have passed since its
+    /// last status change
+    pub fn is_closable(&self, now: i64, retention: i64) -> bool {
+        self.version == PAYMENT_RECORD_VERSION
+            && self.status != PaymentStatus::Pending
+            && now >= self.updated_at.saturating_add(retention)
+    }
+}
+
+/// Vault PDA of `owner`
+pub fn vault_address(program: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"vault", owner.as_ref()], program).0
+}
+
+/// Payment record PDA of `payment_id` in `vault`
+pub fn payment_address(
+    program: &Pubkey,
+    vault: &Pubkey,
+    payment_id: &str,
+) -> ServiceResult<Pubkey> {
+    if payment_id.is_empty() || payment_id.len() > MAX_SEED_LEN {
+        return Err(ServiceError::Validation(format!(
+            "payment_id must be 1-{} bytes",
+            MAX_SEED_LEN
+        )));
+    }
+    Ok(Pubkey::find_program_address(
+        &[b"payment", vault.as_ref(), payment_id.as_bytes()],
+        program,
+    )
+    .0)
+}
+
+/// Memo and payer identity ciphertexts for `record_payment`
+#[derive(Debug, Clone, Default)]
+pub struct EncryptedPaymentMetadata {
+    pub encrypted_memo: Vec<u8>,
+    pub encrypted_payer: Vec<u8>,
+}
+
+impl EncryptedPaymentMetadata {
+    /// Encrypt under the key of vault owner `owner_pubkey`; absent fields stay
+    /// empty
+    pub fn encrypt(
+        encryption: &EncryptionHelper,
+        owner_pubkey: &str,
+        memo: Option<&str>,
+        payer_identity: Option<&str>,
+    ) -> ServiceResult<Self> {
+        let mut metadata = Self::default();
+        if let Some(memo) = memo {
+            if memo.len() > MAX_MEMO_LEN {
+                return Err(ServiceError::Validation(format!(
+                    "memo must be at most {} bytes",
+                    MAX_MEMO_LEN
+                )));
+            }
+            metadata.encrypted_memo = encryption.encrypt_memo(memo, owner_pubkey)?;
+        }
+        if let Some(identity) = payer_identity {
+            if identity.len() > MAX_PAYER_LEN {
+                return Err(ServiceError::Validation(format!(
+                    "payer_identity must be at most {} bytes",
+                    MAX_PAYER_LEN
+                )));
+            }
+            metadata.encrypted_payer = encryption.encrypt_payer_identity(identity, owner_pubkey)?;
+        }
+        Ok(metadata)
+    }
+}
+
+/// A payment record with its metadata decrypted for the vault owner
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentMetadata {
+    pub payment_id: String,
+    pub payment: String,
+    pub vault: String,
+    pub mint: String,
+    pub recipient: String,
+    pub status: PaymentStatus,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub memo: Option<String>,
+    pub payer_identity: Option<String>,
+}
+
+impl PaymentMetadata {
+    /// Decrypt `record`, stored at `address`, with the key of its vault owner
+    /// `owner_pubkey`
+    pub fn decrypt(
+        record: PaymentRecord,
+        address: &Pubkey,
+        owner_pubkey: &str,
+        encryption: &EncryptionHelper,
+    ) -> ServiceResult<Self> {
+        let memo = match record.encrypted_memo.as_slice() {
+            [] => None,
+            encrypted => Some(encryption.decrypt_memo(encrypted, owner_pubkey)?),
+        };
+        let payer_identity = match record.encrypted_payer.as_slice() {
+            [] => None,
+            encrypted => Some(encryption.decrypt_payer_identity(encrypted, owner_pubkey)?),
+        };
+
+        Ok(Self {
+            payment_id: record.payment_id,
+            payment: address.to_string(),
+            vault: record.vault.to_string(),
+            mint: record.mint.to_string(),
+            recipient: record.recipient.to_string(),
+            status: record.status,
+            created_at: record.timestamp,
+            updated_at: record.updated_at,
+            memo,
+            payer_identity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpc::discriminators::{anchor_account_discriminator, decode_anchor_account};
+    use borsh::BorshSerialize;
+
+    fn payment_record(status: u8, updated_at: i64, metadata: &EncryptedPaymentMetadata) -> Vec<u8> {
+        let mut data = anchor_account_discriminator("PaymentRecord").to_vec();
+        data.extend(
+            (
+                (3u8, Pubkey::new_unique(), "pi_1".to_string(), vec![7u8; 36]),
+                (
+                    Pubkey::new_unique(),
+                    0i64,
+                    status,
+                    254u8,
+                    Pubkey::new_unique(),
+                ),
+                (
+                    Some(10i64),
+                    Vec::<u8>::new(),
+                    Pubkey::new_unique(),
+                    updated_at,
+                ),
+                (
+                    metadata.encrypted_memo.clone(),
+                    metadata.encrypted_payer.clone(),
+                ),
+            )
+                .try_to_vec()
+                .unwrap(),
+        );
+        data.extend(vec![0u8; 64]);
+        data
+    }
+
+    #[test]
+    fn test_metadata_decrypts_for_the_owner_only() {
+        let encryption = EncryptionHelper::new();
+        let owner = Pubkey::new_unique().to_string();
+        let address = Pubkey::new_unique();
+
+        let metadata = EncryptedPaymentMetadata::encrypt(
+            &encryption,
+            &owner,
+            Some("INV-0042"),
+            Some("alice@example.com"),
+        )
+        .unwrap();
+        let record: PaymentRecord =
+            decode_anchor_account(&payment_record(1, 0, &metadata), &address).unwrap();
+
+        let decrypted =
+            PaymentMetadata::decrypt(record.clone(), &address, &owner, &encryption).unwrap();
+        assert_eq!(decrypted.memo.as_deref(), Some("INV-0042"));
+        assert_eq!(
+            decrypted.payer_identity.as_deref(),
+            Some("alice@example.com")
+        );
+        assert_eq!(decrypted.payment, address.to_string());
+
+        let stranger = Pubkey::new_unique().to_string();
+        assert!(PaymentMetadata::decrypt(record, &address, &stranger, &encryption).is_err());
+    }
+
+    #[test]
+    fn test_payment_closable_after_retention() {
+        let address = Pubkey::new_unique();
+        let metadata = EncryptedPaymentMetadata::default();
+
+        // Completed at 1_000 with a one hour retention
+        let completed: PaymentRecord =
+            decode_anchor_account(&payment_record(1, 1_000, &metadata), &address).unwrap();
+        assert_eq!(completed.payment_id, "pi_1");
+        assert!(!completed.is_closable(4_599, 3_600));
+        assert!(completed.is_closable(4_600, 3_600));
+
+        let pending: PaymentRecord =
+            decode_anchor_account(&payment_record(0, 1_000, &metadata), &address).unwrap();
+        assert!(!pending.is_closable(i64::MAX, 0));
+
+        let older = PaymentRecord {
+            version: PAYMENT_RECORD_VERSION - 1,
+            ..completed
+        };
+        assert!(!older.is_closable(i64::MAX, 0));
+    }
+
+    #[test]
+    fn test_metadata_is_optional_and_bounded() {
+        let encryption = EncryptionHelper::new();
+        let owner = "merchant";
+
+        let memo_only =
+            EncryptedPaymentMetadata::encrypt(&encryption, owner, Some("ref"), None).unwrap();
+        assert!(memo_only.encrypted_payer.is_empty());
+
+        let longest = "m".repeat(MAX_MEMO_LEN);
+        let metadata =
+            EncryptedPaymentMetadata::encrypt(&encryption, owner, Some(&longest), None).unwrap();
+        assert_eq!(metadata.encrypted_memo.len(), MAX_MEMO_SIZE);
+
+        let too_long = "m".repeat(MAX_MEMO_LEN + 1);
+        let err = EncryptedPaymentMetadata::encrypt(&encryption, owner, Some(&too_long), None)
+            .unwrap_err();
+        assert_eq!(err.code(), "validation_error");
+
+        let too_long = "p".repeat(MAX_PAYER_LEN + 1);
+        assert!(
+            EncryptedPaymentMetadata::encrypt(&encryption, owner, None, Some(&too_long)).is_err()
+        );
+    }
+}
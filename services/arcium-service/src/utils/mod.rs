@@ -1,8 +1,10 @@
 pub mod redis;
+pub mod redis_topology;
 pub mod secrets;
 // TODO: Add back when we need blockchain interaction
 // pub mod solana;
 
 pub use redis::RedisClient;
+pub use redis_topology::RedisTopology;
 pub use secrets::{hmac_sha256_hex, load_master_key_from_env, load_secret_string};
 // pub use solana::SolanaClient;
@@ -1,165 +1,647 @@
-use crate::mpc::types::{ComputationMetadata, ComputationStatus};
-use redis::{aio::Connection, AsyncCommands, Client};
-use std::error::Error;
+use super::redis_topology::{RedisConnection, RedisTopology};
+use crate::error::{ServiceError, ServiceResult};
+use crate::middleware::auth::ApiKeyRecord;
+use crate::middleware::rate_limit::{BucketLimit, BucketState, QuotaState};
+use crate::mpc::types::{
+    ComputationCursor, ComputationFilter, ComputationMetadata, ComputationPage, ComputationStatus,
+};
+use redis::{AsyncCommands, Script};
+use tokio::sync::OnceCell;
+
+/// Number of index entries fetched per round trip while paging
+const LIST_SCAN_BATCH: isize = 100;
+/// Upper bound on index entries examined for a single filtered page
+const LIST_MAX_SCAN: usize = 1000;
+/// TTL applied to computation metadata on every write
+const METADATA_TTL_SECS: u64 = 3600;
+/// Attempts made by a compare-and-set update before giving up
+const MAX_CAS_ATTEMPTS: usize = 5;
+
+/// Replace a value only if it still holds the expected contents, and in the
+/// same step set a second key when one is given.
+///
+/// KEYS[1] = key, ARGV[1] = expected value, ARGV[2] = new value, ARGV[3] = TTL seconds.
+/// Optional KEYS[2] = second key, ARGV[4] = its value, ARGV[5] = its TTL seconds.
+const COMPARE_AND_SET_SCRIPT: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if current ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+if KEYS[2] then
+    redis.call('SET', KEYS[2], ARGV[4], 'EX', ARGV[5])
+end
+return 1
+"#;
+
+/// Take one token from a bucket refilled continuously at ARGV[2] tokens/sec.
+///
+/// Uses the server clock so every service instance agrees on elapsed time.
+/// KEYS[1] = bucket, ARGV[1] = capacity, ARGV[2] = refill rate.
+/// Returns {allowed, tokens left, milliseconds until the next token}.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate / 1000)
+
+local allowed = 0
+local retry_ms = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    retry_ms = math.ceil((1 - tokens) * 1000 / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity * 1000 / rate) + 1000)
+return {allowed, math.floor(tokens), retry_ms}
+"#;
+
+/// Count one use against a limit, refusing once the limit is reached.
+///
+/// KEYS[1] = counter, ARGV[1] = limit, ARGV[2] = TTL seconds.
+/// Returns {allowed, uses so far}.
+const QUOTA_SCRIPT: &str = r#"
+local used = tonumber(redis.call('GET', KEYS[1]) or '0')
+if used >= tonumber(ARGV[1]) then
+    return {0, used}
+end
+used = redis.call('INCR', KEYS[1])
+if used == 1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[2])
+end
+return {1, used}
+"#;
+
+/// Give back one use counted by `QUOTA_SCRIPT`, never going below zero.
+///
+/// KEYS[1] = counter. Returns the uses left counted.
+const QUOTA_REFUND_SCRIPT: &str = r#"
+if tonumber(redis.call('GET', KEYS[1]) or '0') > 0 then
+    return redis.call('DECR', KEYS[1])
+end
+return 0
+"#;
+
+fn invalid_record(e: serde_json::Error) -> ServiceError {
+    ServiceError::Storage(format!("Invalid record in Redis: {}", e))
+}
+
+// Keys carry a `{...}` hash tag so that, in cluster mode, the `comp:` and
+// `result:` keys of one computation hash to the same slot.
+
+fn metadata_key(computation_id: &str) -> String {
+    format!("comp:{{{}}}", computation_id)
+}
+
+fn result_key(computation_id: &str) -> String {
+    format!("result:{{{}}}", computation_id)
+}
+
+fn quota_key(merchant_id: &str, day: &str) -> String {
+    format!("quota:{{{}}}:{}", merchant_id, day)
+}
+
+fn user_computations_key(user_pubkey: &str) -> String {
+    format!("user:{{{}}}:computations:by_created", user_pubkey)
+}
+
+fn reference_computations_key(entity_type: &str, reference_id: &str) -> String {
+    format!("ref:{{{}:{}}}:computations", entity_type, reference_id)
+}
 
 /// Redis client for caching computation metadata and results
+///
+/// All commands share one connection for the configured [`RedisTopology`]:
+/// a reconnecting multiplexed connection in standalone mode, the current
+/// master in Sentinel mode, or a slot-aware connection in cluster mode. The
+/// connection is created lazily on first use so a Redis outage at boot does
+/// not prevent startup.
 pub struct RedisClient {
-    client: Client,
+    topology: RedisTopology,
+    connection: OnceCell<RedisConnection>,
+    compare_and_set: Script,
+    token_bucket: Script,
+    quota: Script,
+    quota_refund: Script,
 }
 
 impl RedisClient {
-    pub fn new(redis_url: &str) -> Result<Self, Box<dyn Error>> {
-        let client = Client::open(redis_url)?;
-        Ok(Self { client })
+    /// Create a client for a single standalone Redis node
+    pub fn new(redis_url: &str) -> ServiceResult<Self> {
+        // Validate the URL up front; the connection itself is opened lazily
+        redis::Client::open(redis_url)?;
+        Ok(Self::from_topology(RedisTopology::Standalone {
+            url: redis_url.to_string(),
+        }))
     }
 
-    pub async fn get_connection(&self) -> Result<Connection, Box<dyn Error>> {
-        let conn = self.client.get_async_connection().await?;
-        Ok(conn)
+    /// Create a client for any supported topology
+    pub fn from_topology(topology: RedisTopology) -> Self {
+        Self {
+            topology,
+            connection: OnceCell::new(),
+            compare_and_set: Script::new(COMPARE_AND_SET_SCRIPT),
+            token_bucket: Script::new(TOKEN_BUCKET_SCRIPT),
+            quota: Script::new(QUOTA_SCRIPT),
+            quota_refund: Script::new(QUOTA_REFUND_SCRIPT),
+        }
+    }
+
+    /// Get a handle to the shared connection
+    ///
+    /// Handles are cheap to clone and safe to use concurrently.
+    pub async fn get_connection(&self) -> ServiceResult<RedisConnection> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| self.topology.connect())
+            .await?;
+        Ok(connection.clone())
     }
 
     /// Health check - PING command
-    pub async fn ping(&self) -> Result<(), Box<dyn Error>> {
+    pub async fn ping(&self) -> ServiceResult<()> {
         let mut conn = self.get_connection().await?;
         let pong: String = redis::cmd("PING").query_async(&mut conn).await?;
         if pong == "PONG" {
             Ok(())
         } else {
-            Err(format!("Unexpected PING response: {}", pong).into())
+            Err(ServiceError::Storage(format!(
+                "Unexpected PING response: {}",
+                pong
+            )))
         }
     }
 
     /// Store computation metadata
+    ///
+    /// The metadata is written before its user/reference index entries.
+    /// These live in different cluster slots, so they cannot share one
+    /// transaction; readers skip (and prune) index entries whose metadata is
+    /// missing, so the ordering keeps the indexes consistent enough.
     pub async fn store_computation_metadata(
         &self,
         metadata: &ComputationMetadata,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> ServiceResult<()> {
         let mut conn = self.get_connection().await?;
-        let key = format!("comp:{}", metadata.computation_id);
+        let key = metadata_key(&metadata.computation_id);
 
         // Serialize metadata to JSON
-        let json = serde_json::to_string(metadata)?;
-        conn.set_ex(&key, json, 3600).await?; // 1 hour TTL
+        let json = serde_json::to_string(metadata).map_err(invalid_record)?;
+        let _: () = conn.set_ex(&key, json, METADATA_TTL_SECS).await?;
+
+        // Index in the user's timeline, scored by creation time
+        let user_key = user_computations_key(&metadata.user_pubkey);
+        self.add_to_index(&mut conn, &user_key, metadata).await?;
 
-        // Add to user's computation set
-        let user_key = format!("user:{}:computations", metadata.user_pubkey);
-        conn.sadd(&user_key, &metadata.computation_id).await?;
-        conn.expire(&user_key, 86400).await?; // 24 hour TTL
+        // Index by the caller's entity so every attempt for it can be found
+        if let (Some(entity_type), Some(reference_id)) =
+            (&metadata.entity_type, &metadata.reference_id)
+        {
+            let ref_key = reference_computations_key(entity_type, reference_id);
+            self.add_to_index(&mut conn, &ref_key, metadata).await?;
+        }
 
         log::debug!("Stored computation {} in Redis", metadata.computation_id);
         Ok(())
     }
 
+    /// Add a computation to a sorted-set index scored by creation time
+    async fn add_to_index(
+        &self,
+        conn: &mut RedisConnection,
+        index_key: &str,
+        metadata: &ComputationMetadata,
+    ) -> ServiceResult<()> {
+        let _: () = redis::pipe()
+            .atomic()
+            .zadd(index_key, &metadata.computation_id, metadata.created_at)
+            .ignore()
+            .expire(index_key, 86400) // 24 hour TTL
+            .ignore()
+            .query_async(conn)
+            .await?;
+        Ok(())
+    }
+
     /// Retrieve computation metadata
     pub async fn get_computation_metadata(
         &self,
         computation_id: &str,
-    ) -> Result<Option<ComputationMetadata>, Box<dyn Error>> {
+    ) -> ServiceResult<Option<ComputationMetadata>> {
         let mut conn = self.get_connection().await?;
-        let key = format!("comp:{}", computation_id);
+        let key = metadata_key(computation_id);
 
         let json: Option<String> = conn.get(&key).await?;
         match json {
             Some(data) => {
-                let metadata: ComputationMetadata = serde_json::from_str(&data)?;
+                let metadata: ComputationMetadata =
+                    serde_json::from_str(&data).map_err(invalid_record)?;
                 Ok(Some(metadata))
             }
             None => Ok(None),
         }
     }
 
-    /// Store computation result
-    pub async fn store_result(
+    /// Retrieve metadata for many computations in one MGET round trip
+    ///
+    /// The returned vector is positionally aligned with `computation_ids`;
+    /// expired or missing entries are `None`.
+    pub async fn get_computation_metadata_batch(
+        &self,
+        computation_ids: &[String],
+    ) -> ServiceResult<Vec<Option<ComputationMetadata>>> {
+        if computation_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conn = self.get_connection().await?;
+        let keys: Vec<String> = computation_ids.iter().map(|id| metadata_key(id)).collect();
+
+        // Issue MGET explicitly; the typed helper downgrades to GET for a
+        // single key, which changes the reply shape. In cluster mode the
+        // keys are split per slot and the replies recombined in order.
+        let values: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        values
+            .into_iter()
+            .map(|value| match value {
+                Some(data) => Ok(Some(serde_json::from_str(&data).map_err(invalid_record)?)),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Atomically read-modify-write computation metadata
+    ///
+    /// Reads the current metadata, applies `update`, and writes it back with a
+    /// server-side compare-and-set. If another writer changed the record in
+    /// between, the update is re-applied to the fresh value, up to
+    /// `MAX_CAS_ATTEMPTS` times.
+    pub async fn update_computation_metadata<F>(
+        &self,
+        computation_id: &str,
+        update: F,
+    ) -> ServiceResult<ComputationMetadata>
+    where
+        F: FnMut(&mut ComputationMetadata) -> ServiceResult<()>,
+    {
+        self.swap_computation_metadata(computation_id, update, None)
+            .await
+    }
+
+    /// Mark a computation completed and store its result in one atomic step
+    ///
+    /// The result is written only if the transition to `Completed` is legal
+    /// and wins the compare-and-set, so a computation cancelled or completed
+    /// concurrently never has its result stored or overwritten.
+    pub async fn complete_computation(
         &self,
         computation_id: &str,
         result: &[u8],
-        ttl_seconds: usize,
-    ) -> Result<(), Box<dyn Error>> {
+        ttl_seconds: u64,
+    ) -> ServiceResult<ComputationMetadata> {
+        let metadata = self
+            .swap_computation_metadata(
+                computation_id,
+                |metadata| {
+                    let now = chrono::Utc::now().timestamp() as u64;
+                    metadata.transition_to(ComputationStatus::Completed, now)?;
+                    Ok(())
+                },
+                Some((result, ttl_seconds)),
+            )
+            .await?;
+
+        log::info!("Completed computation {} with result", computation_id);
+        Ok(metadata)
+    }
+
+    /// Compare-and-set loop behind [`Self::update_computation_metadata`],
+    /// optionally storing `result` with the winning write
+    async fn swap_computation_metadata<F>(
+        &self,
+        computation_id: &str,
+        mut update: F,
+        result: Option<(&[u8], u64)>,
+    ) -> ServiceResult<ComputationMetadata>
+    where
+        F: FnMut(&mut ComputationMetadata) -> ServiceResult<()>,
+    {
         let mut conn = self.get_connection().await?;
-        let key = format!("result:{}", computation_id);
+        let key = metadata_key(computation_id);
 
-        conn.set_ex(&key, result, ttl_seconds as u64).await?;
+        for attempt in 1..=MAX_CAS_ATTEMPTS {
+            let current: Option<String> = conn.get(&key).await?;
+            let current = current.ok_or_else(|| {
+                ServiceError::NotFound(format!("Computation {} not found", computation_id))
+            })?;
 
-        log::debug!("Stored result for computation {} in Redis", computation_id);
-        Ok(())
+            let mut metadata: ComputationMetadata =
+                serde_json::from_str(&current).map_err(invalid_record)?;
+            update(&mut metadata)?;
+            let updated = serde_json::to_string(&metadata).map_err(invalid_record)?;
+
+            let mut invocation = self.compare_and_set.prepare_invoke();
+            invocation
+                .key(&key)
+                .arg(&current)
+                .arg(&updated)
+                .arg(METADATA_TTL_SECS);
+            if let Some((result, ttl_seconds)) = result {
+                invocation
+                    .key(result_key(computation_id))
+                    .arg(result)
+                    .arg(ttl_seconds);
+            }
+            let swapped: i32 = invocation.invoke_async(&mut conn).await?;
+
+            if swapped == 1 {
+                return Ok(metadata);
+            }
+
+            log::debug!(
+                "Concurrent update on computation {} (attempt {}/{}), retrying",
+                computation_id,
+                attempt,
+                MAX_CAS_ATTEMPTS
+            );
+        }
+
+        Err(ServiceError::Conflict(format!(
+            "Computation {} is being updated concurrently; gave up after {} attempts",
+            computation_id, MAX_CAS_ATTEMPTS
+        )))
     }
 
     /// Retrieve computation result
-    pub async fn get_result(
-        &self,
-        computation_id: &str,
-    ) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    pub async fn get_result(&self, computation_id: &str) -> ServiceResult<Option<Vec<u8>>> {
         let mut conn = self.get_connection().await?;
-        let key = format!("result:{}", computation_id);
+        let key = result_key(computation_id);
 
         let result: Option<Vec<u8>> = conn.get(&key).await?;
         Ok(result)
     }
 
-    /// List computations for a user
+    /// List a user's computations, newest first
+    ///
+    /// Walks the user's sorted-set index from the cursor position, applying
+    /// `filter` to each entry until `limit` matches are found. Index entries
+    /// whose metadata has expired are pruned along the way.
     pub async fn list_user_computations(
         &self,
         user_pubkey: &str,
+        filter: &ComputationFilter,
+        cursor: Option<&ComputationCursor>,
         limit: usize,
-    ) -> Result<Vec<ComputationMetadata>, Box<dyn Error>> {
+    ) -> ServiceResult<ComputationPage> {
         let mut conn = self.get_connection().await?;
-        let user_key = format!("user:{}:computations", user_pubkey);
+        let user_key = user_computations_key(user_pubkey);
 
-        // Get computation IDs from set
-        let computation_ids: Vec<String> = conn.smembers(&user_key).await?;
+        let max_score = cursor
+            .map(|c| c.created_at.to_string())
+            .unwrap_or_else(|| "+inf".to_string());
 
-        // Fetch metadata for each computation
         let mut computations = Vec::new();
-        for id in computation_ids.iter().take(limit) {
-            if let Some(metadata) = self.get_computation_metadata(id).await? {
-                computations.push(metadata);
+        let mut expired = Vec::new();
+        let mut next_cursor = None;
+        let mut offset: usize = 0;
+        let mut scanned: usize = 0;
+
+        'scan: loop {
+            let batch: Vec<(String, f64)> = conn
+                .zrevrangebyscore_limit_withscores(
+                    &user_key,
+                    &max_score,
+                    "-inf",
+                    offset as isize,
+                    LIST_SCAN_BATCH,
+                )
+                .await?;
+            offset += batch.len();
+            let exhausted = batch.len() < LIST_SCAN_BATCH as usize;
+
+            // Entries sharing the cursor's timestamp are ordered by
+            // descending id, so anything at or above the cursor id was
+            // already returned on a previous page.
+            let entries: Vec<(String, u64)> = batch
+                .into_iter()
+                .map(|(id, score)| (id, score as u64))
+                .filter(|(id, created_at)| match cursor {
+                    Some(c) => !(*created_at == c.created_at && *id >= c.computation_id),
+                    None => true,
+                })
+                .collect();
+
+            let ids: Vec<String> = entries.iter().map(|(id, _)| id.clone()).collect();
+            let metadata = self.get_computation_metadata_batch(&ids).await?;
+
+            for ((computation_id, created_at), metadata) in entries.into_iter().zip(metadata) {
+                scanned += 1;
+
+                match metadata {
+                    Some(metadata) if filter.matches(&metadata) => computations.push(metadata),
+                    Some(_) => {}
+                    None => expired.push(computation_id.clone()),
+                }
+
+                if computations.len() >= limit || scanned >= LIST_MAX_SCAN {
+                    next_cursor = Some(
+                        ComputationCursor {
+                            created_at,
+                            computation_id,
+                        }
+                        .to_string(),
+                    );
+                    break 'scan;
+                }
+            }
+
+            if exhausted {
+                break;
             }
         }
 
-        // Sort by created_at (newest first)
-        computations.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        if !expired.is_empty() {
+            let _: () = conn.zrem(&user_key, &expired).await?;
+            log::debug!(
+                "Pruned {} expired computations from {}",
+                expired.len(),
+                user_key
+            );
+        }
+
+        Ok(ComputationPage {
+            computations,
+            next_cursor,
+        })
+    }
+
+    /// List every computation attempt for an entity, newest first
+    pub async fn list_reference_computations(
+        &self,
+        entity_type: &str,
+        reference_id: &str,
+    ) -> ServiceResult<Vec<ComputationMetadata>> {
+        let mut conn = self.get_connection().await?;
+        let ref_key = reference_computations_key(entity_type, reference_id);
+
+        let computation_ids: Vec<String> = conn.zrevrange(&ref_key, 0, -1).await?;
+        let metadata = self
+            .get_computation_metadata_batch(&computation_ids)
+            .await?;
+
+        let mut computations = Vec::with_capacity(computation_ids.len());
+        let mut expired = Vec::new();
+        for (id, metadata) in computation_ids.into_iter().zip(metadata) {
+            match metadata {
+                Some(metadata) => computations.push(metadata),
+                None => expired.push(id),
+            }
+        }
+
+        if !expired.is_empty() {
+            let _: () = conn.zrem(&ref_key, &expired).await?;
+        }
 
         Ok(computations)
     }
 
     /// Update computation status
+    ///
+    /// Enforces the `ComputationStatus` transition graph atomically: the
+    /// check and write happen under a compare-and-set, so a stale update can
+    /// never overwrite a newer terminal status. Illegal transitions fail with
+    /// `ServiceError::Conflict`.
     pub async fn update_computation_status(
         &self,
         computation_id: &str,
         status: ComputationStatus,
-    ) -> Result<(), Box<dyn Error>> {
-        // Get existing metadata
-        let mut metadata = self
-            .get_computation_metadata(computation_id)
-            .await?
-            .ok_or("Computation not found")?;
-
-        // Update status
-        metadata.status = status.clone();
-
-        // Set completed_at if finished
-        if matches!(status, ComputationStatus::Completed) {
-            metadata.completed_at = Some(
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            );
-        }
-
-        // Store updated metadata
-        self.store_computation_metadata(&metadata).await?;
+    ) -> ServiceResult<ComputationMetadata> {
+        let metadata = self
+            .update_computation_metadata(computation_id, |metadata| {
+                let now = chrono::Utc::now().timestamp() as u64;
+                metadata.transition_to(status.clone(), now)?;
+                Ok(())
+            })
+            .await?;
 
         log::info!(
             "Updated computation {} status to: {:?}",
             computation_id,
             status
         );
+        Ok(metadata)
+    }
+
+    /// Look up an API key by the hex SHA-256 of the raw key
+    pub async fn get_api_key(&self, key_hash: &str) -> ServiceResult<Option<ApiKeyRecord>> {
+        let mut conn = self.get_connection().await?;
+        let key = format!("apikey:{}", key_hash);
+
+        let json: Option<String> = conn.get(&key).await?;
+        match json {
+            Some(data) => Ok(Some(serde_json::from_str(&data).map_err(invalid_record)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Record a wallet-signature nonce, returning `false` if it was already used
+    pub async fn claim_nonce(
+        &self,
+        user_pubkey: &str,
+        nonce: &str,
+        ttl_seconds: u64,
+    ) -> ServiceResult<bool> {
+        let mut conn = self.get_connection().await?;
+        let key = format!("nonce:{{{}}}:{}", user_pubkey, nonce);
+
+        let set: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_seconds)
+            .query_async(&mut conn)
+            .await?;
+        Ok(set.is_some())
+    }
+
+    /// Take a token from the rate-limit bucket `rl:{bucket}`
+    pub async fn take_token(
+        &self,
+        bucket: &str,
+        limit: &BucketLimit,
+    ) -> ServiceResult<BucketState> {
+        let mut conn = self.get_connection().await?;
+        let key = format!("rl:{{{}}}", bucket);
+
+        let (allowed, remaining, retry_after_ms): (u8, u64, u64) = self
+            .token_bucket
+            .key(&key)
+            .arg(limit.capacity)
+            .arg(limit.refill_per_sec)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(BucketState {
+            allowed: allowed == 1,
+            limit: limit.capacity,
+            remaining,
+            retry_after_ms,
+        })
+    }
+
+    /// Count one computation against a merchant's quota for `day` (UTC `YYYY-MM-DD`)
+    pub async fn consume_daily_quota(
+        &self,
+        merchant_id: &str,
+        day: &str,
+        limit: u64,
+        reset_at: i64,
+    ) -> ServiceResult<QuotaState> {
+        let mut conn = self.get_connection().await?;
+        let key = quota_key(merchant_id, day);
+
+        // Kept a day past the reset so late readers still see the final count
+        let (allowed, used): (u8, u64) = self
+            .quota
+            .key(&key)
+            .arg(limit)
+            .arg(2 * 24 * 3600)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(QuotaState {
+            allowed: allowed == 1,
+            limit,
+            used,
+            day: day.to_string(),
+            reset_at,
+        })
+    }
+
+    /// Give back a computation counted by [`consume_daily_quota`](Self::consume_daily_quota)
+    pub async fn refund_daily_quota(&self, merchant_id: &str, day: &str) -> ServiceResult<()> {
+        let mut conn = self.get_connection().await?;
+        let _: u64 = self
+            .quota_refund
+            .key(quota_key(merchant_id, day))
+            .invoke_async(&mut conn)
+            .await?;
         Ok(())
     }
 
     /// Health check
-    pub async fn health_check(&self) -> Result<bool, Box<dyn Error>> {
+    pub async fn health_check(&self) -> ServiceResult<bool> {
         let mut conn = self.get_connection().await?;
         let pong: String = redis::cmd("PING").query_async(&mut conn).await?;
         Ok(pong == "PONG")
@@ -169,6 +651,106 @@ impl RedisClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mpc::types::ComputationType;
+
+    fn test_metadata(
+        computation_id: &str,
+        user_pubkey: &str,
+        created_at: u64,
+    ) -> ComputationMetadata {
+        ComputationMetadata {
+            computation_id: computation_id.to_string(),
+            user_pubkey: user_pubkey.to_string(),
+            computation_type: ComputationType::ConfidentialTransfer,
+            status: ComputationStatus::Queued,
+            created_at,
+            completed_at: None,
+            callback_url: None,
+            entity_type: Some("payment_intent".to_string()),
+            reference_id: Some(format!("pi_{}", user_pubkey)),
+            metadata: serde_json::Value::Null,
+            cluster_tx_signature: None,
+            attestation: None,
+            status_history: Vec::new(),
+            merchant_id: None,
+        }
+    }
+
+    /// Store, list, transition and fetch results against a live topology
+    async fn exercise_topology(client: &RedisClient) {
+        let user = format!("topology_test_{}", chrono::Utc::now().timestamp_millis());
+        for i in 0..3 {
+            let metadata = test_metadata(&format!("comp_{}_{}", user, i), &user, 1_000 + i);
+            client.store_computation_metadata(&metadata).await.unwrap();
+        }
+
+        let page = client
+            .list_user_computations(&user, &ComputationFilter::default(), None, 2)
+            .await
+            .unwrap();
+        assert_eq!(page.computations.len(), 2);
+        assert_eq!(page.computations[0].created_at, 1_002);
+        let cursor: ComputationCursor = page.next_cursor.unwrap().parse().unwrap();
+
+        let page = client
+            .list_user_computations(&user, &ComputationFilter::default(), Some(&cursor), 2)
+            .await
+            .unwrap();
+        assert_eq!(page.computations.len(), 1);
+        assert_eq!(page.computations[0].created_at, 1_000);
+
+        let id = format!("comp_{}_0", user);
+        client
+            .update_computation_status(&id, ComputationStatus::Processing)
+            .await
+            .unwrap();
+        client
+            .complete_computation(&id, b"ciphertext", 60)
+            .await
+            .unwrap();
+        assert_eq!(
+            client.get_result(&id).await.unwrap(),
+            Some(b"ciphertext".to_vec())
+        );
+
+        // A second completion is rejected and leaves the stored result alone
+        let err = client
+            .complete_computation(&id, b"overwrite", 60)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "conflict");
+        assert_eq!(
+            client.get_result(&id).await.unwrap(),
+            Some(b"ciphertext".to_vec())
+        );
+
+        // A cancelled computation never gets a result
+        let cancelled = format!("comp_{}_1", user);
+        client
+            .update_computation_status(&cancelled, ComputationStatus::Cancelled)
+            .await
+            .unwrap();
+        assert!(client
+            .complete_computation(&cancelled, b"late", 60)
+            .await
+            .is_err());
+        assert_eq!(client.get_result(&cancelled).await.unwrap(), None);
+
+        let by_ref = client
+            .list_reference_computations("payment_intent", &format!("pi_{}", user))
+            .await
+            .unwrap();
+        assert_eq!(by_ref.len(), 3);
+    }
+
+    #[test]
+    fn test_computation_keys_share_hash_slot() {
+        let id = "comp_1700000000000";
+        assert_eq!(
+            redis::cluster_routing::get_slot(metadata_key(id).as_bytes()),
+            redis::cluster_routing::get_slot(result_key(id).as_bytes())
+        );
+    }
 
     #[tokio::test]
     #[ignore] // Requires Redis instance
@@ -176,4 +758,32 @@ mod tests {
         let client = RedisClient::new("redis://127.0.0.1:6379").unwrap();
         assert!(client.health_check().await.is_ok());
     }
+
+    #[tokio::test]
+    #[ignore] // Requires a Redis Cluster, e.g. docker-compose.redis-test.yml
+    async fn test_cluster_topology() {
+        let nodes = std::env::var("REDIS_CLUSTER_NODES")
+            .unwrap_or_else(|_| "redis://127.0.0.1:7000,redis://127.0.0.1:7001".to_string());
+        let client = RedisClient::from_topology(RedisTopology::Cluster {
+            nodes: nodes.split(',').map(str::to_string).collect(),
+        });
+        assert!(client.health_check().await.unwrap());
+        exercise_topology(&client).await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires Redis Sentinel, e.g. docker-compose.redis-test.yml
+    async fn test_sentinel_topology() {
+        let sentinels = std::env::var("REDIS_SENTINEL_URLS")
+            .unwrap_or_else(|_| "redis://127.0.0.1:26379".to_string());
+        let master_name =
+            std::env::var("REDIS_SENTINEL_MASTER").unwrap_or_else(|_| "mymaster".to_string());
+        let client = RedisClient::from_topology(RedisTopology::Sentinel {
+            sentinels: sentinels.split(',').map(str::to_string).collect(),
+            master_name,
+            node_info: Default::default(),
+        });
+        assert!(client.health_check().await.unwrap());
+        exercise_topology(&client).await;
+    }
 }